@@ -6,7 +6,10 @@ pub use wgpu_glyph::{HorizontalAlign, VerticalAlign};
 pub mod winit {
     pub use winit::{
         dpi::{PhysicalPosition, PhysicalSize},
-        event::{ElementState, Event, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent},
+        event::{
+            ElementState, Event, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta,
+            VirtualKeyCode, WindowEvent,
+        },
         event_loop::{ControlFlow, EventLoop},
         window::{Window, WindowBuilder},
     };
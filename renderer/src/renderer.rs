@@ -1,6 +1,7 @@
 mod coordinates;
 mod fonts;
 mod icon_data;
+mod icons;
 mod image_data;
 mod pipeline_util;
 mod rect_data;
@@ -8,11 +9,15 @@ mod render_device;
 mod render_engine;
 mod render_target;
 mod shapes;
+mod software;
+mod stroke_data;
+mod svg;
 mod text;
 mod uniform_buffer;
 mod vertex_data;
 
 pub use self::{
-    coordinates::*, icon_data::*, image_data::*, rect_data::*, render_engine::RenderEngine,
-    shapes::Shapes, text::*,
+    coordinates::*, icon_data::*, icons::Icons, image_data::*, rect_data::*,
+    render_engine::RenderEngine, render_target::OffscreenError, shapes::{ClipRect, Shapes},
+    software::SoftwareRenderer, stroke_data::Stroke, svg::SvgExporter, text::*,
 };
@@ -1,3 +1,5 @@
+use std::sync::OnceLock;
+
 use wgpu_glyph::{ab_glyph::FontArc, GlyphBrush, GlyphBrushBuilder};
 
 use super::{render_device::RenderDevice, render_target::RenderTarget};
@@ -26,14 +28,25 @@ const FONT_DATA: [(&[u8], usize); 3] = fonts!(
     "fonts/Ubuntu-Bold.ttf" => FONT_BOLD,
 );
 
+/// Every font, indexed by style.
+pub(super) fn load_fonts() -> Vec<FontArc> {
+    let mut fonts = vec![];
+    for (index, (data, expected_style)) in FONT_DATA.into_iter().enumerate() {
+        assert_eq!(index, expected_style);
+        fonts.push(FontArc::try_from_slice(data).unwrap());
+    }
+    fonts
+}
+
+/// Every font, loaded the first time text is measured outside of a renderer.
+pub(super) fn shared_fonts() -> &'static [FontArc] {
+    static FONTS: OnceLock<Vec<FontArc>> = OnceLock::new();
+    FONTS.get_or_init(load_fonts)
+}
+
 impl Fonts {
     pub fn new(device: &RenderDevice, target: &RenderTarget) -> Self {
-        let mut fonts = vec![];
-        for (index, (data, expected_style)) in FONT_DATA.into_iter().enumerate() {
-            assert_eq!(index, expected_style);
-            fonts.push(FontArc::try_from_slice(data).unwrap());
-        }
-
+        let fonts = load_fonts();
         Self {
             regular: GlyphBrushBuilder::using_fonts(fonts).build(device.device(), target.format()),
         }
@@ -1,6 +1,11 @@
 use bytemuck::{Pod, Zeroable};
 use wgpu::{VertexAttribute, VertexBufferLayout, VertexStepMode};
 
+/// Icons are rasterized into a grid of cells in a square atlas this big,
+/// see rasterize_icon_atlas.
+pub(super) const ICON_ATLAS_SIZE: u32 = 512;
+pub(super) const ICON_CELL_SIZE: u32 = 64;
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Pod, Zeroable)]
 pub struct IconInstance {
@@ -0,0 +1,176 @@
+use resvg::{
+    tiny_skia::{Pixmap, Transform},
+    usvg::{Options, Tree},
+};
+
+use super::icon_data::{ICON_ATLAS_SIZE, ICON_CELL_SIZE};
+
+/// The icons that are built into the atlas, in the order of their indices.
+/// Adding one only takes a line here.
+const ICON_SOURCES: &[(&str, &str)] = &[
+    (
+        "arrow-down",
+        include_str!("../../../svg_icons/arrow-down.svg"),
+    ),
+    (
+        "arrow-left-right",
+        include_str!("../../../svg_icons/arrow-left-right.svg"),
+    ),
+    ("arrow-up", include_str!("../../../svg_icons/arrow-up.svg")),
+    (
+        "axis-arrow",
+        include_str!("../../../svg_icons/axis-arrow.svg"),
+    ),
+    (
+        "content-copy",
+        include_str!("../../../svg_icons/content-copy.svg"),
+    ),
+    (
+        "cursor-move",
+        include_str!("../../../svg_icons/cursor-move.svg"),
+    ),
+    ("delete", include_str!("../../../svg_icons/delete.svg")),
+    ("eye", include_str!("../../../svg_icons/eye.svg")),
+    (
+        "eye-closed",
+        include_str!("../../../svg_icons/eye-closed.svg"),
+    ),
+    (
+        "eyedropper",
+        include_str!("../../../svg_icons/eyedropper.svg"),
+    ),
+    (
+        "map-marker",
+        include_str!("../../../svg_icons/map-marker.svg"),
+    ),
+    (
+        "menu-right",
+        include_str!("../../../svg_icons/menu-right.svg"),
+    ),
+    ("pin", include_str!("../../../svg_icons/pin.svg")),
+    ("pin-off", include_str!("../../../svg_icons/pin-off.svg")),
+    (
+        "plus-minus",
+        include_str!("../../../svg_icons/plus-minus.svg"),
+    ),
+    ("pound", include_str!("../../../svg_icons/pound.svg")),
+    (
+        "rotate-right",
+        include_str!("../../../svg_icons/rotate-right.svg"),
+    ),
+    (
+        "table-row-plus-after",
+        include_str!("../../../svg_icons/table-row-plus-after.svg"),
+    ),
+    (
+        "table-row-plus-before",
+        include_str!("../../../svg_icons/table-row-plus-before.svg"),
+    ),
+    (
+        "toggle-switch",
+        include_str!("../../../svg_icons/toggle-switch.svg"),
+    ),
+];
+
+/// Looks up icons by the name of their SVG file, without the extension.
+pub struct Icons;
+
+impl Icons {
+    /// The index to use in an IconInstance to draw the named icon.
+    pub fn get(name: &str) -> i32 {
+        Self::find(name).unwrap_or_else(|| panic!("There is no icon named {}", name))
+    }
+
+    /// Like get, but returns None for names that are not built in, such as
+    /// ones read from a file.
+    pub fn find(name: &str) -> Option<i32> {
+        ICON_SOURCES
+            .iter()
+            .position(|&(source_name, _)| source_name == name)
+            .map(|index| index as i32)
+    }
+
+    /// The names of all icons, in the order of their indices.
+    pub fn names() -> impl Iterator<Item = &'static str> {
+        ICON_SOURCES.iter().map(|&(name, _)| name)
+    }
+}
+
+/// Rasterizes every icon into its own cell of the atlas, left to right and
+/// then top to bottom. Returns the alpha of every pixel, starting at the top
+/// left.
+pub(super) fn rasterize_icon_atlas() -> Vec<u8> {
+    let icons_per_row = (ICON_ATLAS_SIZE / ICON_CELL_SIZE) as usize;
+    assert!(ICON_SOURCES.len() <= icons_per_row * icons_per_row);
+    let size = ICON_ATLAS_SIZE as usize;
+    let cell = ICON_CELL_SIZE as usize;
+    let mut alphas = vec![0; size * size];
+    for (index, (name, source)) in ICON_SOURCES.iter().enumerate() {
+        let icon = rasterize_icon(source).unwrap_or_else(|err| panic!("The {} icon {}", name, err));
+        let left = (index % icons_per_row) * cell;
+        let top = (index / icons_per_row) * cell;
+        for (row, pixels) in icon.pixels().chunks(cell).enumerate() {
+            let start = (top + row) * size + left;
+            for (alpha, pixel) in alphas[start..start + cell].iter_mut().zip(pixels) {
+                *alpha = pixel.alpha();
+            }
+        }
+    }
+    alphas
+}
+
+/// Draws an SVG scaled to fill one cell of the atlas.
+fn rasterize_icon(source: &str) -> Result<Pixmap, String> {
+    let tree = Tree::from_str(source, &Options::default())
+        .map_err(|err| format!("could not be read: {}", err))?;
+    let cell = ICON_CELL_SIZE as f32;
+    let size = tree.size();
+    let mut pixmap = Pixmap::new(ICON_CELL_SIZE, ICON_CELL_SIZE).unwrap();
+    let transform = Transform::from_scale(cell / size.width(), cell / size.height());
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+    Ok(pixmap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// How many pixels of a cell an icon covers at least half of.
+    fn covered_pixels(icon: &Pixmap) -> usize {
+        icon.pixels()
+            .iter()
+            .filter(|pixel| pixel.alpha() >= 128)
+            .count()
+    }
+
+    #[test]
+    fn every_icon_draws_something() {
+        for (name, source) in ICON_SOURCES {
+            let icon = rasterize_icon(source).unwrap();
+            assert!(covered_pixels(&icon) > 0, "The {} icon is empty", name);
+        }
+    }
+
+    #[test]
+    fn transforms_are_applied() {
+        let square = |transform: &str| {
+            format!(
+                r#"<svg viewBox="0 0 24 24" xmlns="http://www.w3.org/2000/svg"><path d="M 0,0 H 12 V 12 H 0 Z" transform="{}"/></svg>"#,
+                transform
+            )
+        };
+        let plain = rasterize_icon(&square("")).unwrap();
+        let moved = rasterize_icon(&square("translate(12 12)")).unwrap();
+        let cell = ICON_CELL_SIZE as usize;
+        let alpha = |icon: &Pixmap, x: usize, y: usize| icon.pixels()[y * cell + x].alpha();
+        assert_eq!(alpha(&plain, cell / 4, cell / 4), 255);
+        assert_eq!(alpha(&plain, cell * 3 / 4, cell * 3 / 4), 0);
+        assert_eq!(alpha(&moved, cell / 4, cell / 4), 0);
+        assert_eq!(alpha(&moved, cell * 3 / 4, cell * 3 / 4), 255);
+    }
+
+    #[test]
+    fn invalid_icons_are_errors() {
+        assert!(rasterize_icon("<svg").is_err());
+    }
+}
@@ -1,6 +1,9 @@
 use bytemuck::{Pod, Zeroable};
 use wgpu::{VertexAttribute, VertexBufferLayout, VertexStepMode};
 
+/// How many images can be uploaded with RenderEngine::upload_image at once.
+pub const NUM_IMAGES: usize = 16;
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Pod, Zeroable)]
 pub struct ImageInstance {
@@ -9,12 +9,21 @@ pub struct RectInstance {
     pub fill_color: [f32; 3],
     pub outline_color: [f32; 3],
     pub outline_modes: u32,
+    pub outline_thickness: f32,
+    /// How far each corner is rounded, or cut off if it is chamfered in
+    /// outline_modes. Starts at the bottom left and goes counterclockwise.
+    pub corner_sizes: [f32; 4],
+    pub shadow_offset: [f32; 2],
+    /// How far the shadow fades out past the rect. No shadow is drawn if
+    /// this is 0.
+    pub shadow_size: f32,
+    pub shadow_opacity: f32,
 }
 
 // 0 produces no outline and is the default.
-const OUTLINE_MODE_FLAT: u32 = 1;
-const OUTLINE_MODE_DIAGONAL: u32 = 2;
-const OUTLINE_MODE_ANTIDIAGONAL: u32 = 3;
+pub(super) const OUTLINE_MODE_FLAT: u32 = 1;
+pub(super) const OUTLINE_MODE_DIAGONAL: u32 = 2;
+pub(super) const OUTLINE_MODE_ANTIDIAGONAL: u32 = 3;
 
 pub const LEFT_OUTLINE_FLAT: u32 = OUTLINE_MODE_FLAT << 0;
 pub const LEFT_OUTLINE_DIAGONAL: u32 = OUTLINE_MODE_DIAGONAL << 0;
@@ -23,16 +32,52 @@ pub const RIGHT_OUTLINE_FLAT: u32 = OUTLINE_MODE_FLAT << 2;
 pub const RIGHT_OUTLINE_DIAGONAL: u32 = OUTLINE_MODE_DIAGONAL << 2;
 pub const RIGHT_OUTLINE_ANTIDIAGONAL: u32 = OUTLINE_MODE_ANTIDIAGONAL << 2;
 pub const BOTTOM_OUTLINE_FLAT: u32 = OUTLINE_MODE_FLAT << 4;
-pub const TOP_OUTLINE_FLAT: u32 = OUTLINE_MODE_FLAT << 5;
+pub const BOTTOM_OUTLINE_DIAGONAL: u32 = OUTLINE_MODE_DIAGONAL << 4;
+pub const BOTTOM_OUTLINE_ANTIDIAGONAL: u32 = OUTLINE_MODE_ANTIDIAGONAL << 4;
+pub const TOP_OUTLINE_FLAT: u32 = OUTLINE_MODE_FLAT << 6;
+pub const TOP_OUTLINE_DIAGONAL: u32 = OUTLINE_MODE_DIAGONAL << 6;
+pub const TOP_OUTLINE_ANTIDIAGONAL: u32 = OUTLINE_MODE_ANTIDIAGONAL << 6;
+
+// Corners are rounded unless these are set.
+pub const BOTTOM_LEFT_CORNER_CHAMFER: u32 = 1 << 8;
+pub const BOTTOM_RIGHT_CORNER_CHAMFER: u32 = 1 << 9;
+pub const TOP_RIGHT_CORNER_CHAMFER: u32 = 1 << 10;
+pub const TOP_LEFT_CORNER_CHAMFER: u32 = 1 << 11;
+
+/// Where the edges of a rect are once its outline modes are applied, as an
+/// offset and a slope. The left and right edges are at x = offset + slope *
+/// y, the bottom and top ones at y = offset + slope * x. Follows
+/// rect_shader.wgsl.
+pub(super) struct RectEdges {
+    pub(super) left: [f32; 2],
+    pub(super) right: [f32; 2],
+    pub(super) bottom: [f32; 2],
+    pub(super) top: [f32; 2],
+}
+
+impl Default for RectInstance {
+    /// A black rect at the origin without outlines, corners or shadow.
+    fn default() -> Self {
+        Self {
+            outline_thickness: 1.0,
+            ..Zeroable::zeroed()
+        }
+    }
+}
 
 impl RectInstance {
     pub fn desc() -> VertexBufferLayout<'static> {
-        const ATTRS: [VertexAttribute; 5] = wgpu::vertex_attr_array![
+        const ATTRS: [VertexAttribute; 10] = wgpu::vertex_attr_array![
             1 => Float32x2,
             2 => Float32x2,
             3 => Float32x3,
             4 => Float32x3,
             5 => Uint32,
+            6 => Float32,
+            7 => Float32x4,
+            8 => Float32x2,
+            9 => Float32,
+            10 => Float32,
         ];
         VertexBufferLayout {
             array_stride: std::mem::size_of::<Self>() as _,
@@ -40,4 +85,62 @@ impl RectInstance {
             attributes: &ATTRS,
         }
     }
+
+    /// The outline modes of the left, right, bottom and top edges.
+    pub(super) fn side_modes(&self) -> [u32; 4] {
+        [0, 2, 4, 6].map(|shift| (self.outline_modes >> shift) & 0x3)
+    }
+
+    pub(super) fn edges(&self) -> RectEdges {
+        let [start_x, start_y] = self.position;
+        let end_x = start_x + self.size[0];
+        let end_y = start_y + self.size[1];
+        let [left, right, bottom, top] = self.side_modes();
+        RectEdges {
+            left: match left {
+                OUTLINE_MODE_DIAGONAL => [start_x - start_y, 1.0],
+                OUTLINE_MODE_ANTIDIAGONAL => [start_x + end_y - 1.0, -1.0],
+                _ => [start_x, 0.0],
+            },
+            right: match right {
+                OUTLINE_MODE_DIAGONAL => [end_x - end_y, 1.0],
+                OUTLINE_MODE_ANTIDIAGONAL => [end_x + start_y, -1.0],
+                _ => [end_x, 0.0],
+            },
+            bottom: match bottom {
+                OUTLINE_MODE_DIAGONAL => [start_y - start_x, 1.0],
+                OUTLINE_MODE_ANTIDIAGONAL => [start_y + end_x - 1.0, -1.0],
+                _ => [start_y, 0.0],
+            },
+            top: match top {
+                OUTLINE_MODE_DIAGONAL => [end_y - end_x, 1.0],
+                OUTLINE_MODE_ANTIDIAGONAL => [end_y + start_x, -1.0],
+                _ => [end_y, 0.0],
+            },
+        }
+    }
+
+    pub(super) fn corner_is_chamfered(&self, corner: usize) -> bool {
+        (self.outline_modes >> (8 + corner)) & 1 == 1
+    }
+
+    /// Corners are outlined if either of the edges next to them are.
+    pub(super) fn corner_is_outlined(&self, corner: usize) -> bool {
+        let [left, right, bottom, top] = self.side_modes().map(|mode| mode > 0);
+        [left || bottom, right || bottom, right || top, left || top][corner]
+    }
+
+    /// How far a point is from the vertical and horizontal edges of a
+    /// corner, counting towards the inside of the rect.
+    pub(super) fn corner_distances(&self, corner: usize, [x, y]: [f32; 2]) -> [f32; 2] {
+        let [start_x, start_y] = self.position;
+        let end_x = start_x + self.size[0];
+        let end_y = start_y + self.size[1];
+        match corner {
+            0 => [x - start_x, y - start_y],
+            1 => [end_x - x, y - start_y],
+            2 => [end_x - x, end_y - y],
+            _ => [x - start_x, end_y - y],
+        }
+    }
 }
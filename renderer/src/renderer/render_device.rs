@@ -1,8 +1,10 @@
 use wgpu::{
-    Device, DeviceDescriptor, Features, Instance, Limits, Queue, RequestAdapterOptions, Surface,
-    TextureFormat,
+    Device, DeviceDescriptor, Features, Instance, Limits, Queue, RequestAdapterOptions,
+    RequestDeviceError, Surface, TextureFormat,
 };
 
+use super::render_target::OffscreenError;
+
 pub struct RenderDevice {
     device: Device,
     queue: Queue,
@@ -15,20 +17,42 @@ impl RenderDevice {
             ..Default::default()
         };
         let adapter = instance.request_adapter(&options).await.unwrap();
-        let (device, queue) = Self::create_device_and_queue(&adapter).await;
+        let (device, queue) = Self::create_device_and_queue(&adapter).await.unwrap();
         (
             Self { device, queue },
             surface.get_supported_formats(&adapter)[0],
         )
     }
 
-    async fn create_device_and_queue(adapter: &wgpu::Adapter) -> (Device, Queue) {
+    /// Picks an adapter without needing a surface to be compatible with,
+    /// falling back to a software one if there is no GPU.
+    pub async fn new_offscreen(instance: &Instance) -> Result<Self, OffscreenError> {
+        let mut options = RequestAdapterOptions::default();
+        let adapter = match instance.request_adapter(&options).await {
+            Some(adapter) => adapter,
+            None => {
+                options.force_fallback_adapter = true;
+                instance
+                    .request_adapter(&options)
+                    .await
+                    .ok_or(OffscreenError::NoAdapter)?
+            }
+        };
+        let (device, queue) = Self::create_device_and_queue(&adapter)
+            .await
+            .map_err(OffscreenError::Device)?;
+        Ok(Self { device, queue })
+    }
+
+    async fn create_device_and_queue(
+        adapter: &wgpu::Adapter,
+    ) -> Result<(Device, Queue), RequestDeviceError> {
         let desc = DeviceDescriptor {
             features: Features::empty(),
             limits: Self::limits(),
             label: Some("UI Device"),
         };
-        adapter.request_device(&desc, None).await.unwrap()
+        adapter.request_device(&desc, None).await
     }
 
     fn limits() -> Limits {
@@ -4,6 +4,7 @@ mod render;
 mod render_icons;
 mod render_images;
 mod render_rects;
+mod render_strokes;
 mod render_text;
 
 use std::num::NonZeroU32;
@@ -11,20 +12,26 @@ use std::num::NonZeroU32;
 use theme::PREVIEW_TEXTURE_SIZE;
 use wgpu::{
     util::StagingBelt, BindGroup, Buffer, CommandEncoder, ImageCopyTexture, ImageDataLayout,
-    Origin3d, RenderPipeline, Texture, TextureAspect, TextureView,
+    Origin3d, RenderPass, RenderPipeline, Texture, TextureAspect, TextureView,
 };
 
-use super::{fonts::Fonts, render_device::RenderDevice, render_target::RenderTarget, Shapes};
+use super::{
+    coordinates::Size, fonts::Fonts, image_data::NUM_IMAGES, render_device::RenderDevice,
+    render_target::RenderTarget, shapes::ClipRect, Shapes,
+};
 
 struct ReadOnlyResources {
     device: RenderDevice,
     target: RenderTarget,
     rect_verts: Buffer,
     rect_pipeline: RenderPipeline,
+    stroke_pipeline: RenderPipeline,
     icon_pipeline: RenderPipeline,
     icon_texture_bind_group: BindGroup,
     image_pipeline: RenderPipeline,
-    image_textures: [(Texture, BindGroup); 16],
+    image_textures: [(Texture, BindGroup); NUM_IMAGES],
+    /// What the target is cleared to before drawing.
+    background: [f32; 4],
 }
 
 struct MutableResources {
@@ -67,4 +74,22 @@ impl RenderEngine {
             },
         );
     }
+
+    pub fn set_background(&mut self, color: [f32; 4]) {
+        self.ror.background = color;
+    }
+}
+
+/// Restricts drawing to the clip rect, or to the whole target if there is
+/// none. Returns false if nothing would be drawn at all.
+fn set_clip(render_pass: &mut RenderPass, clip: Option<ClipRect>, target: Size) -> bool {
+    let Some(clip) = clip else {
+        render_pass.set_scissor_rect(0, 0, target.width as u32, target.height as u32);
+        return true;
+    };
+    let Some([x, y, width, height]) = clip.scissor(target) else {
+        return false;
+    };
+    render_pass.set_scissor_rect(x, y, width, height);
+    true
 }
@@ -1,6 +1,6 @@
 use std::num::NonZeroU32;
 
-use theme::{PREVIEW_WIDGET_SIZE, PREVIEW_TEXTURE_SIZE};
+use theme::{Theme, PREVIEW_TEXTURE_SIZE};
 use wgpu::{
     util::StagingBelt, AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry,
     BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType,
@@ -14,20 +14,22 @@ use super::{MutableResources, ReadOnlyResources, RenderEngine};
 use crate::renderer::{
     coordinates::Size,
     fonts::Fonts,
-    icon_data::IconInstance,
+    icon_data::{IconInstance, ICON_ATLAS_SIZE},
+    icons::rasterize_icon_atlas,
+    image_data::NUM_IMAGES,
     pipeline_util::{create_render_pipeline, create_shader},
     rect_data::RectInstance,
     render_device::RenderDevice,
-    render_target::RenderTarget,
+    render_target::{OffscreenError, RenderTarget},
+    stroke_data::StrokeSegmentInstance,
     vertex_data::{create_rect_verts_buffer, Vertex},
 };
 
 fn create_icon_texture(device: &RenderDevice) -> (BindGroupLayout, BindGroup) {
-    let image = image::load_from_memory(include_bytes!("icons.png")).unwrap();
-    let image = image.to_luma8();
+    let atlas = rasterize_icon_atlas();
     let texture_size = Extent3d {
-        width: 2048,
-        height: 2048,
+        width: ICON_ATLAS_SIZE,
+        height: ICON_ATLAS_SIZE,
         ..Default::default()
     };
     let desc = TextureDescriptor {
@@ -47,11 +49,11 @@ fn create_icon_texture(device: &RenderDevice) -> (BindGroupLayout, BindGroup) {
             origin: Origin3d::ZERO,
             aspect: TextureAspect::All,
         },
-        &image,
+        &atlas,
         ImageDataLayout {
             offset: 0,
-            bytes_per_row: NonZeroU32::new(2048),
-            rows_per_image: NonZeroU32::new(2048),
+            bytes_per_row: NonZeroU32::new(ICON_ATLAS_SIZE),
+            rows_per_image: NonZeroU32::new(ICON_ATLAS_SIZE),
         },
         texture_size,
     );
@@ -105,8 +107,6 @@ fn create_icon_texture(device: &RenderDevice) -> (BindGroupLayout, BindGroup) {
     (texture_bind_group_layout, texture_bind_group)
 }
 
-const NUM_IMAGES: usize = 16;
-
 fn create_image_textures(
     device: &RenderDevice,
 ) -> (BindGroupLayout, [(Texture, BindGroup); NUM_IMAGES]) {
@@ -185,6 +185,17 @@ fn create_image_textures(
 impl RenderEngine {
     pub async fn new_for_window(window: &Window) -> Self {
         let (target, device) = RenderTarget::new_for_window(window).await;
+        Self::new_for_target(target, device)
+    }
+
+    /// Creates an engine that renders into a texture of the given size,
+    /// which render_to_image reads back.
+    pub async fn new_offscreen(size: Size) -> Result<Self, OffscreenError> {
+        let (target, device) = RenderTarget::new_offscreen(size).await?;
+        Ok(Self::new_for_target(target, device))
+    }
+
+    fn new_for_target(target: RenderTarget, device: RenderDevice) -> Self {
         let rect_shader = create_shader("Rect Shader", include_str!("rect_shader.wgsl"), &device);
         let rect_verts = create_rect_verts_buffer(&device);
         let rect_pipeline = create_render_pipeline(
@@ -195,6 +206,16 @@ impl RenderEngine {
             &device,
             &target,
         );
+        let stroke_shader =
+            create_shader("Stroke Shader", include_str!("stroke_shader.wgsl"), &device);
+        let stroke_pipeline = create_render_pipeline(
+            "Stroke Pipeline",
+            &stroke_shader,
+            &[target.surface_geometry_bind_group_layout()],
+            &[Vertex::desc(), StrokeSegmentInstance::desc()],
+            &device,
+            &target,
+        );
         let (icon_texture_bind_group_layout, icon_texture_bind_group) =
             create_icon_texture(&device);
         let icon_shader = create_shader("Icon Shader", include_str!("icon_shader.wgsl"), &device);
@@ -231,10 +252,12 @@ impl RenderEngine {
                 target,
                 rect_verts,
                 rect_pipeline,
+                stroke_pipeline,
                 icon_pipeline,
                 icon_texture_bind_group,
                 image_pipeline,
                 image_textures,
+                background: Theme::dark().background,
             },
             mr: MutableResources {
                 staging_belt,
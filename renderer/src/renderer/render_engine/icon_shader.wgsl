@@ -30,7 +30,7 @@ var icon_sampler: sampler;
 fn vertex_shader(vert: VertexInput, icon: IconInstance) -> VertexOutput {
     var out: VertexOutput;
 
-    let icons_per_row = 2048u / 64u;
+    let icons_per_row = 512u / 64u;
     let uv = vec2<f32>(
         vert.position.x + f32(icon.index % icons_per_row),
         1.0 - vert.position.y + f32(icon.index / icons_per_row),
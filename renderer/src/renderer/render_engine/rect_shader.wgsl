@@ -8,6 +8,11 @@ struct RectInstance {
     @location(3) fill_color: vec3<f32>,
     @location(4) outline_color: vec3<f32>,
     @location(5) border_modes: u32,
+    @location(6) outline_thickness: f32,
+    @location(7) corner_sizes: vec4<f32>,
+    @location(8) shadow_offset: vec2<f32>,
+    @location(9) shadow_size: f32,
+    @location(10) shadow_opacity: f32,
 };
 
 struct Screen {
@@ -23,6 +28,11 @@ struct VertexOutput {
     @location(3) fill_color: vec3<f32>,
     @location(4) outline_color: vec3<f32>,
     @location(5) border_modes: u32,
+    @location(6) outline_thickness: f32,
+    @location(7) corner_sizes: vec4<f32>,
+    @location(8) shadow_offset: vec2<f32>,
+    @location(9) shadow_size: f32,
+    @location(10) shadow_opacity: f32,
 };
 
 @group(0) @binding(0)
@@ -35,8 +45,18 @@ fn vertex_shader(vert: VertexInput, rect: RectInstance) -> VertexOutput {
     out.fill_color = rect.fill_color;
     out.outline_color = rect.outline_color;
     out.border_modes = rect.border_modes;
-
-    out.position = vert.position * rect.size + rect.position;
+    out.outline_thickness = rect.outline_thickness;
+    out.corner_sizes = rect.corner_sizes;
+    out.shadow_offset = rect.shadow_offset;
+    out.shadow_size = rect.shadow_size;
+    out.shadow_opacity = rect.shadow_opacity;
+
+    // The shadow can reach past the rect.
+    var margin = 0.0;
+    if rect.shadow_size > 0.0 {
+        margin = rect.shadow_size + max(abs(rect.shadow_offset.x), abs(rect.shadow_offset.y));
+    }
+    out.position = vert.position * (rect.size + 2.0 * margin) + rect.position - margin;
     let x = out.position.x / screen.width * 2.0 - 1.0;
     let y = out.position.y / screen.height * 2.0 - 1.0;
     out.clip_position = vec4<f32>(x, y, 0.0, 1.0);
@@ -54,11 +74,13 @@ fn fragment_shader(in: VertexOutput) -> @location(0) vec4<f32> {
 
     let left_border_mode = in.border_modes & 0x3u;
     let right_border_mode = (in.border_modes >> 2u) & 0x3u;
-    let bottom_border_mode = (in.border_modes >> 4u) & 0x1u;
-    let top_border_mode = (in.border_modes >> 5u) & 0x1u;
+    let bottom_border_mode = (in.border_modes >> 4u) & 0x3u;
+    let top_border_mode = (in.border_modes >> 6u) & 0x3u;
 
     var left = in.start.x;
     var right = in.end.x;
+    var bottom = in.start.y;
+    var top = in.end.y;
 
     if left_border_mode == 2u {
         // Diagonal mode.
@@ -76,18 +98,93 @@ fn fragment_shader(in: VertexOutput) -> @location(0) vec4<f32> {
         right -= y - in.start.y;
     }
 
-    let thickness = 1.0;
+    if bottom_border_mode == 2u {
+        // Diagonal mode.
+        bottom += x - in.start.x;
+    } else if bottom_border_mode == 3u {
+        // Antidiagonal mode.
+        bottom += in.end.x - x - 1.0;
+    }
+
+    if top_border_mode == 2u {
+        // Diagonal mode.
+        top -= in.end.x - x;
+    } else if top_border_mode == 3u {
+        // Antidiagonal mode.
+        top -= x - in.start.x;
+    }
+
+    let thickness = in.outline_thickness;
+
+    // Without a shadow, the quad already covers only the rect.
+    let in_rect = all(in.position >= in.start) && all(in.position < in.end);
+    var inside = in_rect && x >= left && x <= right && y >= bottom && y <= top;
+    var outline = (left_border_mode > 0u && x <= left + thickness)
+        || (right_border_mode > 0u && x >= right - thickness)
+        || (bottom_border_mode > 0u && y <= bottom + thickness)
+        || (top_border_mode > 0u && y >= top - thickness);
+
+    // Corners go counterclockwise from the bottom left. Each one is outlined
+    // if either edge next to it is.
+    var horizontal_distances = array<f32, 4>(
+        x - in.start.x,
+        in.end.x - x,
+        in.end.x - x,
+        x - in.start.x,
+    );
+    var vertical_distances = array<f32, 4>(
+        y - in.start.y,
+        y - in.start.y,
+        in.end.y - y,
+        in.end.y - y,
+    );
+    var corner_outlined = array<bool, 4>(
+        left_border_mode > 0u || bottom_border_mode > 0u,
+        right_border_mode > 0u || bottom_border_mode > 0u,
+        right_border_mode > 0u || top_border_mode > 0u,
+        left_border_mode > 0u || top_border_mode > 0u,
+    );
+    var corner_sizes = in.corner_sizes;
+    for (var corner = 0; corner < 4; corner++) {
+        let size = corner_sizes[corner];
+        let u = horizontal_distances[corner];
+        let v = vertical_distances[corner];
+        let chamfer = ((in.border_modes >> (8u + u32(corner))) & 1u) == 1u;
+        if size <= 0.0 {
+            continue;
+        }
+        if chamfer {
+            inside = inside && u + v >= size;
+            outline = outline || (corner_outlined[corner] && u + v < size + thickness * sqrt(2.0));
+        } else if u < size && v < size {
+            let distance = length(vec2<f32>(size - u, size - v));
+            inside = inside && distance <= size;
+            outline = outline || (corner_outlined[corner] && distance > size - thickness);
+        }
+    }
 
-    if x >= left && x <= right {
-        if (left_border_mode > 0u && x <= left + thickness)
-            || (right_border_mode > 0u && x >= right - thickness)
-            || (bottom_border_mode > 0u && y <= in.start.y + thickness)
-            || (top_border_mode > 0u && y >= in.end.y - thickness) {
+    if inside {
+        if outline {
             return vec4<f32>(in.outline_color, 1.0);
         } else {
             return vec4<f32>(in.fill_color, 1.0);
         }
-    } else {
-        return vec4<f32>(0.0, 0.0, 0.0, 0.0);
     }
+
+    if in.shadow_size > 0.0 {
+        // The shadow is shaped like the rect with all corners as round as
+        // its roundest one.
+        let half_size = (in.end - in.start) / 2.0;
+        let center = in.start + half_size + in.shadow_offset;
+        let radius = min(
+            max(max(corner_sizes.x, corner_sizes.y), max(corner_sizes.z, corner_sizes.w)),
+            min(half_size.x, half_size.y),
+        );
+        let q = abs(in.position - center) - half_size + radius;
+        let distance = length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0) - radius;
+        let alpha = in.shadow_opacity * clamp(1.0 - distance / in.shadow_size, 0.0, 1.0);
+        return vec4<f32>(0.0, 0.0, 0.0, alpha);
+    }
+
+    return vec4<f32>(0.0, 0.0, 0.0, 0.0);
 }
@@ -1,63 +1,214 @@
+use std::{num::NonZeroU32, sync::mpsc};
+
 use wgpu::{
-    CommandEncoder, CommandEncoderDescriptor, SurfaceError, SurfaceTexture, TextureView,
-    TextureViewDescriptor,
+    Buffer, BufferAsyncError, BufferDescriptor, BufferUsages, CommandEncoder,
+    CommandEncoderDescriptor, Extent3d, ImageCopyBuffer, ImageCopyTexture, ImageDataLayout,
+    Maintain, MapMode, Origin3d, SurfaceError, SurfaceTexture, Texture, TextureAspect, TextureView,
+    TextureViewDescriptor, COPY_BYTES_PER_ROW_ALIGNMENT,
 };
 
 use super::{
     clear::clear, render_icons::render_icons, render_images::render_images,
-    render_rects::render_rects, render_text::render_text, ActiveRenderInfo, MutableResources,
-    ReadOnlyResources, RenderEngine,
+    render_rects::render_rects, render_strokes::render_strokes, render_text::render_text,
+    ActiveRenderInfo, MutableResources, ReadOnlyResources, RenderEngine,
+};
+use crate::renderer::{
+    render_target::{OffscreenError, TargetOutput},
+    shapes::Shapes,
 };
-use crate::renderer::shapes::Shapes;
 
 impl RenderEngine {
     pub fn render(&mut self, layers: &[&Shapes]) -> Result<(), SurfaceError> {
-        let (target, view, mut encoder) = start_rendering(&self.ror)?;
+        let (frame, view, mut encoder) = start_rendering(&self.ror)?;
+        render_layers(&self.ror, &mut self.mr, layers, &view, &mut encoder);
+        finish_rendering(&self.ror, &mut self.mr, encoder, frame);
+        Ok(())
+    }
+
+    /// Renders like render does, then reads the frame back as rows of RGBA
+    /// pixels from top to bottom. Only works for engines created with
+    /// new_offscreen.
+    pub fn render_to_image(&mut self, layers: &[&Shapes]) -> Result<Vec<[u8; 4]>, OffscreenError> {
+        let TargetOutput::Texture(texture) = self.ror.target.output() else {
+            return Err(OffscreenError::NotOffscreen);
+        };
+        let (_, view, mut encoder) = start_rendering(&self.ror).map_err(OffscreenError::Surface)?;
+        render_layers(&self.ror, &mut self.mr, layers, &view, &mut encoder);
+        let size = self.ror.target.size();
+        let (width, height) = (size.width as u32, size.height as u32);
+        let buffer = copy_to_buffer(&self.ror, &mut encoder, texture, width, height);
+        finish_rendering(&self.ror, &mut self.mr, encoder, None);
+        read_buffer(&self.ror, &buffer, width, height)
+    }
+}
+
+fn render_layers(
+    ror: &ReadOnlyResources,
+    mr: &mut MutableResources,
+    layers: &[&Shapes],
+    view: &TextureView,
+    encoder: &mut CommandEncoder,
+) {
+    let mut info = ActiveRenderInfo {
+        shapes: &Shapes::new(),
+        view,
+        encoder: &mut *encoder,
+    };
+    clear(ror, &mut info);
+    for shapes in layers {
         let mut info = ActiveRenderInfo {
-            shapes: &Shapes::new(),
-            view: &view,
-            encoder: &mut encoder,
+            shapes,
+            view,
+            encoder: &mut *encoder,
         };
-        clear(&self.ror, &mut info);
-        for shapes in layers {
-            let mut info = ActiveRenderInfo {
-                shapes,
-                view: &view,
-                encoder: &mut encoder,
-            };
-            render_rects(&self.ror, &mut info);
-            render_text(&self.ror, &mut self.mr, &mut info);
-            render_icons(&self.ror, &mut info);
-            render_images(&self.ror, &mut info);
-        }
-        finish_rendering(&self.ror, &mut self.mr, encoder, target);
-        Ok(())
+        render_rects(ror, &mut info);
+        render_strokes(ror, &mut info);
+        render_text(ror, mr, &mut info);
+        render_icons(ror, &mut info);
+        render_images(ror, &mut info);
     }
 }
 
 fn start_rendering(
     ror: &ReadOnlyResources,
-) -> Result<(SurfaceTexture, TextureView, CommandEncoder), SurfaceError> {
-    let target = ror.target.surface().get_current_texture()?;
+) -> Result<(Option<SurfaceTexture>, TextureView, CommandEncoder), SurfaceError> {
     let view_desc = TextureViewDescriptor {
         ..Default::default()
     };
-    let view = target.texture.create_view(&view_desc);
+    let (frame, view) = match ror.target.output() {
+        TargetOutput::Surface(surface) => {
+            let frame = surface.get_current_texture()?;
+            let view = frame.texture.create_view(&view_desc);
+            (Some(frame), view)
+        }
+        TargetOutput::Texture(texture) => (None, texture.create_view(&view_desc)),
+    };
     let encoder_desc = CommandEncoderDescriptor {
         label: Some("Render Encoder"),
     };
     let encoder = ror.device.device().create_command_encoder(&encoder_desc);
-    Ok((target, view, encoder))
+    Ok((frame, view, encoder))
 }
 
 fn finish_rendering(
     ror: &ReadOnlyResources,
     mr: &mut MutableResources,
     encoder: CommandEncoder,
-    target: SurfaceTexture,
+    frame: Option<SurfaceTexture>,
 ) {
     mr.staging_belt.finish();
     ror.device.queue().submit([encoder.finish()]);
-    target.present();
+    if let Some(frame) = frame {
+        frame.present();
+    }
     mr.staging_belt.recall();
 }
+
+/// Rows copied out of a texture have to start at multiples of this many
+/// bytes, so they may be padded.
+fn padded_bytes_per_row(width: u32) -> u32 {
+    let bytes = width * 4;
+    let align = COPY_BYTES_PER_ROW_ALIGNMENT;
+    (bytes + align - 1) / align * align
+}
+
+fn copy_to_buffer(
+    ror: &ReadOnlyResources,
+    encoder: &mut CommandEncoder,
+    texture: &Texture,
+    width: u32,
+    height: u32,
+) -> Buffer {
+    let bytes_per_row = padded_bytes_per_row(width);
+    let desc = BufferDescriptor {
+        label: Some("Offscreen Read Back Buffer"),
+        size: (bytes_per_row * height) as u64,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    };
+    let buffer = ror.device.device().create_buffer(&desc);
+    encoder.copy_texture_to_buffer(
+        ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: Origin3d::ZERO,
+            aspect: TextureAspect::All,
+        },
+        ImageCopyBuffer {
+            buffer: &buffer,
+            layout: ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(bytes_per_row),
+                rows_per_image: NonZeroU32::new(height),
+            },
+        },
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+    buffer
+}
+
+fn read_buffer(
+    ror: &ReadOnlyResources,
+    buffer: &Buffer,
+    width: u32,
+    height: u32,
+) -> Result<Vec<[u8; 4]>, OffscreenError> {
+    let slice = buffer.slice(..);
+    let (sender, receiver) = mpsc::channel();
+    slice.map_async(MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    ror.device.device().poll(Maintain::Wait);
+    // The callback has run by the time the device is done waiting, unless
+    // mapping failed without reporting it.
+    receiver
+        .try_recv()
+        .unwrap_or(Err(BufferAsyncError))
+        .map_err(OffscreenError::ReadBack)?;
+    let bytes_per_row = padded_bytes_per_row(width) as usize;
+    let data = slice.get_mapped_range();
+    let mut pixels = Vec::with_capacity((width * height) as usize);
+    for row in data.chunks(bytes_per_row) {
+        let row = &row[..width as usize * 4];
+        pixels.extend(
+            row.chunks(4)
+                .map(|pixel| [pixel[0], pixel[1], pixel[2], pixel[3]]),
+        );
+    }
+    drop(data);
+    buffer.unmap();
+    Ok(pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::{coordinates::Size, rect_data::RectInstance};
+
+    #[test]
+    #[ignore = "needs a graphics adapter, run with --ignored where there is one"]
+    fn offscreen_frames_can_be_read_back() {
+        let size = Size {
+            width: 32.0,
+            height: 16.0,
+        };
+        let mut engine = pollster::block_on(RenderEngine::new_offscreen(size))
+            .unwrap_or_else(|err| panic!("{}", err));
+        engine.set_background([0.0, 0.0, 0.0, 1.0]);
+        let mut shapes = Shapes::new();
+        shapes.push_rect(RectInstance {
+            position: [0.0, 0.0],
+            size: [16.0, 16.0],
+            fill_color: [1.0, 1.0, 1.0],
+            ..Default::default()
+        });
+        let pixels = engine.render_to_image(&[&shapes]).unwrap();
+        assert_eq!(pixels.len(), 32 * 16);
+        assert_eq!(pixels[8 * 32 + 4], [255, 255, 255, 255]);
+        assert_eq!(pixels[8 * 32 + 28], [0, 0, 0, 255]);
+    }
+}
@@ -1,22 +1,25 @@
+use std::ops::Range;
+
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
     Buffer, BufferUsages, LoadOp, Operations, RenderPass, RenderPassColorAttachment,
     RenderPassDescriptor,
 };
 
-use super::{ActiveRenderInfo, ReadOnlyResources};
-use crate::renderer::vertex_data::RECT_VERTS_LEN;
+use super::{set_clip, ActiveRenderInfo, ReadOnlyResources};
+use crate::renderer::{shapes::ClipRect, vertex_data::RECT_VERTS_LEN};
 
 pub(super) fn render_icons(ror: &ReadOnlyResources, info: &mut ActiveRenderInfo) {
-    let (instance_buffer, len) = upload_icons(ror, info);
+    let instance_buffer = upload_icons(ror, info);
+    let runs = info.shapes.clip_runs(|counts| counts.icons);
     let mut render_pass = start_render_pass(info);
-    render_icon_instructions(&mut render_pass, &instance_buffer, len, ror);
+    render_icon_instructions(&mut render_pass, &instance_buffer, runs, ror);
 }
 
 fn render_icon_instructions<'a>(
     render_pass: &mut RenderPass<'a>,
     instance_buffer: &'a Buffer,
-    len: usize,
+    runs: Vec<(Range<usize>, Option<ClipRect>)>,
     ror: &'a ReadOnlyResources,
 ) {
     render_pass.set_pipeline(&ror.icon_pipeline);
@@ -24,18 +27,21 @@ fn render_icon_instructions<'a>(
     render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
     render_pass.set_bind_group(0, ror.target.surface_geometry_bind_group(), &[]);
     render_pass.set_bind_group(1, &ror.icon_texture_bind_group, &[]);
-    render_pass.draw(0..RECT_VERTS_LEN as _, 0..len as _);
+    for (range, clip) in runs {
+        if set_clip(render_pass, clip, ror.target.size()) {
+            render_pass.draw(0..RECT_VERTS_LEN as _, range.start as _..range.end as _);
+        }
+    }
 }
 
-fn upload_icons(ror: &ReadOnlyResources, info: &mut ActiveRenderInfo) -> (Buffer, usize) {
+fn upload_icons(ror: &ReadOnlyResources, info: &mut ActiveRenderInfo) -> Buffer {
     let contents = &info.shapes.icons;
     let buffer_desc = BufferInitDescriptor {
         label: Some("Icon Instance Buffer"),
         contents: bytemuck::cast_slice(contents),
         usage: BufferUsages::VERTEX,
     };
-    let instance_buffer = ror.device.device().create_buffer_init(&buffer_desc);
-    (instance_buffer, contents.len())
+    ror.device.device().create_buffer_init(&buffer_desc)
 }
 
 fn start_render_pass<'a, 'b: 'a>(info: &'a mut ActiveRenderInfo<'b>) -> RenderPass<'a> {
@@ -1,41 +1,55 @@
+use std::ops::Range;
+
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
     Buffer, BufferUsages, LoadOp, Operations, RenderPass, RenderPassColorAttachment,
     RenderPassDescriptor,
 };
 
-use super::{ActiveRenderInfo, ReadOnlyResources};
-use crate::renderer::vertex_data::RECT_VERTS_LEN;
+use super::{set_clip, ActiveRenderInfo, ReadOnlyResources};
+use crate::renderer::{image_data::ImageInstance, shapes::ClipRect, vertex_data::RECT_VERTS_LEN};
 
 pub(super) fn render_images(ror: &ReadOnlyResources, info: &mut ActiveRenderInfo) {
-    let (instance_buffer, len) = upload_images(ror, info);
+    let instance_buffer = upload_images(ror, info);
+    let shapes = info.shapes;
+    let runs = shapes.clip_runs(|counts| counts.images);
     let mut render_pass = start_render_pass(info);
-    render_image_instructions(&mut render_pass, &instance_buffer, len, ror);
+    render_image_instructions(&mut render_pass, &instance_buffer, &shapes.images, runs, ror);
 }
 
 fn render_image_instructions<'a>(
     render_pass: &mut RenderPass<'a>,
     instance_buffer: &'a Buffer,
-    len: usize,
+    images: &[ImageInstance],
+    runs: Vec<(Range<usize>, Option<ClipRect>)>,
     ror: &'a ReadOnlyResources,
 ) {
     render_pass.set_pipeline(&ror.image_pipeline);
     render_pass.set_vertex_buffer(0, ror.rect_verts.slice(..));
     render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
     render_pass.set_bind_group(0, ror.target.surface_geometry_bind_group(), &[]);
-    render_pass.set_bind_group(1, &ror.image_textures[0].1, &[]);
-    render_pass.draw(0..RECT_VERTS_LEN as _, 0..len as _);
+    // Each image samples its own texture, so they are drawn one at a time.
+    for (range, clip) in runs {
+        if !set_clip(render_pass, clip, ror.target.size()) {
+            continue;
+        }
+        for instance in range {
+            let image = &images[instance];
+            let instance = instance as u32;
+            render_pass.set_bind_group(1, &ror.image_textures[image.index as usize].1, &[]);
+            render_pass.draw(0..RECT_VERTS_LEN as _, instance..instance + 1);
+        }
+    }
 }
 
-fn upload_images(ror: &ReadOnlyResources, info: &mut ActiveRenderInfo) -> (Buffer, usize) {
+fn upload_images(ror: &ReadOnlyResources, info: &mut ActiveRenderInfo) -> Buffer {
     let contents = &info.shapes.images;
     let buffer_desc = BufferInitDescriptor {
         label: Some("Image Instance Buffer"),
         contents: bytemuck::cast_slice(contents),
         usage: BufferUsages::VERTEX,
     };
-    let instance_buffer = ror.device.device().create_buffer_init(&buffer_desc);
-    (instance_buffer, contents.len())
+    ror.device.device().create_buffer_init(&buffer_desc)
 }
 
 fn start_render_pass<'a, 'b: 'a>(info: &'a mut ActiveRenderInfo<'b>) -> RenderPass<'a> {
@@ -0,0 +1,74 @@
+use std::ops::Range;
+
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    Buffer, BufferUsages, LoadOp, Operations, RenderPass, RenderPassColorAttachment,
+    RenderPassDescriptor,
+};
+
+use super::{set_clip, ActiveRenderInfo, ReadOnlyResources};
+use crate::renderer::{
+    shapes::ClipRect, stroke_data::StrokeSegmentInstance, vertex_data::RECT_VERTS_LEN,
+};
+
+pub(super) fn render_strokes(ror: &ReadOnlyResources, info: &mut ActiveRenderInfo) {
+    let (instance_buffer, runs) = upload_strokes(ror, info);
+    let mut render_pass = start_render_pass(info);
+    render_stroke_instructions(&mut render_pass, &instance_buffer, runs, ror);
+}
+
+fn render_stroke_instructions<'a>(
+    render_pass: &mut RenderPass<'a>,
+    instance_buffer: &'a Buffer,
+    runs: Vec<(Range<usize>, Option<ClipRect>)>,
+    ror: &'a ReadOnlyResources,
+) {
+    render_pass.set_pipeline(&ror.stroke_pipeline);
+    render_pass.set_vertex_buffer(0, ror.rect_verts.slice(..));
+    render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
+    render_pass.set_bind_group(0, ror.target.surface_geometry_bind_group(), &[]);
+    for (range, clip) in runs {
+        if set_clip(render_pass, clip, ror.target.size()) {
+            render_pass.draw(0..RECT_VERTS_LEN as _, range.start as _..range.end as _);
+        }
+    }
+}
+
+/// Uploads every segment of every stroke, along with the clip runs of the
+/// strokes turned into ranges of segments.
+fn upload_strokes(
+    ror: &ReadOnlyResources,
+    info: &mut ActiveRenderInfo,
+) -> (Buffer, Vec<(Range<usize>, Option<ClipRect>)>) {
+    let mut segments: Vec<StrokeSegmentInstance> = Vec::new();
+    let mut runs = Vec::new();
+    for (range, clip) in info.shapes.clip_runs(|counts| counts.strokes) {
+        let start = segments.len();
+        for stroke in &info.shapes.strokes[range] {
+            segments.append(&mut stroke.segments());
+        }
+        runs.push((start..segments.len(), clip));
+    }
+    let buffer_desc = BufferInitDescriptor {
+        label: Some("Stroke Segment Buffer"),
+        contents: bytemuck::cast_slice(&segments),
+        usage: BufferUsages::VERTEX,
+    };
+    (ror.device.device().create_buffer_init(&buffer_desc), runs)
+}
+
+fn start_render_pass<'a, 'b: 'a>(info: &'a mut ActiveRenderInfo<'b>) -> RenderPass<'a> {
+    let render_pass_desc = RenderPassDescriptor {
+        label: Some("Render Pass"),
+        color_attachments: &[Some(RenderPassColorAttachment {
+            view: info.view,
+            resolve_target: None,
+            ops: Operations {
+                load: LoadOp::Load,
+                store: true,
+            },
+        })],
+        depth_stencil_attachment: None,
+    };
+    info.encoder.begin_render_pass(&render_pass_desc)
+}
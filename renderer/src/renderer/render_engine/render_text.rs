@@ -1,3 +1,8 @@
+use wgpu_glyph::{
+    ab_glyph::{point, Rect},
+    orthographic_projection, Extra, FontId, Region, SectionGlyph,
+};
+
 use super::{ActiveRenderInfo, MutableResources, ReadOnlyResources};
 
 pub(super) fn render_text(
@@ -5,21 +10,66 @@ pub(super) fn render_text(
     mr: &mut MutableResources,
     info: &mut ActiveRenderInfo,
 ) {
-    let screen_height = ror.target.size().height;
-    let texts = info.shapes.texts.iter();
-    let wgpu_sections = texts.map(|text| text.as_wgpu_section(screen_height));
-    for section in wgpu_sections {
-        mr.fonts.regular.queue(section);
-    }
-    mr.fonts
-        .regular
-        .draw_queued(
-            ror.device.device(),
-            &mut mr.staging_belt,
-            info.encoder,
-            info.view,
-            ror.target.size().width as u32,
-            ror.target.size().height as u32,
-        )
+    let size = ror.target.size();
+    let (width, height) = (size.width as u32, size.height as u32);
+    // Text using different clip rects has to be drawn separately.
+    for (range, clip) in info.shapes.clip_runs(|counts| counts.texts) {
+        let region = match clip.map(|clip| clip.scissor(size)) {
+            Some(None) => continue,
+            Some(Some([x, y, width, height])) => Some(Region {
+                x,
+                y,
+                width,
+                height,
+            }),
+            None => None,
+        };
+        // Glyphs are laid out by Text so they land where the other
+        // backends put them.
+        let screen = Rect {
+            min: point(0.0, 0.0),
+            max: point(size.width, size.height),
+        };
+        for text in &info.shapes.texts[range] {
+            let layout = text.layout(mr.fonts.regular.fonts(), size.height);
+            let glyphs = layout
+                .glyphs
+                .into_iter()
+                .map(|placed| SectionGlyph {
+                    section_index: placed.section,
+                    byte_index: placed.byte_index,
+                    glyph: placed.glyph,
+                    font_id: FontId(text.sections[placed.section].style),
+                })
+                .collect();
+            let extra = text
+                .sections
+                .iter()
+                .map(|section| Extra {
+                    color: section.color,
+                    z: 0.0,
+                })
+                .collect();
+            mr.fonts.regular.queue_pre_positioned(glyphs, extra, screen);
+        }
+        let device = ror.device.device();
+        let belt = &mut mr.staging_belt;
+        let encoder = &mut *info.encoder;
+        match region {
+            Some(region) => mr.fonts.regular.draw_queued_with_transform_and_scissoring(
+                device,
+                belt,
+                encoder,
+                info.view,
+                orthographic_projection(width, height),
+                region,
+            ),
+            None => {
+                mr.fonts
+                    .regular
+                    .draw_queued(device, belt, encoder, info.view, width, height)
+            }
+        }
         .unwrap();
+    }
 }
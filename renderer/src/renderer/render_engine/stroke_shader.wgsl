@@ -0,0 +1,81 @@
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+};
+
+struct StrokeSegmentInstance {
+    @location(1) start: vec2<f32>,
+    @location(2) end: vec2<f32>,
+    @location(3) width: f32,
+    @location(4) color: vec3<f32>,
+    @location(5) distance: f32,
+    @location(6) dashes: vec2<f32>,
+};
+
+struct Screen {
+    width: f32,
+    height: f32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) position: vec2<f32>,
+    @location(1) start: vec2<f32>,
+    @location(2) end: vec2<f32>,
+    @location(3) width: f32,
+    @location(4) color: vec3<f32>,
+    @location(5) distance: f32,
+    @location(6) dashes: vec2<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> screen: Screen;
+
+fn segment_direction(start: vec2<f32>, end: vec2<f32>) -> vec2<f32> {
+    let length = distance(start, end);
+    if length > 0.0 {
+        return (end - start) / length;
+    }
+    return vec2<f32>(1.0, 0.0);
+}
+
+@vertex
+fn vertex_shader(vert: VertexInput, segment: StrokeSegmentInstance) -> VertexOutput {
+    var out: VertexOutput;
+
+    out.start = segment.start;
+    out.end = segment.end;
+    out.width = segment.width;
+    out.color = segment.color;
+    out.distance = segment.distance;
+    out.dashes = segment.dashes;
+
+    // Covers the segment, its round ends and a pixel of antialiasing.
+    let direction = segment_direction(segment.start, segment.end);
+    let normal = vec2<f32>(-direction.y, direction.x);
+    let padding = segment.width / 2.0 + 1.0;
+    let along = mix(-padding, distance(segment.start, segment.end) + padding, vert.position.x);
+    let across = mix(-padding, padding, vert.position.y);
+    out.position = segment.start + direction * along + normal * across;
+    let x = out.position.x / screen.width * 2.0 - 1.0;
+    let y = out.position.y / screen.height * 2.0 - 1.0;
+    out.clip_position = vec4<f32>(x, y, 0.0, 1.0);
+
+    return out;
+}
+
+@fragment
+fn fragment_shader(in: VertexOutput) -> @location(0) vec4<f32> {
+    let length = distance(in.start, in.end);
+    let direction = segment_direction(in.start, in.end);
+    let along = clamp(dot(in.position - in.start, direction), 0.0, length);
+    let closest = in.start + direction * along;
+
+    let dash = in.dashes.x;
+    let gap = in.dashes.y;
+    if gap > 0.0 && (in.distance + along) % (dash + gap) >= dash {
+        return vec4<f32>(0.0, 0.0, 0.0, 0.0);
+    }
+
+    let coverage = clamp(in.width / 2.0 + 0.5 - distance(closest, in.position), 0.0, 1.0);
+    return vec4<f32>(in.color, coverage);
+}
@@ -1,17 +1,58 @@
+use std::fmt::{self, Display, Formatter};
+
 use bytemuck::{Pod, Zeroable};
 use wgpu::{
-    Backends, BindGroup, BindGroupLayout, Instance, Surface, SurfaceConfiguration, TextureFormat,
+    Backends, BindGroup, BindGroupLayout, BufferAsyncError, Extent3d, Instance, RequestDeviceError,
+    Surface, SurfaceConfiguration, SurfaceError, Texture, TextureDescriptor, TextureDimension,
+    TextureFormat, TextureUsages,
 };
 use winit::window::Window;
 
 use super::{coordinates::Size, render_device::RenderDevice, uniform_buffer::UniformBuffer};
 
+/// Offscreen frames are stored as RGBA, so that they can be read back
+/// without converting them.
+const OFFSCREEN_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
 pub struct RenderTarget {
-    surface: Surface,
+    output: TargetOutput,
+    /// For offscreen targets, this only describes the texture.
     config: SurfaceConfiguration,
     surface_geometry_buffer: UniformBuffer<SurfaceGeometry>,
 }
 
+/// Where rendered frames end up.
+pub enum TargetOutput {
+    Surface(Surface),
+    /// A texture that frames can be copied out of, for rendering without a
+    /// window.
+    Texture(Texture),
+}
+
+/// Why rendering without a window failed.
+#[derive(Debug)]
+pub enum OffscreenError {
+    /// There is no adapter to render with, not even a software one.
+    NoAdapter,
+    Device(RequestDeviceError),
+    /// The engine renders to a window, whose frames cannot be read back.
+    NotOffscreen,
+    Surface(SurfaceError),
+    ReadBack(BufferAsyncError),
+}
+
+impl Display for OffscreenError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoAdapter => write!(f, "There is no graphics adapter to render with"),
+            Self::Device(err) => write!(f, "Could not open the graphics device: {}", err),
+            Self::NotOffscreen => write!(f, "Only offscreen targets can be read back"),
+            Self::Surface(err) => write!(f, "Could not render: {}", err),
+            Self::ReadBack(err) => write!(f, "Could not read the frame back: {}", err),
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Pod, Zeroable)]
 pub struct SurfaceGeometry {
@@ -25,8 +66,31 @@ impl RenderTarget {
         (Self::new_for_surface(surface, config, &device), device)
     }
 
-    pub fn surface(&self) -> &Surface {
-        &self.surface
+    /// Creates a target that renders into a texture instead of a window.
+    /// Uses a software adapter if there is no GPU.
+    pub async fn new_offscreen(size: Size) -> Result<(Self, RenderDevice), OffscreenError> {
+        let instance = Instance::new(Backends::all());
+        let device = RenderDevice::new_offscreen(&instance).await?;
+        let config = SurfaceConfiguration {
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            format: OFFSCREEN_FORMAT,
+            width: size.width as u32,
+            height: size.height as u32,
+            present_mode: wgpu::PresentMode::AutoNoVsync,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+        };
+        let texture = Self::create_texture(&config, &device);
+        let surface_geometry_buffer = Self::surface_geometry_buffer(&config, &device);
+        let target = Self {
+            output: TargetOutput::Texture(texture),
+            config,
+            surface_geometry_buffer,
+        };
+        Ok((target, device))
+    }
+
+    pub fn output(&self) -> &TargetOutput {
+        &self.output
     }
 
     pub fn format(&self) -> TextureFormat {
@@ -46,7 +110,10 @@ impl RenderTarget {
         self.config.height = new_size.height as u32;
         self.surface_geometry_buffer
             .set(Self::surface_geometry(&self.config), device);
-        self.surface.configure(device.device(), &self.config);
+        match &mut self.output {
+            TargetOutput::Surface(surface) => surface.configure(device.device(), &self.config),
+            TargetOutput::Texture(texture) => *texture = Self::create_texture(&self.config, device),
+        }
     }
 
     pub fn refresh(&mut self, device: &RenderDevice) {
@@ -87,12 +154,29 @@ impl RenderTarget {
         let surface_geometry_buffer = Self::surface_geometry_buffer(&config, device);
 
         Self {
-            surface,
+            output: TargetOutput::Surface(surface),
             config,
             surface_geometry_buffer,
         }
     }
 
+    fn create_texture(config: &SurfaceConfiguration, device: &RenderDevice) -> Texture {
+        let desc = TextureDescriptor {
+            label: Some("Offscreen Target"),
+            size: Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: config.format,
+            usage: config.usage,
+        };
+        device.device().create_texture(&desc)
+    }
+
     fn surface_geometry(config: &SurfaceConfiguration) -> SurfaceGeometry {
         SurfaceGeometry {
             size: Size {
@@ -1,20 +1,109 @@
-use super::{image_data::ImageInstance, IconInstance, RectInstance, Text};
+use std::ops::Range;
+
+use super::{
+    coordinates::{Position, Size},
+    image_data::ImageInstance,
+    stroke_data::Stroke,
+    IconInstance, RectInstance, Text,
+};
 
 #[derive(Clone, Debug)]
 pub struct Shapes {
     pub rects: Vec<RectInstance>,
+    pub strokes: Vec<Stroke>,
     pub texts: Vec<Text>,
     pub icons: Vec<IconInstance>,
     pub images: Vec<ImageInstance>,
+    /// Every point at which the clip rect changes, in order.
+    clip_changes: Vec<ClipChange>,
+    /// The clip rects pushed with push_clip, each already cut down to the
+    /// ones before it.
+    clip_stack: Vec<ClipRect>,
+}
+
+/// A part of the target that shapes can be restricted to, in the same
+/// coordinates as their positions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClipRect {
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+}
+
+/// How many shapes of each kind there are, or were at some point.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) struct ShapeCounts {
+    pub(super) rects: usize,
+    pub(super) strokes: usize,
+    pub(super) texts: usize,
+    pub(super) icons: usize,
+    pub(super) images: usize,
+}
+
+/// Shapes added after the given counts were reached use the clip rect, or
+/// are not clipped if there is none.
+#[derive(Clone, Debug)]
+struct ClipChange {
+    starts: ShapeCounts,
+    clip: Option<ClipRect>,
+}
+
+impl ClipRect {
+    /// The part of the rect that is also inside the other one.
+    pub fn intersection(&self, other: &ClipRect) -> ClipRect {
+        let start = [0, 1].map(|i| self.position[i].max(other.position[i]));
+        let end = [0, 1].map(|i| {
+            let end = self.position[i] + self.size[i];
+            end.min(other.position[i] + other.size[i])
+        });
+        ClipRect {
+            position: start,
+            size: [0, 1].map(|i| (end[i] - start[i]).max(0.0)),
+        }
+    }
+
+    /// The pixels whose centers are inside the rect, as x, y, width and
+    /// height with y counted from the top like scissor rects do. None if
+    /// there are no such pixels.
+    pub(super) fn scissor(&self, target: Size) -> Option<[u32; 4]> {
+        let pixels = |start: f32, end: f32, limit: f32| {
+            let clamp = |value: f32| (value - 0.5).ceil().clamp(0.0, limit) as u32;
+            (clamp(start), clamp(end))
+        };
+        let (left, right) = pixels(
+            self.position[0],
+            self.position[0] + self.size[0],
+            target.width,
+        );
+        let (top, bottom) = pixels(
+            target.height - self.position[1] - self.size[1],
+            target.height - self.position[1],
+            target.height,
+        );
+        if left == right || top == bottom {
+            return None;
+        }
+        Some([left, top, right - left, bottom - top])
+    }
+}
+
+/// Shapes inside both clip rects, where None means not clipped at all.
+fn clip_within(clip: Option<ClipRect>, outer: Option<ClipRect>) -> Option<ClipRect> {
+    match (clip, outer) {
+        (Some(clip), Some(outer)) => Some(clip.intersection(&outer)),
+        (clip, outer) => clip.or(outer),
+    }
 }
 
 impl Shapes {
     pub fn new() -> Self {
         Self {
             rects: vec![],
+            strokes: vec![],
             texts: vec![],
             icons: vec![],
             images: vec![],
+            clip_changes: vec![],
+            clip_stack: vec![],
         }
     }
 
@@ -22,6 +111,10 @@ impl Shapes {
         self.rects.push(rect)
     }
 
+    pub fn push_stroke(&mut self, stroke: Stroke) {
+        self.strokes.push(stroke)
+    }
+
     pub fn push_text(&mut self, text: Text) {
         self.texts.push(text)
     }
@@ -34,10 +127,274 @@ impl Shapes {
         self.images.push(image)
     }
 
+    /// Only draws the parts of the shapes pushed from now on that are inside
+    /// the rect, and inside any clip rects pushed before it, until the
+    /// matching pop_clip.
+    pub fn push_clip(&mut self, clip: ClipRect) {
+        let clip = clip_within(Some(clip), self.current_clip()).unwrap();
+        self.clip_stack.push(clip);
+        self.change_clip(Some(clip));
+    }
+
+    pub fn pop_clip(&mut self) {
+        self.clip_stack.pop().expect("No clip rect to pop");
+        self.change_clip(self.current_clip());
+    }
+
+    fn current_clip(&self) -> Option<ClipRect> {
+        self.clip_stack.last().copied()
+    }
+
+    pub(super) fn counts(&self) -> ShapeCounts {
+        ShapeCounts {
+            rects: self.rects.len(),
+            strokes: self.strokes.len(),
+            texts: self.texts.len(),
+            icons: self.icons.len(),
+            images: self.images.len(),
+        }
+    }
+
+    fn change_clip(&mut self, clip: Option<ClipRect>) {
+        self.push_clip_change(ClipChange {
+            starts: self.counts(),
+            clip,
+        });
+    }
+
+    /// Adds the change unless it would leave a run empty or split one that
+    /// already uses the same clip.
+    fn push_clip_change(&mut self, change: ClipChange) {
+        let last = self.clip_changes.last();
+        if last.is_some_and(|last| last.starts == change.starts) {
+            // Nothing was added with the previous clip, so it can be dropped.
+            self.clip_changes.pop();
+        }
+        let current = self.clip_changes.last().and_then(|last| last.clip);
+        if change.clip != current {
+            self.clip_changes.push(change);
+        }
+    }
+
+    /// Splits the shapes of one kind into runs that use the same clip rect,
+    /// skipping empty ones. Which kind is picked out of the counts by count.
+    pub(super) fn clip_runs(
+        &self,
+        count: fn(&ShapeCounts) -> usize,
+    ) -> Vec<(Range<usize>, Option<ClipRect>)> {
+        let mut runs = Vec::new();
+        let mut start = 0;
+        let mut clip = None;
+        for change in &self.clip_changes {
+            let end = count(&change.starts);
+            if end > start {
+                runs.push((start..end, clip));
+            }
+            start = end;
+            clip = change.clip;
+        }
+        let end = count(&self.counts());
+        if end > start {
+            runs.push((start..end, clip));
+        }
+        runs
+    }
+
+    /// Scales everything by the given factor and then moves it by the
+    /// offset. Rect outlines stay as thick as they were.
+    pub fn transform(&mut self, offset: Position, scale: f32) {
+        let point = |[x, y]: [f32; 2]| [x * scale + offset.x, y * scale + offset.y];
+        for rect in &mut self.rects {
+            rect.position = point(rect.position);
+            rect.size = rect.size.map(|length| length * scale);
+        }
+        for stroke in &mut self.strokes {
+            stroke.points = stroke.points.iter().copied().map(point).collect();
+            stroke.width *= scale;
+            stroke.dashes = stroke.dashes.map(|length| length * scale);
+        }
+        for text in &mut self.texts {
+            text.center = point(text.center);
+            text.bounds = text.bounds.map(|length| length * scale);
+            for section in &mut text.sections {
+                section.size *= scale;
+            }
+        }
+        for icon in &mut self.icons {
+            icon.position = point(icon.position);
+            icon.size *= scale;
+        }
+        for image in &mut self.images {
+            image.position = point(image.position);
+            image.size *= scale;
+        }
+        let clips = self
+            .clip_changes
+            .iter_mut()
+            .filter_map(|change| change.clip.as_mut())
+            .chain(&mut self.clip_stack);
+        for clip in clips {
+            clip.position = point(clip.position);
+            clip.size = clip.size.map(|length| length * scale);
+        }
+    }
+
+    /// Adds the other shapes on top, clipped by the clip rects currently
+    /// pushed as well as their own.
     pub fn append(&mut self, other: Self) {
+        let base = self.counts();
+        let outer = self.current_clip();
+        for change in other.clip_changes {
+            let starts = change.starts;
+            self.push_clip_change(ClipChange {
+                starts: ShapeCounts {
+                    rects: base.rects + starts.rects,
+                    strokes: base.strokes + starts.strokes,
+                    texts: base.texts + starts.texts,
+                    icons: base.icons + starts.icons,
+                    images: base.images + starts.images,
+                },
+                clip: clip_within(change.clip, outer),
+            });
+        }
         self.rects.append(&mut { other.rects });
+        self.strokes.append(&mut { other.strokes });
         self.texts.append(&mut { other.texts });
         self.icons.append(&mut { other.icons });
         self.images.append(&mut { other.images });
+        self.change_clip(outer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clip(x: f32, y: f32, width: f32, height: f32) -> ClipRect {
+        ClipRect {
+            position: [x, y],
+            size: [width, height],
+        }
+    }
+
+    fn rect_runs(shapes: &Shapes) -> Vec<(Range<usize>, Option<ClipRect>)> {
+        shapes.clip_runs(|counts| counts.rects)
+    }
+
+    #[test]
+    fn runs_follow_pushed_clips() {
+        let mut shapes = Shapes::new();
+        shapes.push_rect(RectInstance::default());
+        shapes.push_clip(clip(0.0, 0.0, 10.0, 10.0));
+        shapes.push_rect(RectInstance::default());
+        shapes.push_rect(RectInstance::default());
+        shapes.push_clip(clip(5.0, 5.0, 10.0, 10.0));
+        shapes.push_rect(RectInstance::default());
+        shapes.pop_clip();
+        shapes.pop_clip();
+        shapes.push_rect(RectInstance::default());
+        assert_eq!(
+            rect_runs(&shapes),
+            [
+                (0..1, None),
+                (1..3, Some(clip(0.0, 0.0, 10.0, 10.0))),
+                (3..4, Some(clip(5.0, 5.0, 5.0, 5.0))),
+                (4..5, None),
+            ]
+        );
+        // Other kinds of shapes were not added, so they have no runs.
+        assert_eq!(shapes.clip_runs(|counts| counts.texts), []);
+    }
+
+    #[test]
+    fn clips_without_shapes_are_dropped() {
+        let mut shapes = Shapes::new();
+        for _ in 0..3 {
+            shapes.push_clip(clip(0.0, 0.0, 10.0, 10.0));
+            shapes.pop_clip();
+        }
+        shapes.push_clip(clip(1.0, 2.0, 3.0, 4.0));
+        shapes.push_rect(RectInstance::default());
+        assert_eq!(shapes.clip_changes.len(), 1);
+        shapes.pop_clip();
+        shapes.push_clip(clip(0.0, 0.0, 1.0, 1.0));
+        shapes.pop_clip();
+        assert_eq!(shapes.clip_changes.len(), 2);
+        assert_eq!(rect_runs(&shapes), [(0..1, Some(clip(1.0, 2.0, 3.0, 4.0)))]);
+    }
+
+    #[test]
+    fn appended_shapes_are_clipped_by_both() {
+        let mut inner = Shapes::new();
+        inner.push_rect(RectInstance::default());
+        inner.push_clip(clip(5.0, 0.0, 10.0, 10.0));
+        inner.push_rect(RectInstance::default());
+        inner.pop_clip();
+        inner.push_rect(RectInstance::default());
+
+        let mut outer = Shapes::new();
+        outer.push_rect(RectInstance::default());
+        outer.push_clip(clip(0.0, 0.0, 8.0, 8.0));
+        outer.append(inner.clone());
+        outer.push_rect(RectInstance::default());
+        outer.pop_clip();
+        outer.append(inner);
+        let outer_clip = Some(clip(0.0, 0.0, 8.0, 8.0));
+        assert_eq!(
+            rect_runs(&outer),
+            [
+                (0..1, None),
+                (1..2, outer_clip),
+                (2..3, Some(clip(5.0, 0.0, 3.0, 8.0))),
+                (3..5, outer_clip),
+                (5..6, None),
+                (6..7, Some(clip(5.0, 0.0, 10.0, 10.0))),
+                (7..8, None),
+            ]
+        );
+        // Shapes pushed after appending are clipped as before it.
+        outer.push_rect(RectInstance::default());
+        assert_eq!(rect_runs(&outer).last(), Some(&(7..9, None)));
+    }
+
+    #[test]
+    fn transforms_move_clips_with_the_shapes() {
+        let mut shapes = Shapes::new();
+        shapes.push_clip(clip(1.0, 2.0, 3.0, 4.0));
+        shapes.push_rect(RectInstance::default());
+        shapes.transform(Position { x: 10.0, y: 20.0 }, 2.0);
+        shapes.push_rect(RectInstance::default());
+        assert_eq!(
+            rect_runs(&shapes),
+            [(0..2, Some(clip(12.0, 24.0, 6.0, 8.0)))]
+        );
+    }
+
+    #[test]
+    fn scissors_cover_pixels_whose_centers_are_inside() {
+        let target = Size {
+            width: 100.0,
+            height: 50.0,
+        };
+        // y counts up from the bottom, so the clip is flipped.
+        assert_eq!(
+            clip(10.0, 5.0, 20.0, 10.0).scissor(target),
+            Some([10, 35, 20, 10])
+        );
+        assert_eq!(
+            clip(10.2, 5.0, 20.0, 10.0).scissor(target),
+            Some([10, 35, 20, 10])
+        );
+        assert_eq!(
+            clip(10.6, 5.0, 20.0, 10.0).scissor(target),
+            Some([11, 35, 20, 10])
+        );
+        assert_eq!(
+            clip(-10.0, -10.0, 200.0, 200.0).scissor(target),
+            Some([0, 0, 100, 50])
+        );
+        assert_eq!(clip(10.0, 5.0, 0.4, 10.0).scissor(target), None);
+        assert_eq!(clip(10.0, 5.0, 20.0, 0.0).scissor(target), None);
+        assert_eq!(clip(150.0, 5.0, 20.0, 10.0).scissor(target), None);
     }
 }
@@ -0,0 +1,543 @@
+use std::ops::Range;
+
+use ab_glyph::{Font, FontArc};
+use theme::{srgb_inverse_transfer_function, srgb_transfer_function, Theme, PREVIEW_TEXTURE_SIZE};
+
+use super::{
+    coordinates::Size,
+    fonts::load_fonts,
+    icon_data::{IconInstance, ICON_ATLAS_SIZE, ICON_CELL_SIZE},
+    icons::rasterize_icon_atlas,
+    image_data::{ImageInstance, NUM_IMAGES},
+    rect_data::RectInstance,
+    shapes::{ClipRect, Shapes},
+    stroke_data::Stroke,
+    text::Text,
+};
+
+/// Draws the same shapes as RenderEngine without a GPU, producing an image
+/// instead of presenting to a window. Follows the shaders closely, but text
+/// and filtered textures can differ from the GPU in their last bit.
+pub struct SoftwareRenderer {
+    size: Size,
+    fonts: Vec<FontArc>,
+    /// The alpha of every pixel in the icon atlas.
+    icon_atlas: Vec<u8>,
+    /// Uploaded images, in linear color.
+    images: Vec<Vec<[f32; 4]>>,
+    background: [f32; 4],
+}
+
+/// Pixels being drawn, in linear color, stored in rows from top to bottom.
+struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<[f32; 4]>,
+    /// The columns and rows that can currently be drawn to.
+    clip: [Range<usize>; 2],
+}
+
+impl Canvas {
+    /// Blends color over the pixel the same way BlendState::ALPHA_BLENDING
+    /// does.
+    fn blend(&mut self, column: usize, row: usize, color: [f32; 4]) {
+        let pixel = &mut self.pixels[row * self.width + column];
+        let alpha = color[3];
+        for channel in 0..3 {
+            pixel[channel] = color[channel] * alpha + pixel[channel] * (1.0 - alpha);
+        }
+        pixel[3] = alpha + pixel[3] * (1.0 - alpha);
+    }
+
+    /// Uses the same pixels for the clip rect as the scissor rects the GPU
+    /// gets.
+    fn set_clip(&mut self, clip: Option<ClipRect>) {
+        let size = Size {
+            width: self.width as f32,
+            height: self.height as f32,
+        };
+        self.clip = match clip.map(|clip| clip.scissor(size)) {
+            None => [0..self.width, 0..self.height],
+            Some(None) => [0..0, 0..0],
+            Some(Some([x, y, width, height])) => {
+                let [x, y, width, height] = [x, y, width, height].map(|value| value as usize);
+                [x..x + width, y..y + height]
+            }
+        };
+    }
+
+    fn is_drawable(&self, column: i64, row: i64) -> bool {
+        let [columns, rows] = &self.clip;
+        let contains = |range: &Range<usize>, value: i64| {
+            value >= range.start as i64 && value < range.end as i64
+        };
+        contains(columns, column) && contains(rows, row)
+    }
+
+    /// The drawable columns whose centers lie in [start, end).
+    fn columns(&self, start: f32, end: f32) -> Range<usize> {
+        within(pixel_range(start, end, self.width), &self.clip[0])
+    }
+
+    /// The drawable rows whose centers lie in [start, end), where y
+    /// increases upwards like it does for shapes.
+    fn rows(&self, start: f32, end: f32) -> Range<usize> {
+        let height = self.height as f32;
+        within(
+            pixel_range(height - end, height - start, self.height),
+            &self.clip[1],
+        )
+    }
+
+    /// Where the center of a pixel is, in the coordinates shapes use.
+    fn position(&self, column: usize, row: usize) -> (f32, f32) {
+        (column as f32 + 0.5, self.height as f32 - row as f32 - 0.5)
+    }
+}
+
+fn within(range: Range<usize>, limit: &Range<usize>) -> Range<usize> {
+    range.start.max(limit.start)..range.end.min(limit.end)
+}
+
+fn pixel_range(start: f32, end: f32, limit: usize) -> Range<usize> {
+    let clamp = |value: f32| (value.ceil().max(0.0) as usize).min(limit);
+    clamp(start - 0.5)..clamp(end - 0.5)
+}
+
+impl SoftwareRenderer {
+    pub fn new(size: Size) -> Self {
+        let image_len = (PREVIEW_TEXTURE_SIZE * PREVIEW_TEXTURE_SIZE) as usize;
+        Self {
+            size,
+            fonts: load_fonts(),
+            icon_atlas: rasterize_icon_atlas(),
+            images: vec![vec![[0.0; 4]; image_len]; NUM_IMAGES],
+            background: Theme::dark().background,
+        }
+    }
+
+    // Data is assumed to be in sRGB format.
+    pub fn upload_image(&mut self, index: usize, data: &[[u8; 4]]) {
+        let to_linear = |[r, g, b, a]: [u8; 4]| {
+            let channel = |value: u8| srgb_transfer_function(value as f32 / 255.0);
+            [channel(r), channel(g), channel(b), a as f32 / 255.0]
+        };
+        self.images[index] = data.iter().copied().map(to_linear).collect();
+    }
+
+    pub fn set_background(&mut self, color: [f32; 4]) {
+        self.background = color;
+    }
+
+    pub fn resize_target(&mut self, new_size: Size) {
+        self.size = new_size;
+    }
+
+    pub fn target_size(&self) -> Size {
+        self.size
+    }
+
+    /// Draws the layers in order, returning rows of RGBA pixels from top to
+    /// bottom like RenderEngine::render_to_image.
+    pub fn render_to_image(&self, layers: &[&Shapes]) -> Vec<[u8; 4]> {
+        let width = self.size.width as usize;
+        let height = self.size.height as usize;
+        let mut canvas = Canvas {
+            width,
+            height,
+            pixels: vec![self.background; width * height],
+            clip: [0..width, 0..height],
+        };
+        for shapes in layers {
+            for (range, clip) in shapes.clip_runs(|counts| counts.rects) {
+                canvas.set_clip(clip);
+                for rect in &shapes.rects[range] {
+                    draw_rect(&mut canvas, rect);
+                }
+            }
+            for (range, clip) in shapes.clip_runs(|counts| counts.strokes) {
+                canvas.set_clip(clip);
+                for stroke in &shapes.strokes[range] {
+                    draw_stroke(&mut canvas, stroke);
+                }
+            }
+            for (range, clip) in shapes.clip_runs(|counts| counts.texts) {
+                canvas.set_clip(clip);
+                for text in &shapes.texts[range] {
+                    self.draw_text(&mut canvas, text);
+                }
+            }
+            for (range, clip) in shapes.clip_runs(|counts| counts.icons) {
+                canvas.set_clip(clip);
+                for icon in &shapes.icons[range] {
+                    self.draw_icon(&mut canvas, icon);
+                }
+            }
+            for (range, clip) in shapes.clip_runs(|counts| counts.images) {
+                canvas.set_clip(clip);
+                for image in &shapes.images[range] {
+                    self.draw_image(&mut canvas, image);
+                }
+            }
+        }
+        canvas
+            .pixels
+            .into_iter()
+            .map(|[r, g, b, a]| {
+                let byte = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+                [
+                    byte(srgb_inverse_transfer_function(r)),
+                    byte(srgb_inverse_transfer_function(g)),
+                    byte(srgb_inverse_transfer_function(b)),
+                    byte(a),
+                ]
+            })
+            .collect()
+    }
+
+    /// Follows icon_shader.wgsl.
+    fn draw_icon(&self, canvas: &mut Canvas, icon: &IconInstance) {
+        let icons_per_row = ICON_ATLAS_SIZE / ICON_CELL_SIZE;
+        let cell_x = (icon.index as u32 % icons_per_row) as f32;
+        let cell_y = (icon.index as u32 / icons_per_row) as f32;
+        let [x, y] = icon.position;
+        let size = ICON_ATLAS_SIZE as usize;
+        let magnified = icon.size > ICON_CELL_SIZE as f32;
+        for row in canvas.rows(y, y + icon.size) {
+            for column in canvas.columns(x, x + icon.size) {
+                let (px, py) = canvas.position(column, row);
+                let (vx, vy) = ((px - x) / icon.size, (py - y) / icon.size);
+                let uv = [
+                    (vx + cell_x) / icons_per_row as f32,
+                    (1.0 - vy + cell_y) / icons_per_row as f32,
+                ];
+                let alpha = sample(size, size, uv, magnified, |tx, ty| {
+                    [self.icon_atlas[ty * size + tx] as f32 / 255.0; 4]
+                })[0];
+                canvas.blend(column, row, [0.5, 0.5, 0.5, alpha]);
+            }
+        }
+    }
+
+    /// Follows image_shader.wgsl.
+    fn draw_image(&self, canvas: &mut Canvas, image: &ImageInstance) {
+        let data = &self.images[image.index as usize];
+        let [x, y] = image.position;
+        let size = PREVIEW_TEXTURE_SIZE as usize;
+        let magnified = image.size > PREVIEW_TEXTURE_SIZE as f32;
+        for row in canvas.rows(y, y + image.size) {
+            for column in canvas.columns(x, x + image.size) {
+                let (px, py) = canvas.position(column, row);
+                let uv = [(px - x) / image.size, (py - y) / image.size];
+                let color = sample(size, size, uv, magnified, |tx, ty| data[ty * size + tx]);
+                canvas.blend(column, row, color);
+            }
+        }
+    }
+
+    /// Lays out and draws a single line of text the way wgpu_glyph does with
+    /// Layout::default_single_line.
+    fn draw_text(&self, canvas: &mut Canvas, text: &Text) {
+        let layout = text.layout(&self.fonts, canvas.height as f32);
+        for placed in layout.glyphs {
+            let section = &text.sections[placed.section];
+            let Some(outline) = self.fonts[section.style].outline_glyph(placed.glyph) else {
+                continue;
+            };
+            let bounds = outline.px_bounds();
+            outline.draw(|x, y, coverage| {
+                let column = bounds.min.x as i64 + x as i64;
+                let row = bounds.min.y as i64 + y as i64;
+                if canvas.is_drawable(column, row) {
+                    let [r, g, b, a] = section.color;
+                    canvas.blend(column as usize, row as usize, [r, g, b, a * coverage]);
+                }
+            });
+        }
+    }
+}
+
+/// Follows rect_shader.wgsl, leaving pixels alone where the shader makes
+/// them transparent.
+fn draw_rect(canvas: &mut Canvas, rect: &RectInstance) {
+    let [start_x, start_y] = rect.position;
+    let end_x = start_x + rect.size[0];
+    let end_y = start_y + rect.size[1];
+    let [left_mode, right_mode, bottom_mode, top_mode] = rect.side_modes();
+    let edges = rect.edges();
+    let thickness = rect.outline_thickness;
+    let margin = if rect.shadow_size > 0.0 {
+        let [offset_x, offset_y] = rect.shadow_offset;
+        rect.shadow_size + offset_x.abs().max(offset_y.abs())
+    } else {
+        0.0
+    };
+    for row in canvas.rows(start_y - margin, end_y + margin) {
+        for column in canvas.columns(start_x - margin, end_x + margin) {
+            let (x, y) = canvas.position(column, row);
+            let left = edges.left[0] + edges.left[1] * y;
+            let right = edges.right[0] + edges.right[1] * y;
+            let bottom = edges.bottom[0] + edges.bottom[1] * x;
+            let top = edges.top[0] + edges.top[1] * x;
+            let in_rect = x >= start_x && x < end_x && y >= start_y && y < end_y;
+            let mut inside = in_rect && x >= left && x <= right && y >= bottom && y <= top;
+            let mut outline = (left_mode > 0 && x <= left + thickness)
+                || (right_mode > 0 && x >= right - thickness)
+                || (bottom_mode > 0 && y <= bottom + thickness)
+                || (top_mode > 0 && y >= top - thickness);
+            for (corner, &size) in rect.corner_sizes.iter().enumerate() {
+                if size <= 0.0 {
+                    continue;
+                }
+                let [u, v] = rect.corner_distances(corner, [x, y]);
+                let outlined = rect.corner_is_outlined(corner);
+                if rect.corner_is_chamfered(corner) {
+                    inside &= u + v >= size;
+                    outline |= outlined && u + v < size + thickness * 2f32.sqrt();
+                } else if u < size && v < size {
+                    let distance = (size - u).hypot(size - v);
+                    inside &= distance <= size;
+                    outline |= outlined && distance > size - thickness;
+                }
+            }
+            if inside {
+                let [r, g, b] = if outline {
+                    rect.outline_color
+                } else {
+                    rect.fill_color
+                };
+                canvas.blend(column, row, [r, g, b, 1.0]);
+            } else if rect.shadow_size > 0.0 {
+                let distance = shadow_distance(rect, [x, y]);
+                let alpha =
+                    rect.shadow_opacity * (1.0 - distance / rect.shadow_size).clamp(0.0, 1.0);
+                canvas.blend(column, row, [0.0, 0.0, 0.0, alpha]);
+            }
+        }
+    }
+}
+
+/// Follows stroke_shader.wgsl, drawing each segment on its own.
+fn draw_stroke(canvas: &mut Canvas, stroke: &Stroke) {
+    for segment in stroke.segments() {
+        let padding = segment.width / 2.0 + 1.0;
+        let [(left, right), (bottom, top)] = [0, 1].map(|i| {
+            let (a, b) = (segment.start[i], segment.end[i]);
+            (a.min(b) - padding, a.max(b) + padding)
+        });
+        for row in canvas.rows(bottom, top) {
+            for column in canvas.columns(left, right) {
+                let (x, y) = canvas.position(column, row);
+                let coverage = segment.coverage([x, y]);
+                if coverage > 0.0 {
+                    let [r, g, b] = segment.color;
+                    canvas.blend(column, row, [r, g, b, coverage]);
+                }
+            }
+        }
+    }
+}
+
+/// How far a point is outside the shadow of a rect, which has the shape of
+/// the rect with every corner as round as its roundest one. Negative inside.
+fn shadow_distance(rect: &RectInstance, [x, y]: [f32; 2]) -> f32 {
+    let half_size = rect.size.map(|length| length / 2.0);
+    let center = [0, 1].map(|i| rect.position[i] + half_size[i] + rect.shadow_offset[i]);
+    let roundest = rect.corner_sizes.iter().copied().fold(0.0, f32::max);
+    let radius = roundest.min(half_size[0].min(half_size[1]));
+    let q = [0, 1].map(|i| ([x, y][i] - center[i]).abs() - half_size[i] + radius);
+    q[0].max(0.0).hypot(q[1].max(0.0)) + q[0].max(q[1]).min(0.0) - radius
+}
+
+/// Samples a texture with clamped edges. Like the samplers the GPU uses,
+/// filters linearly when magnifying and picks the nearest texel otherwise.
+fn sample(
+    width: usize,
+    height: usize,
+    [u, v]: [f32; 2],
+    magnified: bool,
+    texel: impl Fn(usize, usize) -> [f32; 4],
+) -> [f32; 4] {
+    let clamp = |value: f32, limit: usize| (value.max(0.0) as usize).min(limit - 1);
+    let x = u * width as f32;
+    let y = v * height as f32;
+    if !magnified {
+        return texel(clamp(x, width), clamp(y, height));
+    }
+    let (x, y) = (x - 0.5, y - 0.5);
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let (left, right) = (clamp(x0, width), clamp(x0 + 1.0, width));
+    let (top, bottom) = (clamp(y0, height), clamp(y0 + 1.0, height));
+    let lerp = |a: [f32; 4], b: [f32; 4], t: f32| [0, 1, 2, 3].map(|i| a[i] + (b[i] - a[i]) * t);
+    lerp(
+        lerp(texel(left, top), texel(right, top), fx),
+        lerp(texel(left, bottom), texel(right, bottom), fx),
+        fy,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use wgpu_glyph::{HorizontalAlign, VerticalAlign};
+
+    use super::*;
+    use crate::renderer::{
+        icons::Icons,
+        rect_data::{
+            BOTTOM_LEFT_CORNER_CHAMFER, BOTTOM_OUTLINE_ANTIDIAGONAL, BOTTOM_OUTLINE_DIAGONAL,
+            BOTTOM_OUTLINE_FLAT, BOTTOM_RIGHT_CORNER_CHAMFER, LEFT_OUTLINE_ANTIDIAGONAL,
+            LEFT_OUTLINE_DIAGONAL, LEFT_OUTLINE_FLAT, RIGHT_OUTLINE_ANTIDIAGONAL,
+            RIGHT_OUTLINE_DIAGONAL, RIGHT_OUTLINE_FLAT, TOP_LEFT_CORNER_CHAMFER,
+            TOP_OUTLINE_ANTIDIAGONAL, TOP_OUTLINE_DIAGONAL, TOP_OUTLINE_FLAT,
+            TOP_RIGHT_CORNER_CHAMFER,
+        },
+        render_engine::RenderEngine,
+        text::{Section, TextOverflow},
+    };
+
+    /// Where the expected image is kept. Set UPDATE_SNAPSHOTS to write it
+    /// again after changing how shapes are drawn on purpose.
+    const SNAPSHOT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/snapshots/software.png");
+    /// How far a channel may be off, so that floating point differences
+    /// between platforms do not fail the test.
+    const TOLERANCE: u8 = 2;
+    /// The glyph cache on the GPU rounds the coverage of text a little
+    /// differently, which shows up as a few levels in dim pixels.
+    const GPU_TOLERANCE: u8 = 4;
+
+    /// Rects and icons sit off the pixel grid, so that no pixel center lands
+    /// exactly on an edge or between two texels, where the GPU can round
+    /// either way.
+    fn representative_shapes(theme: &Theme) -> Shapes {
+        let mut shapes = Shapes::new();
+        let outline_modes = [
+            0,
+            LEFT_OUTLINE_FLAT | RIGHT_OUTLINE_FLAT | TOP_OUTLINE_FLAT | BOTTOM_OUTLINE_FLAT,
+            LEFT_OUTLINE_DIAGONAL | RIGHT_OUTLINE_ANTIDIAGONAL,
+            LEFT_OUTLINE_ANTIDIAGONAL | RIGHT_OUTLINE_DIAGONAL | BOTTOM_OUTLINE_FLAT,
+            TOP_OUTLINE_DIAGONAL | BOTTOM_OUTLINE_ANTIDIAGONAL | LEFT_OUTLINE_FLAT,
+            TOP_OUTLINE_ANTIDIAGONAL | BOTTOM_OUTLINE_DIAGONAL | RIGHT_OUTLINE_FLAT,
+            TOP_LEFT_CORNER_CHAMFER
+                | TOP_RIGHT_CORNER_CHAMFER
+                | BOTTOM_LEFT_CORNER_CHAMFER
+                | BOTTOM_RIGHT_CORNER_CHAMFER,
+        ];
+        for (index, outline_modes) in outline_modes.into_iter().enumerate() {
+            shapes.push_rect(RectInstance {
+                position: [8.25 + 48.0 * index as f32, 8.0],
+                size: [40.0, 24.0],
+                fill_color: theme.node_fill,
+                outline_color: theme.node_outline,
+                outline_modes,
+                outline_thickness: 2.0,
+                corner_sizes: [4.0; 4],
+                shadow_offset: [0.0, 2.0],
+                shadow_size: 4.0,
+                shadow_opacity: 0.5,
+            });
+        }
+        shapes.push_stroke(Stroke {
+            points: vec![[8.0, 44.0], [72.0, 52.0], [136.0, 44.0]],
+            width: 2.0,
+            color: theme.node_outline,
+            dashes: [6.0, 3.0],
+        });
+        shapes.push_text(Text {
+            sections: vec![
+                Section::node_label(theme, format!("Label ")),
+                Section::node_label_unit(theme, format!("unit")),
+            ],
+            center: [8.0, 72.0],
+            bounds: [120.0, 20.0],
+            horizontal_align: HorizontalAlign::Left,
+            vertical_align: VerticalAlign::Center,
+            overflow: TextOverflow::Clip,
+        });
+        shapes.push_icon(IconInstance {
+            position: [144.3, 60.3],
+            size: 24.0,
+            index: Icons::get("eye"),
+        });
+        shapes.push_image(ImageInstance {
+            position: [176.0, 44.0],
+            size: 40.0,
+            index: 0,
+        });
+        shapes
+    }
+
+    const SIZE: Size = Size {
+        width: 344.0,
+        height: 96.0,
+    };
+
+    /// A gradient to show in the preview image, in sRGB.
+    fn gradient() -> Vec<[u8; 4]> {
+        (0..PREVIEW_TEXTURE_SIZE * PREVIEW_TEXTURE_SIZE)
+            .map(|index| {
+                let x = index % PREVIEW_TEXTURE_SIZE * 255 / PREVIEW_TEXTURE_SIZE;
+                let y = index / PREVIEW_TEXTURE_SIZE * 255 / PREVIEW_TEXTURE_SIZE;
+                [x as u8, y as u8, 128, 255]
+            })
+            .collect()
+    }
+
+    fn render_in_software(theme: &Theme) -> Vec<[u8; 4]> {
+        let mut renderer = SoftwareRenderer::new(SIZE);
+        renderer.set_background(theme.background);
+        renderer.upload_image(0, &gradient());
+        renderer.render_to_image(&[&representative_shapes(theme)])
+    }
+
+    fn assert_close(pixels: &[[u8; 4]], expected: &[[u8; 4]], tolerance: u8, source: &str) {
+        let width = SIZE.width as usize;
+        for (index, (pixel, expected)) in pixels.iter().zip(expected).enumerate() {
+            let close =
+                (0..4).all(|channel| pixel[channel].abs_diff(expected[channel]) <= tolerance);
+            assert!(
+                close,
+                "Pixel {} at {}, {} is {:?}, but {} {:?}",
+                index,
+                index % width,
+                index / width,
+                pixel,
+                source,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn representative_shapes_match_snapshot() {
+        let theme = Theme::dark();
+        let pixels = render_in_software(&theme);
+        let (width, height) = (SIZE.width as u32, SIZE.height as u32);
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            let bytes = pixels.iter().flatten().copied().collect();
+            let image = image::RgbaImage::from_raw(width, height, bytes).unwrap();
+            image.save(SNAPSHOT).unwrap();
+            return;
+        }
+        let expected = image::open(SNAPSHOT).unwrap().into_rgba8();
+        assert_eq!(expected.dimensions(), (width, height));
+        let expected: Vec<_> = expected.pixels().map(|pixel| pixel.0).collect();
+        assert_close(&pixels, &expected, TOLERANCE, "the snapshot has");
+    }
+
+    #[test]
+    #[ignore = "needs a graphics adapter, run with --ignored where there is one"]
+    fn representative_shapes_match_the_gpu() {
+        let theme = Theme::dark();
+        let mut engine = pollster::block_on(RenderEngine::new_offscreen(SIZE))
+            .unwrap_or_else(|err| panic!("{}", err));
+        engine.set_background(theme.background);
+        engine.upload_image(0, &gradient());
+        let expected = engine
+            .render_to_image(&[&representative_shapes(&theme)])
+            .unwrap();
+        let pixels = render_in_software(&theme);
+        assert_eq!(pixels.len(), expected.len());
+        assert_close(&pixels, &expected, GPU_TOLERANCE, "the GPU drew");
+    }
+}
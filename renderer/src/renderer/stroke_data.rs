@@ -0,0 +1,129 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::{VertexAttribute, VertexBufferLayout, VertexStepMode};
+
+/// How long each straight piece of a flattened curve is at most.
+const CURVE_SEGMENT_LENGTH: f32 = 4.0;
+const MAX_CURVE_SEGMENTS: usize = 64;
+
+/// A line of some width through a list of points, with round ends.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stroke {
+    pub points: Vec<[f32; 2]>,
+    pub width: f32,
+    pub color: [f32; 3],
+    /// How long each dash is and how long the gap after it is. The stroke
+    /// is solid if the gap is 0.
+    pub dashes: [f32; 2],
+}
+
+/// One straight piece of a stroke, which is how strokes are drawn.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub(super) struct StrokeSegmentInstance {
+    pub(super) start: [f32; 2],
+    pub(super) end: [f32; 2],
+    pub(super) width: f32,
+    pub(super) color: [f32; 3],
+    /// How far along the stroke the start of the segment is, to keep dashes
+    /// going across segments.
+    pub(super) distance: f32,
+    pub(super) dashes: [f32; 2],
+}
+
+impl Stroke {
+    pub fn polyline(points: Vec<[f32; 2]>, width: f32, color: [f32; 3]) -> Self {
+        Self {
+            points,
+            width,
+            color,
+            dashes: [0.0, 0.0],
+        }
+    }
+
+    /// A cubic bezier curve from start to end, flattened into a polyline.
+    pub fn cubic_bezier(
+        [start, control_1, control_2, end]: [[f32; 2]; 4],
+        width: f32,
+        color: [f32; 3],
+    ) -> Self {
+        // The curve is never longer than its control polygon.
+        let length =
+            distance(start, control_1) + distance(control_1, control_2) + distance(control_2, end);
+        let segments =
+            ((length / CURVE_SEGMENT_LENGTH).ceil() as usize).clamp(1, MAX_CURVE_SEGMENTS);
+        let points = (0..=segments)
+            .map(|index| {
+                let t = index as f32 / segments as f32;
+                let s = 1.0 - t;
+                let weights = [s * s * s, 3.0 * s * s * t, 3.0 * s * t * t, t * t * t];
+                let controls = [start, control_1, control_2, end];
+                [0, 1].map(|axis| (0..4).map(|i| weights[i] * controls[i][axis]).sum::<f32>())
+            })
+            .collect();
+        Self::polyline(points, width, color)
+    }
+
+    pub fn with_dashes(mut self, dash: f32, gap: f32) -> Self {
+        self.dashes = [dash, gap];
+        self
+    }
+
+    pub(super) fn segments(&self) -> Vec<StrokeSegmentInstance> {
+        let mut distance_so_far = 0.0;
+        let mut segments = Vec::new();
+        for pair in self.points.windows(2) {
+            segments.push(StrokeSegmentInstance {
+                start: pair[0],
+                end: pair[1],
+                width: self.width,
+                color: self.color,
+                distance: distance_so_far,
+                dashes: self.dashes,
+            });
+            distance_so_far += distance(pair[0], pair[1]);
+        }
+        segments
+    }
+}
+
+fn distance(a: [f32; 2], b: [f32; 2]) -> f32 {
+    (b[0] - a[0]).hypot(b[1] - a[1])
+}
+
+impl StrokeSegmentInstance {
+    pub(super) fn desc() -> VertexBufferLayout<'static> {
+        const ATTRS: [VertexAttribute; 6] = wgpu::vertex_attr_array![
+            1 => Float32x2,
+            2 => Float32x2,
+            3 => Float32,
+            4 => Float32x3,
+            5 => Float32,
+            6 => Float32x2,
+        ];
+        VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as _,
+            step_mode: VertexStepMode::Instance,
+            attributes: &ATTRS,
+        }
+    }
+
+    /// How much of the pixel centered on the point the segment covers,
+    /// without the parts of it that fall in gaps between dashes. Follows
+    /// stroke_shader.wgsl.
+    pub(super) fn coverage(&self, [x, y]: [f32; 2]) -> f32 {
+        let length = distance(self.start, self.end);
+        let direction = if length > 0.0 {
+            [0, 1].map(|i| (self.end[i] - self.start[i]) / length)
+        } else {
+            [1.0, 0.0]
+        };
+        let relative = [x - self.start[0], y - self.start[1]];
+        let along = (relative[0] * direction[0] + relative[1] * direction[1]).clamp(0.0, length);
+        let closest = [0, 1].map(|i| self.start[i] + direction[i] * along);
+        let [dash, gap] = self.dashes;
+        if gap > 0.0 && (self.distance + along) % (dash + gap) >= dash {
+            return 0.0;
+        }
+        (self.width / 2.0 + 0.5 - distance(closest, [x, y])).clamp(0.0, 1.0)
+    }
+}
@@ -0,0 +1,499 @@
+use std::{collections::BTreeSet, fmt::Write};
+
+use image::{codecs::png::PngEncoder, ColorType, ImageEncoder};
+use theme::{srgb_inverse_transfer_function, Theme, PREVIEW_TEXTURE_SIZE};
+use wgpu_glyph::ab_glyph::FontArc;
+
+use super::{
+    coordinates::Size,
+    fonts::{load_fonts, FONT_BOLD, FONT_LIGHT},
+    icon_data::{ICON_ATLAS_SIZE, ICON_CELL_SIZE},
+    icons::rasterize_icon_atlas,
+    image_data::NUM_IMAGES,
+    rect_data::RectInstance,
+    shapes::{ClipRect, Shapes},
+    stroke_data::Stroke,
+    text::Text,
+};
+
+/// Turns shapes into an SVG document that looks like what RenderEngine
+/// draws. Rects become polygons and strokes polylines, text keeps its
+/// sections and icons and images are embedded as PNGs, so the result stays
+/// sharp at any scale.
+pub struct SvgExporter {
+    fonts: Vec<FontArc>,
+    /// The alpha of every pixel in the icon atlas.
+    icon_atlas: Vec<u8>,
+    images: Vec<Vec<[u8; 4]>>,
+    background: [f32; 4],
+}
+
+impl SvgExporter {
+    pub fn new() -> Self {
+        let image_len = (PREVIEW_TEXTURE_SIZE * PREVIEW_TEXTURE_SIZE) as usize;
+        Self {
+            fonts: load_fonts(),
+            icon_atlas: rasterize_icon_atlas(),
+            images: vec![vec![[0; 4]; image_len]; NUM_IMAGES],
+            background: Theme::dark().background,
+        }
+    }
+
+    // Data is assumed to be in sRGB format, like for RenderEngine.
+    pub fn upload_image(&mut self, index: usize, data: &[[u8; 4]]) {
+        self.images[index] = data.to_vec();
+    }
+
+    pub fn set_background(&mut self, color: [f32; 4]) {
+        self.background = color;
+    }
+
+    /// Writes the layers as they would appear on a target of the given
+    /// size, later layers on top.
+    pub fn export(&self, size: Size, layers: &[&Shapes]) -> String {
+        let mut svg = String::new();
+        writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+            w = size.width,
+            h = size.height
+        )
+        .unwrap();
+        self.write_defs(&mut svg, layers);
+        let [r, g, b, _] = self.background;
+        writeln!(
+            svg,
+            r#"<rect width="100%" height="100%" fill="{}"/>"#,
+            color([r, g, b])
+        )
+        .unwrap();
+        let height = size.height;
+        let mut clips = 0;
+        let mut shadows = 0;
+        for shapes in layers {
+            for (range, clip) in shapes.clip_runs(|counts| counts.rects) {
+                let clipped = open_clip(&mut svg, height, clip, &mut clips);
+                for rect in &shapes.rects[range] {
+                    write_rect(&mut svg, height, rect, &mut shadows);
+                }
+                close_clip(&mut svg, clipped);
+            }
+            for (range, clip) in shapes.clip_runs(|counts| counts.strokes) {
+                let clipped = open_clip(&mut svg, height, clip, &mut clips);
+                for stroke in &shapes.strokes[range] {
+                    write_stroke(&mut svg, height, stroke);
+                }
+                close_clip(&mut svg, clipped);
+            }
+            for (range, clip) in shapes.clip_runs(|counts| counts.texts) {
+                let clipped = open_clip(&mut svg, height, clip, &mut clips);
+                for text in &shapes.texts[range] {
+                    self.write_text(&mut svg, height, text);
+                }
+                close_clip(&mut svg, clipped);
+            }
+            for (range, clip) in shapes.clip_runs(|counts| counts.icons) {
+                let clipped = open_clip(&mut svg, height, clip, &mut clips);
+                for icon in &shapes.icons[range] {
+                    let (index, position) = (icon.index, icon.position);
+                    write_use(&mut svg, height, "icon", index, position, icon.size);
+                }
+                close_clip(&mut svg, clipped);
+            }
+            for (range, clip) in shapes.clip_runs(|counts| counts.images) {
+                let clipped = open_clip(&mut svg, height, clip, &mut clips);
+                for image in &shapes.images[range] {
+                    let (index, position) = (image.index, image.position);
+                    write_use(&mut svg, height, "image", index, position, image.size);
+                }
+                close_clip(&mut svg, clipped);
+            }
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// Embeds every icon and image the layers use once, so that they can be
+    /// referred to by write_use.
+    fn write_defs(&self, svg: &mut String, layers: &[&Shapes]) {
+        let icons: BTreeSet<_> = layers
+            .iter()
+            .flat_map(|shapes| shapes.icons.iter().map(|icon| icon.index))
+            .collect();
+        let images: BTreeSet<_> = layers
+            .iter()
+            .flat_map(|shapes| shapes.images.iter().map(|image| image.index))
+            .collect();
+        svg.push_str("<defs>\n");
+        for index in icons {
+            write_symbol(svg, "icon", index, ICON_CELL_SIZE, &self.icon_pixels(index));
+        }
+        for index in images {
+            // The image shader puts the first row of the texture at the
+            // bottom.
+            let size = PREVIEW_TEXTURE_SIZE as usize;
+            let data = &self.images[index as usize];
+            let pixels: Vec<_> = data.chunks(size).rev().flatten().copied().collect();
+            write_symbol(svg, "image", index, PREVIEW_TEXTURE_SIZE, &pixels);
+        }
+        svg.push_str("</defs>\n");
+    }
+
+    /// Cuts an icon out of the atlas, in the same gray icon_shader.wgsl uses.
+    fn icon_pixels(&self, index: i32) -> Vec<[u8; 4]> {
+        let icons_per_row = ICON_ATLAS_SIZE / ICON_CELL_SIZE;
+        let cell_x = (index as u32 % icons_per_row * ICON_CELL_SIZE) as usize;
+        let cell_y = (index as u32 / icons_per_row * ICON_CELL_SIZE) as usize;
+        let gray = (srgb_inverse_transfer_function(0.5) * 255.0).round() as u8;
+        let mut pixels = Vec::new();
+        for row in cell_y..cell_y + ICON_CELL_SIZE as usize {
+            let start = row * ICON_ATLAS_SIZE as usize + cell_x;
+            let alphas = &self.icon_atlas[start..start + ICON_CELL_SIZE as usize];
+            pixels.extend(alphas.iter().map(|&alpha| [gray, gray, gray, alpha]));
+        }
+        pixels
+    }
+
+    /// Places every character where RenderEngine would, so the text lines
+    /// up even if the viewer falls back to another font.
+    fn write_text(&self, svg: &mut String, height: f32, text: &Text) {
+        let layout = text.layout(&self.fonts, height);
+        svg.push_str(r#"<text font-family="Ubuntu, sans-serif" xml:space="preserve">"#);
+        svg.push('\n');
+        // Each run of glyphs from the same section on the same line becomes
+        // a tspan.
+        let runs = layout
+            .glyphs
+            .chunk_by(|a, b| a.section == b.section && a.glyph.position.y == b.glyph.position.y);
+        for run in runs {
+            let section = &text.sections[run[0].section];
+            let xs: Vec<_> = run
+                .iter()
+                .map(|placed| number(placed.glyph.position.x))
+                .collect();
+            let content: String = run.iter().map(|placed| placed.character).collect();
+            let [r, g, b, a] = section.color;
+            writeln!(
+                svg,
+                r#"<tspan x="{}" y="{}" font-size="{}" font-weight="{}" fill="{}" fill-opacity="{}">{}</tspan>"#,
+                xs.join(" "),
+                number(run[0].glyph.position.y),
+                number(section.size),
+                font_weight(section.style),
+                color([r, g, b]),
+                number(a),
+                escape(&content)
+            )
+            .unwrap();
+        }
+        svg.push_str("</text>\n");
+    }
+}
+
+fn font_weight(style: usize) -> u32 {
+    match style {
+        FONT_LIGHT => 300,
+        FONT_BOLD => 700,
+        _ => 400,
+    }
+}
+
+/// Starts a group of elements that only shows what is inside the clip rect,
+/// if there is one. Returns whether a group was started.
+fn open_clip(svg: &mut String, height: f32, clip: Option<ClipRect>, clips: &mut usize) -> bool {
+    let Some(clip) = clip else {
+        return false;
+    };
+    let [x, y] = clip.position;
+    let [width, clip_height] = clip.size;
+    writeln!(
+        svg,
+        r#"<clipPath id="clip-{clips}"><rect x="{}" y="{}" width="{}" height="{}"/></clipPath>"#,
+        number(x),
+        number(height - y - clip_height),
+        number(width),
+        number(clip_height)
+    )
+    .unwrap();
+    writeln!(svg, r#"<g clip-path="url(#clip-{clips})">"#).unwrap();
+    *clips += 1;
+    true
+}
+
+fn close_clip(svg: &mut String, clipped: bool) {
+    if clipped {
+        svg.push_str("</g>\n");
+    }
+}
+
+/// Draws the same area rect_shader.wgsl does. The outline is the whole
+/// shape in the outline color, with the shape moved in by the thickness on
+/// the outlined sides drawn over it in the fill color.
+fn write_rect(svg: &mut String, height: f32, rect: &RectInstance, shadows: &mut usize) {
+    if rect.shadow_size > 0.0 {
+        write_shadow(svg, height, rect, *shadows);
+        *shadows += 1;
+    }
+    let outer = rect_polygon(rect, 0.0);
+    if rect.outline_modes & 0xff == 0 {
+        write_polygon(svg, height, &outer, rect.fill_color);
+        return;
+    }
+    write_polygon(svg, height, &outer, rect.outline_color);
+    let inner = rect_polygon(rect, rect.outline_thickness);
+    write_polygon(svg, height, &inner, rect.fill_color);
+}
+
+/// The shape of a rect as a polygon, with the outlined sides and corners
+/// moved in by inset. Round corners become a few straight segments.
+fn rect_polygon(rect: &RectInstance, inset: f32) -> Vec<[f32; 2]> {
+    const CORNER_SEGMENTS: usize = 8;
+    let [start_x, start_y] = rect.position;
+    let end_x = start_x + rect.size[0];
+    let end_y = start_y + rect.size[1];
+    let [left_mode, right_mode, bottom_mode, top_mode] = rect.side_modes();
+    let side_inset = |mode: u32| if mode > 0 { inset } else { 0.0 };
+    let edges = rect.edges();
+    let [left, left_slope] = edges.left;
+    let [right, right_slope] = edges.right;
+    let [bottom, bottom_slope] = edges.bottom;
+    let [top, top_slope] = edges.top;
+    let mut shape = vec![
+        [start_x, start_y],
+        [end_x, start_y],
+        [end_x, end_y],
+        [start_x, end_y],
+    ];
+    let half_planes = [
+        [1.0, -left_slope, -left - side_inset(left_mode)],
+        [-1.0, right_slope, right - side_inset(right_mode)],
+        [-bottom_slope, 1.0, -bottom - side_inset(bottom_mode)],
+        [top_slope, -1.0, top - side_inset(top_mode)],
+    ];
+    for half_plane in half_planes {
+        shape = clip(&shape, half_plane);
+    }
+    for (corner, &size) in rect.corner_sizes.iter().enumerate() {
+        if size <= 0.0 {
+            continue;
+        }
+        // The corner of the rect, and which way is inside from it.
+        let [corner_x, corner_y] = [
+            [start_x, start_y],
+            [end_x, start_y],
+            [end_x, end_y],
+            [start_x, end_y],
+        ][corner];
+        let sign_x = if corner == 0 || corner == 3 {
+            1.0
+        } else {
+            -1.0
+        };
+        let sign_y = if corner < 2 { 1.0 } else { -1.0 };
+        let inset = if rect.corner_is_outlined(corner) {
+            inset
+        } else {
+            0.0
+        };
+        if rect.corner_is_chamfered(corner) {
+            let distance = size + inset * 2f32.sqrt();
+            let offset = -sign_x * corner_x - sign_y * corner_y - distance;
+            shape = clip(&shape, [sign_x, sign_y, offset]);
+            continue;
+        }
+        let radius = size - inset;
+        if radius <= 0.0 {
+            continue;
+        }
+        // Cuts along each segment of the arc, keeping the side with its
+        // center.
+        let center = [corner_x + sign_x * size, corner_y + sign_y * size];
+        let arc_point = |index: usize| {
+            let angle = index as f32 / CORNER_SEGMENTS as f32 * std::f32::consts::FRAC_PI_2;
+            [
+                center[0] - sign_x * radius * angle.cos(),
+                center[1] - sign_y * radius * angle.sin(),
+            ]
+        };
+        for index in 0..CORNER_SEGMENTS {
+            let [x0, y0] = arc_point(index);
+            let [x1, y1] = arc_point(index + 1);
+            let [a, b] = [y0 - y1, x1 - x0];
+            let c = -(a * x0 + b * y0);
+            let half_plane = if a * center[0] + b * center[1] + c >= 0.0 {
+                [a, b, c]
+            } else {
+                [-a, -b, -c]
+            };
+            shape = clip(&shape, half_plane);
+        }
+    }
+    shape
+}
+
+/// A blurred rounded rect behind the rect, close to the shadow
+/// rect_shader.wgsl fades out linearly.
+fn write_shadow(svg: &mut String, height: f32, rect: &RectInstance, index: usize) {
+    let half_size = rect.size.map(|length| length / 2.0);
+    let roundest = rect.corner_sizes.iter().copied().fold(0.0, f32::max);
+    let radius = roundest.min(half_size[0].min(half_size[1]));
+    // Half way out is where the linear fade is at half the opacity.
+    let spread = rect.shadow_size / 2.0;
+    let [x, y] = [0, 1].map(|i| rect.position[i] + rect.shadow_offset[i] - spread);
+    let [width, shadow_height] = rect.size.map(|length| length + 2.0 * spread);
+    writeln!(
+        svg,
+        r#"<filter id="shadow-{index}" x="-1" y="-1" width="3" height="3"><feGaussianBlur stdDeviation="{}"/></filter>"#,
+        number(rect.shadow_size / 4.0)
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        r#"<rect x="{}" y="{}" width="{}" height="{}" rx="{}" fill="black" fill-opacity="{}" filter="url(#shadow-{index})"/>"#,
+        number(x),
+        number(height - y - shadow_height),
+        number(width),
+        number(shadow_height),
+        number(radius + spread),
+        number(rect.shadow_opacity)
+    )
+    .unwrap();
+}
+
+/// Keeps the part of a convex polygon where a * x + b * y + c >= 0.
+fn clip(polygon: &[[f32; 2]], [a, b, c]: [f32; 3]) -> Vec<[f32; 2]> {
+    let distance = |[x, y]: [f32; 2]| a * x + b * y + c;
+    let mut clipped = Vec::new();
+    for (index, &point) in polygon.iter().enumerate() {
+        let next = polygon[(index + 1) % polygon.len()];
+        let (here, there) = (distance(point), distance(next));
+        if here >= 0.0 {
+            clipped.push(point);
+        }
+        if (here >= 0.0) != (there >= 0.0) {
+            let t = here / (here - there);
+            clipped.push([0, 1].map(|i| point[i] + (next[i] - point[i]) * t));
+        }
+    }
+    // Edges that cross at a corner of the polygon add that corner twice.
+    clipped.dedup();
+    if clipped.len() > 1 && clipped.first() == clipped.last() {
+        clipped.pop();
+    }
+    clipped
+}
+
+fn write_polygon(svg: &mut String, height: f32, polygon: &[[f32; 2]], fill: [f32; 3]) {
+    if polygon.len() < 3 {
+        return;
+    }
+    let points: Vec<_> = polygon
+        .iter()
+        .map(|[x, y]| format!("{},{}", number(*x), number(height - y)))
+        .collect();
+    writeln!(
+        svg,
+        r#"<polygon points="{}" fill="{}"/>"#,
+        points.join(" "),
+        color(fill)
+    )
+    .unwrap();
+}
+
+/// Dashes end flat like they do in stroke_shader.wgsl, though the ends of
+/// the stroke then do too.
+fn write_stroke(svg: &mut String, height: f32, stroke: &Stroke) {
+    let points: Vec<_> = stroke
+        .points
+        .iter()
+        .map(|[x, y]| format!("{},{}", number(*x), number(height - y)))
+        .collect();
+    let [dash, gap] = stroke.dashes;
+    let dashes = if gap > 0.0 {
+        format!(
+            r#" stroke-dasharray="{} {}" stroke-linecap="butt""#,
+            number(dash),
+            number(gap)
+        )
+    } else {
+        r#" stroke-linecap="round""#.to_owned()
+    };
+    writeln!(
+        svg,
+        r#"<polyline points="{}" fill="none" stroke="{}" stroke-width="{}" stroke-linejoin="round"{}/>"#,
+        points.join(" "),
+        color(stroke.color),
+        number(stroke.width),
+        dashes
+    )
+    .unwrap();
+}
+
+fn write_symbol(svg: &mut String, kind: &str, index: i32, size: u32, pixels: &[[u8; 4]]) {
+    let mut png = Vec::new();
+    PngEncoder::new(&mut png)
+        .write_image(bytemuck::cast_slice(pixels), size, size, ColorType::Rgba8)
+        .unwrap();
+    writeln!(
+        svg,
+        r#"<symbol id="{kind}-{index}" viewBox="0 0 {size} {size}"><image width="{size}" height="{size}" href="data:image/png;base64,{}"/></symbol>"#,
+        base64(&png)
+    )
+    .unwrap();
+}
+
+fn write_use(svg: &mut String, height: f32, kind: &str, index: i32, [x, y]: [f32; 2], size: f32) {
+    writeln!(
+        svg,
+        r##"<use href="#{kind}-{index}" x="{}" y="{}" width="{size}" height="{size}"/>"##,
+        number(x),
+        number(height - y - size)
+    )
+    .unwrap();
+}
+
+/// Linear colors, like the ones shapes use, as sRGB hex codes.
+fn color(linear: [f32; 3]) -> String {
+    let [r, g, b] = linear.map(|channel| {
+        let srgb = srgb_inverse_transfer_function(channel.clamp(0.0, 1.0));
+        (srgb * 255.0).round() as u8
+    });
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// Formats a coordinate without more digits than anyone can see.
+fn number(value: f32) -> String {
+    let formatted = format!("{:.2}", value);
+    let trimmed = formatted.trim_end_matches('0').trim_end_matches('.');
+    match trimmed {
+        "-0" => "0".to_owned(),
+        _ => trimmed.to_owned(),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity((data.len() + 2) / 3 * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(ALPHABET[(bits >> (18 - 6 * index) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
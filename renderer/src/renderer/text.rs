@@ -1,8 +1,10 @@
-use itertools::Itertools;
-use theme::{big_value_color, node_label_color, BIG_VALUE_SIZE, NODE_LABEL_SIZE};
-use wgpu_glyph::{Extra, FontId, HorizontalAlign, Layout, VerticalAlign};
+use ab_glyph::{point, Font, FontArc, Glyph, PxScale, ScaleFont};
+use theme::Theme;
+use wgpu_glyph::{HorizontalAlign, VerticalAlign};
 
-use super::fonts::{FONT_BOLD, FONT_LIGHT};
+use super::fonts::{self, FONT_BOLD, FONT_LIGHT};
+
+const ELLIPSIS: char = '…';
 
 #[derive(Clone, Debug)]
 pub struct Text {
@@ -11,22 +13,268 @@ pub struct Text {
     pub bounds: [f32; 2],
     pub horizontal_align: HorizontalAlign,
     pub vertical_align: VerticalAlign,
+    pub overflow: TextOverflow,
+}
+
+/// What happens to text that is wider than its bounds.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TextOverflow {
+    /// Words that do not fit are left out, but the first one is always kept.
+    #[default]
+    Clip,
+    /// Words that do not fit go on the next line, for as many lines as fit
+    /// in the bounds. Newlines start a new line too.
+    Wrap,
+    /// Characters that do not fit are replaced by an ellipsis.
+    Ellipsis,
 }
 
 impl Text {
-    pub fn as_wgpu_section(&self, screen_height: f32) -> wgpu_glyph::Section {
-        wgpu_glyph::Section {
-            text: self
-                .sections
-                .iter()
-                .map(Section::as_wgpu_text)
-                .collect_vec(),
-            screen_position: (self.center[0], screen_height - self.center[1]),
-            bounds: (self.bounds[0], self.bounds[1]),
-            layout: Layout::default_single_line()
-                .h_align(self.horizontal_align)
-                .v_align(self.vertical_align),
+    /// How wide and tall the text is once laid out in its bounds, so widgets
+    /// can give it as much room as they are able to and size themselves to
+    /// what it actually takes up.
+    pub fn measure(&self) -> [f32; 2] {
+        self.layout(fonts::shared_fonts(), 0.0).size
+    }
+
+    /// Positions glyphs on a target of the given height, in pixels from its
+    /// top left corner. Every backend draws text from this, so they all
+    /// break lines in the same places.
+    pub(super) fn layout(&self, fonts: &[FontArc], screen_height: f32) -> TextLayout {
+        let words = self.words(fonts);
+        let mut lines = match self.overflow {
+            TextOverflow::Clip => vec![self.clipped_line(words)],
+            TextOverflow::Wrap => self.wrapped_lines(words),
+            TextOverflow::Ellipsis => vec![self.ellipsized_line(words, fonts)],
+        };
+        if self.overflow == TextOverflow::Wrap {
+            // Like words, lines that do not fit are left out, except for the
+            // first one.
+            let mut height = 0.0;
+            let mut kept = 0;
+            for line in &lines {
+                if kept > 0 && height + line.height() > self.bounds[1] {
+                    break;
+                }
+                height += line.height();
+                kept += 1;
+            }
+            lines.truncate(kept);
+        }
+
+        let width = lines.iter().map(|line| line.rightmost).fold(0.0, f32::max);
+        let height: f32 = lines.iter().map(Line::height).sum();
+        let screen_x = self.center[0];
+        let screen_y = screen_height - self.center[1];
+        let mut top = match self.vertical_align {
+            VerticalAlign::Top => screen_y,
+            VerticalAlign::Center => screen_y - height / 2.0,
+            VerticalAlign::Bottom => screen_y - height,
+        };
+        let mut glyphs = Vec::new();
+        for line in lines {
+            let left = match self.horizontal_align {
+                HorizontalAlign::Left => screen_x,
+                HorizontalAlign::Center => screen_x - line.rightmost / 2.0,
+                HorizontalAlign::Right => screen_x - line.rightmost,
+            };
+            let baseline = top + line.ascent;
+            top += line.height();
+            glyphs.extend(line.glyphs.into_iter().map(|mut placed| {
+                placed.glyph.position = point(left + placed.glyph.position.x, baseline);
+                placed
+            }));
+        }
+        TextLayout {
+            glyphs,
+            size: [width, height],
+        }
+    }
+
+    /// Splits the text into words, each of which keeps the whitespace after
+    /// it. Glyphs are positioned relative to the start of their word.
+    fn words(&self, fonts: &[FontArc]) -> Vec<Word> {
+        let mut words = Vec::new();
+        let mut word = Word::default();
+        let mut caret = 0.0;
+        let mut previous: Option<(usize, Glyph)> = None;
+        for (section_index, section) in self.sections.iter().enumerate() {
+            let font = &fonts[section.style];
+            let scaled = font.as_scaled(PxScale::from(section.size));
+            for (byte_index, character) in section.text.char_indices() {
+                // Every newline ends a line, so one after another ends an
+                // empty line.
+                let after_newline = character == '\n' && word.ends_line;
+                if after_newline || !character.is_whitespace() && word.trailing_whitespace {
+                    words.push(std::mem::take(&mut word));
+                    caret = 0.0;
+                }
+                word.ascent = word.ascent.max(scaled.ascent());
+                word.descent = word.descent.min(scaled.descent());
+                word.line_gap = word.line_gap.max(scaled.line_gap());
+                if character == '\n' {
+                    word.trailing_whitespace = true;
+                    word.ends_line = true;
+                    previous = None;
+                    continue;
+                }
+                let id = font.glyph_id(character);
+                if let Some((previous_font, previous_glyph)) = &previous {
+                    if *previous_font == section.style {
+                        caret += scaled.kern(previous_glyph.id, id);
+                    }
+                }
+                let glyph = id.with_scale_and_position(section.size, point(caret, 0.0));
+                caret += scaled.h_advance(id);
+                word.width = caret;
+                if character.is_whitespace() {
+                    word.trailing_whitespace = true;
+                } else {
+                    word.width_without_trail = caret;
+                }
+                previous = Some((section.style, glyph.clone()));
+                word.glyphs.push(PlacedGlyph {
+                    section: section_index,
+                    byte_index,
+                    character,
+                    glyph,
+                    right: caret,
+                });
+            }
+        }
+        words.push(word);
+        words
+    }
+
+    /// Like glyph_brush, only keeps the words that fit in the bounds, but
+    /// always keeps the first one.
+    fn clipped_line(&self, words: Vec<Word>) -> Line {
+        let mut line = Line::default();
+        for word in words {
+            if line.words > 0 && line.caret + word.width_without_trail > self.bounds[0] {
+                break;
+            }
+            line.push(word);
+        }
+        line
+    }
+
+    fn wrapped_lines(&self, words: Vec<Word>) -> Vec<Line> {
+        let mut lines = Vec::new();
+        let mut line = Line::default();
+        for word in words {
+            if line.words > 0 && line.caret + word.width_without_trail > self.bounds[0] {
+                lines.push(std::mem::take(&mut line));
+            }
+            let ends_line = word.ends_line;
+            line.push(word);
+            if ends_line {
+                lines.push(std::mem::take(&mut line));
+            }
+        }
+        lines.push(line);
+        lines
+    }
+
+    /// Puts every word on one line, then replaces as many characters at the
+    /// end of it as needed to make room for an ellipsis.
+    fn ellipsized_line(&self, words: Vec<Word>, fonts: &[FontArc]) -> Line {
+        let mut line = Line::default();
+        for word in words {
+            line.push(word);
         }
+        if line.rightmost <= self.bounds[0] {
+            return line;
+        }
+        let Some(last) = line.glyphs.last() else {
+            return line;
+        };
+        let section_index = last.section;
+        let section = &self.sections[section_index];
+        let font = &fonts[section.style];
+        let id = font.glyph_id(ELLIPSIS);
+        let ellipsis_width = font.as_scaled(PxScale::from(section.size)).h_advance(id);
+        while let Some(last) = line.glyphs.last() {
+            let ends_word = last.character.is_whitespace();
+            if !ends_word && last.right + ellipsis_width <= self.bounds[0] {
+                break;
+            }
+            line.glyphs.pop();
+        }
+        let left = line.glyphs.last().map(|last| last.right).unwrap_or(0.0);
+        line.glyphs.push(PlacedGlyph {
+            section: section_index,
+            byte_index: section.text.len(),
+            character: ELLIPSIS,
+            glyph: id.with_scale_and_position(section.size, point(left, 0.0)),
+            right: left + ellipsis_width,
+        });
+        line.rightmost = left + ellipsis_width;
+        line
+    }
+}
+
+/// A glyph along with where in the text it came from.
+#[derive(Clone, Debug)]
+pub(super) struct PlacedGlyph {
+    pub(super) section: usize,
+    pub(super) byte_index: usize,
+    pub(super) character: char,
+    pub(super) glyph: Glyph,
+    /// Where the glyph ends, relative to the start of its word or line.
+    right: f32,
+}
+
+/// Text laid out by Text::layout.
+pub(super) struct TextLayout {
+    /// In the order of the characters they were made from. Newlines and
+    /// anything left out do not have glyphs.
+    pub(super) glyphs: Vec<PlacedGlyph>,
+    pub(super) size: [f32; 2],
+}
+
+#[derive(Default)]
+struct Word {
+    glyphs: Vec<PlacedGlyph>,
+    width: f32,
+    width_without_trail: f32,
+    trailing_whitespace: bool,
+    ends_line: bool,
+    ascent: f32,
+    descent: f32,
+    line_gap: f32,
+}
+
+#[derive(Default)]
+struct Line {
+    glyphs: Vec<PlacedGlyph>,
+    words: usize,
+    caret: f32,
+    rightmost: f32,
+    ascent: f32,
+    descent: f32,
+    line_gap: f32,
+}
+
+impl Line {
+    fn push(&mut self, word: Word) {
+        self.rightmost = self.caret + word.width_without_trail;
+        self.ascent = self.ascent.max(word.ascent);
+        self.descent = self.descent.min(word.descent);
+        self.line_gap = self.line_gap.max(word.line_gap);
+        let caret = self.caret;
+        self.glyphs
+            .extend(word.glyphs.into_iter().map(|mut placed| {
+                placed.glyph.position.x += caret;
+                placed.right += caret;
+                placed
+            }));
+        self.caret += word.width;
+        self.words += 1;
+    }
+
+    fn height(&self) -> f32 {
+        self.ascent - self.descent + self.line_gap
     }
 }
 
@@ -39,33 +287,136 @@ pub struct Section {
 }
 
 impl Section {
-    pub fn node_label(text: String) -> Self {
+    pub fn node_label(theme: &Theme, text: String) -> Self {
         Self {
             text,
-            color: node_label_color(),
-            size: NODE_LABEL_SIZE,
+            color: theme.node_label_color,
+            size: theme.node_label_size,
             style: FONT_LIGHT,
         }
     }
 
-    pub fn big_value_text(text: String) -> Self {
+    /// For the unit after a number in a node label, which is dimmer than the
+    /// number itself.
+    pub fn node_label_unit(theme: &Theme, text: String) -> Self {
+        Self {
+            color: theme.node_label_unit_color,
+            ..Self::node_label(theme, text)
+        }
+    }
+
+    pub fn big_value_text(theme: &Theme, text: String) -> Self {
         Self {
             text,
-            color: big_value_color(),
-            size: BIG_VALUE_SIZE,
+            color: theme.big_value_color,
+            size: theme.big_value_size,
             style: FONT_BOLD,
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn section(text: &str) -> Section {
+        Section {
+            text: text.to_owned(),
+            color: [1.0; 4],
+            size: 20.0,
+            style: FONT_LIGHT,
+        }
+    }
 
-    pub(super) fn as_wgpu_text(&self) -> wgpu_glyph::Text {
-        wgpu_glyph::Text {
-            text: &self.text,
-            scale: self.size.into(),
-            font_id: FontId(self.style),
-            extra: Extra {
-                color: self.color,
-                z: 0.0,
-            },
+    fn text(text: &str, width: f32, overflow: TextOverflow) -> Text {
+        Text {
+            sections: vec![section(text)],
+            center: [0.0, 0.0],
+            bounds: [width, f32::INFINITY],
+            horizontal_align: HorizontalAlign::Left,
+            vertical_align: VerticalAlign::Top,
+            overflow,
         }
     }
+
+    /// The characters on each line that has any.
+    fn lines(text: &Text) -> Vec<String> {
+        let mut lines: Vec<(f32, String)> = Vec::new();
+        for placed in text.layout(fonts::shared_fonts(), 0.0).glyphs {
+            match lines.last_mut() {
+                Some((baseline, line)) if *baseline == placed.glyph.position.y => {
+                    line.push(placed.character)
+                }
+                _ => lines.push((placed.glyph.position.y, placed.character.to_string())),
+            }
+        }
+        lines.into_iter().map(|(_, line)| line).collect()
+    }
+
+    fn line_height() -> f32 {
+        let font = &fonts::shared_fonts()[FONT_LIGHT];
+        let scaled = font.as_scaled(PxScale::from(20.0));
+        scaled.ascent() - scaled.descent() + scaled.line_gap()
+    }
+
+    #[test]
+    fn words_wrap_where_they_stop_fitting() {
+        let two_words = text("aaa aaa", f32::INFINITY, TextOverflow::Clip).measure()[0];
+        let wrapped = |width| lines(&text("aaa aaa aaa", width, TextOverflow::Wrap));
+        assert_eq!(wrapped(two_words + 1.0), ["aaa aaa ", "aaa"]);
+        assert_eq!(wrapped(two_words - 1.0), ["aaa ", "aaa ", "aaa"]);
+        // Words wider than the bounds still get a line of their own.
+        assert_eq!(wrapped(1.0), ["aaa ", "aaa ", "aaa"]);
+    }
+
+    #[test]
+    fn every_newline_starts_a_line() {
+        let measure = |content| text(content, f32::INFINITY, TextOverflow::Wrap).measure();
+        let wrapped = |content| lines(&text(content, f32::INFINITY, TextOverflow::Wrap));
+        assert_eq!(wrapped("a\nb"), ["a", "b"]);
+        assert_eq!(wrapped("a\n\nb c"), ["a", "b c"]);
+        for (content, count) in [("a", 1.0), ("a\nb", 2.0), ("a\n\nb", 3.0), ("\n\n\nb", 4.0)] {
+            let height = measure(content)[1];
+            assert!(
+                (height - count * line_height()).abs() < 0.01,
+                "{:?}",
+                content
+            );
+        }
+    }
+
+    #[test]
+    fn clipping_keeps_the_first_word() {
+        let clipped = |width| lines(&text("aaaaaa bbb ccc", width, TextOverflow::Clip));
+        assert_eq!(clipped(1.0), ["aaaaaa "]);
+        assert_eq!(clipped(f32::INFINITY), ["aaaaaa bbb ccc"]);
+    }
+
+    #[test]
+    fn ellipses_fit_in_the_bounds() {
+        let content = "The quick brown fox jumps over the lazy dog";
+        for width in [20.0, 43.5, 80.0, 150.0] {
+            let text = text(content, width, TextOverflow::Ellipsis);
+            assert!(text.measure()[0] <= width, "{}", width);
+            let lines = lines(&text);
+            assert_eq!(lines.len(), 1);
+            assert!(lines[0].ends_with(ELLIPSIS), "{}", lines[0]);
+            assert!(!lines[0].contains(&format!(" {}", ELLIPSIS)));
+        }
+        let fits = text("fox", f32::INFINITY, TextOverflow::Ellipsis);
+        assert_eq!(lines(&fits), ["fox"]);
+    }
+
+    #[test]
+    fn measure_does_not_depend_on_sections() {
+        let whole = text("AVATAR To", f32::INFINITY, TextOverflow::Clip);
+        let [width, height] = whole.measure();
+        assert!((height - line_height()).abs() < 0.01);
+        let mut split = whole.clone();
+        split.sections = vec![section("AV"), section("ATAR T"), section("o")];
+        assert_eq!(split.measure(), [width, height]);
+        split.center = [100.0, 50.0];
+        split.horizontal_align = HorizontalAlign::Right;
+        assert_eq!(split.measure(), [width, height]);
+    }
 }
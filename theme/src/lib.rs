@@ -1,51 +1,155 @@
 //! All color values are linear unless otherwise noted.
 
-pub const BG: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
-
-pub const FILL_BRIGHTNESS: f32 = 0.005;
-pub const NODE_FILL: [f32; 3] = [FILL_BRIGHTNESS, FILL_BRIGHTNESS, FILL_BRIGHTNESS];
-
-pub const OUTLINE_BRIGHTNESS: f32 = 0.1;
-pub const NODE_OUTLINE: [f32; 3] = [OUTLINE_BRIGHTNESS, OUTLINE_BRIGHTNESS, OUTLINE_BRIGHTNESS];
-
-pub const NODE_WIDTH: f32 = 120.0;
-pub const NODE_LABEL_HEIGHT: f32 = 24.0;
-pub const NODE_CORNER_SIZE: f32 = 6.0;
-pub const NODE_PARAMETER_PADDING: f32 = 2.0;
-pub const NODE_LABEL_PADDING: f32 = 4.0;
-pub const NODE_ICON_PADDING: f32 = NODE_LABEL_PADDING;
-pub const NODE_ICON_SIZE: f32 = 16.0;
-pub const INTER_NODE_PADDING: f32 = 6.0;
-pub const NODE_GUTTER_WIDTH: f32 = INTER_NODE_PADDING * 2.0;
-pub const INTER_PANEL_PADDING: f32 = 18.0;
-
-pub const PREVIEW_WIDGET_SIZE: f32 = 360.0;
+mod load;
+
+pub use self::load::{ThemeError, ThemeFile};
+
+/// How many pixels wide and tall the images shown in previews are. Unlike
+/// the theme, this is fixed because textures are allocated for it.
 pub const PREVIEW_TEXTURE_SIZE: u32 = 90;
-pub const TOOL_BUTTON_SIZE: f32 = 32.0;
-pub const TOOL_ICON_SIZE: f32 = 24.0;
-pub const TOOL_BUTTON_PADDING: f32 = (TOOL_BUTTON_SIZE - TOOL_ICON_SIZE) / 2.0;
-
-pub const NODE_LABEL_COLOR_BRIGHTNESS_SRGB: f32 = 0.7;
-pub const NODE_LABEL_COLOR_OPACITY: f32 = 1.0;
-pub fn node_label_color() -> [f32; 4] {
-    srgba_to_linear_rgba([
-        NODE_LABEL_COLOR_BRIGHTNESS_SRGB,
-        NODE_LABEL_COLOR_BRIGHTNESS_SRGB,
-        NODE_LABEL_COLOR_BRIGHTNESS_SRGB,
-        NODE_LABEL_COLOR_OPACITY,
-    ])
+
+/// The colors and sizes the interface is drawn with. Usually one of the
+/// presets, changed by a theme file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    pub background: [f32; 4],
+    pub node_fill: [f32; 3],
+    pub node_outline: [f32; 3],
+    /// The outline color of the nodes in each column of node editors. The
+    /// first column gets the first color, and columns past the end cycle
+    /// through the rest.
+    pub column_outlines: Vec<[f32; 3]>,
+    /// How far the fill of a column is mixed from the background towards its
+    /// outline color, in sRGB.
+    pub column_fill_amount: f32,
+
+    pub node_width: f32,
+    pub node_label_height: f32,
+    pub node_corner_size: f32,
+    pub node_parameter_padding: f32,
+    pub node_label_padding: f32,
+    /// The same as node_label_padding unless a theme file sets it.
+    pub node_icon_padding: f32,
+    pub node_icon_size: f32,
+    pub inter_node_padding: f32,
+    /// Twice inter_node_padding unless a theme file sets it.
+    pub node_gutter_width: f32,
+    pub inter_panel_padding: f32,
+    pub link_width: f32,
+
+    pub preview_widget_size: f32,
+    pub tool_button_size: f32,
+    pub tool_icon_size: f32,
+
+    pub panel_shadow_offset: [f32; 2],
+    pub panel_shadow_size: f32,
+    pub panel_shadow_opacity: f32,
+
+    pub node_label_color: [f32; 4],
+    /// For units after numbers, which are dimmer than the numbers.
+    pub node_label_unit_color: [f32; 4],
+    /// What is left of node_label_height inside node_label_padding, unless
+    /// a theme file sets it.
+    pub node_label_size: f32,
+    pub big_value_color: [f32; 4],
+    pub big_value_size: f32,
 }
-pub const NODE_LABEL_SIZE: f32 = NODE_LABEL_HEIGHT - 2.0 * NODE_LABEL_PADDING;
-
-pub const BIG_VALUE_SIZE: f32 = 32.0;
-pub const BIG_VALUE_BRIGHTNESS_SRGB: f32 = 1.0;
-pub fn big_value_color() -> [f32; 4] {
-    srgba_to_linear_rgba([
-        BIG_VALUE_BRIGHTNESS_SRGB,
-        BIG_VALUE_BRIGHTNESS_SRGB,
-        BIG_VALUE_BRIGHTNESS_SRGB,
-        1.0,
-    ])
+
+impl Theme {
+    /// Light text on a black background.
+    pub fn dark() -> Self {
+        let node_label_height = 24.0;
+        let node_label_padding = 4.0;
+        let inter_node_padding = 6.0;
+        Self {
+            background: [0.0, 0.0, 0.0, 1.0],
+            node_fill: [0.005, 0.005, 0.005],
+            node_outline: [0.1, 0.1, 0.1],
+            column_outlines: default_column_outlines(),
+            column_fill_amount: 0.2,
+
+            node_width: 120.0,
+            node_label_height,
+            node_corner_size: 6.0,
+            node_parameter_padding: 2.0,
+            node_label_padding,
+            node_icon_padding: node_label_padding,
+            node_icon_size: 16.0,
+            inter_node_padding,
+            node_gutter_width: inter_node_padding * 2.0,
+            inter_panel_padding: 18.0,
+            link_width: 1.5,
+
+            preview_widget_size: 360.0,
+            tool_button_size: 32.0,
+            tool_icon_size: 24.0,
+
+            panel_shadow_offset: [2.0, -4.0],
+            panel_shadow_size: 12.0,
+            panel_shadow_opacity: 0.5,
+
+            node_label_color: srgba_to_linear_rgba([0.7, 0.7, 0.7, 1.0]),
+            node_label_unit_color: srgba_to_linear_rgba([0.45, 0.45, 0.45, 1.0]),
+            node_label_size: node_label_height - 2.0 * node_label_padding,
+            big_value_color: srgba_to_linear_rgba([1.0, 1.0, 1.0, 1.0]),
+            big_value_size: 32.0,
+        }
+    }
+
+    /// Dark text on a light gray background, with the same sizes as the
+    /// dark theme.
+    pub fn light() -> Self {
+        Self {
+            background: srgba_to_linear_rgba([0.92, 0.92, 0.92, 1.0]),
+            node_fill: srgb_to_linear_rgb([0.98, 0.98, 0.98]),
+            node_outline: srgb_to_linear_rgb([0.6, 0.6, 0.6]),
+            column_fill_amount: 0.15,
+            panel_shadow_opacity: 0.2,
+            node_label_color: srgba_to_linear_rgba([0.2, 0.2, 0.2, 1.0]),
+            node_label_unit_color: srgba_to_linear_rgba([0.5, 0.5, 0.5, 1.0]),
+            big_value_color: srgba_to_linear_rgba([0.05, 0.05, 0.05, 1.0]),
+            ..Self::dark()
+        }
+    }
+
+    /// The theme with the given name, which theme files can start from.
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            _ => None,
+        }
+    }
+
+    /// The fill and outline colors of the nodes in a column of node editors,
+    /// at any depth.
+    pub fn column_colors(&self, index: usize) -> [[f32; 3]; 2] {
+        let outlines = &self.column_outlines;
+        let outline = if index < outlines.len() || outlines.len() == 1 {
+            outlines[index.min(outlines.len() - 1)]
+        } else {
+            // The first color is for the root column, so only the others
+            // repeat.
+            outlines[1 + (index - 1) % (outlines.len() - 1)]
+        };
+        let background = self.background;
+        let fill = [0, 1, 2].map(|channel| {
+            let from = srgb_inverse_transfer_function(background[channel]);
+            let to = srgb_inverse_transfer_function(outline[channel]);
+            srgb_transfer_function(from + (to - from) * self.column_fill_amount)
+        });
+        [fill, outline]
+    }
+
+    pub fn tool_button_padding(&self) -> f32 {
+        (self.tool_button_size - self.tool_icon_size) / 2.0
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
 }
 
 macro_rules! hex_color {
@@ -58,6 +162,20 @@ macro_rules! hex_color {
     };
 }
 
+fn default_column_outlines() -> Vec<[f32; 3]> {
+    [
+        [0.5, 0.5, 0.5],
+        hex_color!(0x007BFF),
+        hex_color!(0x8201D9),
+        hex_color!(0xFF006E),
+        hex_color!(0xFF5100),
+        hex_color!(0xFFBE0B),
+    ]
+    .into_iter()
+    .map(srgb_to_linear_rgb)
+    .collect()
+}
+
 pub fn srgb_transfer_function(x: f32) -> f32 {
     if x < 0.04045 {
         return x / 12.92;
@@ -66,6 +184,14 @@ pub fn srgb_transfer_function(x: f32) -> f32 {
     }
 }
 
+pub fn srgb_inverse_transfer_function(x: f32) -> f32 {
+    if x <= 0.0031308 {
+        return x * 12.92;
+    } else {
+        return 1.055 * x.powf(1.0 / 2.4) - 0.055;
+    }
+}
+
 pub fn srgb_to_linear_rgb(srgb_color: [f32; 3]) -> [f32; 3] {
     [
         srgb_transfer_function(srgb_color[0]),
@@ -83,27 +209,3 @@ pub fn srgba_to_linear_rgba(srgba_color: [f32; 4]) -> [f32; 4] {
         srgba_color[3],
     ]
 }
-
-macro_rules! column_color {
-    ($OUTLINE_COLOR:expr) => {
-        [
-            srgb_to_linear_rgb([
-                0.2 * $OUTLINE_COLOR[0],
-                0.2 * $OUTLINE_COLOR[1],
-                0.2 * $OUTLINE_COLOR[2],
-            ]),
-            srgb_to_linear_rgb($OUTLINE_COLOR),
-        ]
-    };
-}
-
-pub fn column_colors() -> [[[f32; 3]; 2]; 6] {
-    [
-        column_color!([0.5, 0.5, 0.5]),
-        column_color!(hex_color!(0x007BFF)),
-        column_color!(hex_color!(0x8201D9)),
-        column_color!(hex_color!(0xFF006E)),
-        column_color!(hex_color!(0xFF5100)),
-        column_color!(hex_color!(0xFFBE0B)),
-    ]
-}
@@ -0,0 +1,358 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime},
+};
+
+use serde_json::{Map, Value};
+
+use super::{srgb_transfer_function, Theme};
+
+/// How often a ThemeFile looks at whether the file changed, since it is
+/// asked every frame.
+const CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Debug)]
+pub struct ThemeError {
+    /// The member of the theme the problem is with, if any.
+    pub key: String,
+    pub message: String,
+}
+
+impl Display for ThemeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.key.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.key, self.message)
+        }
+    }
+}
+
+impl Theme {
+    /// Reads a theme from a JSON object. It starts from the preset named by
+    /// its "base" member, or the dark one, and every other member replaces
+    /// the field of the same name. Colors are written in sRGB, as
+    /// "#rrggbb" or "#rrggbbaa". Sizes that the presets derive from others
+    /// follow the members they are derived from unless they are set too.
+    pub fn from_json(text: &str) -> Result<Self, ThemeError> {
+        let value: Value = serde_json::from_str(text).map_err(|err| ThemeError {
+            key: String::new(),
+            message: err.to_string(),
+        })?;
+        let Some(object) = value.as_object() else {
+            return error("", format!("Expected an object, found {}", value));
+        };
+        let mut theme = match object.get("base") {
+            None => Self::dark(),
+            Some(base) => {
+                let name = string("base", base)?;
+                match Self::preset(name) {
+                    Some(theme) => theme,
+                    None => return error("base", format!("Unknown preset \"{}\"", name)),
+                }
+            }
+        };
+        theme.read_members(object)?;
+        theme.derive_members(object)?;
+        if theme.tool_icon_size > theme.tool_button_size {
+            return error(
+                "tool_icon_size",
+                format!(
+                    "{} does not fit in tool buttons of size {}",
+                    theme.tool_icon_size, theme.tool_button_size
+                ),
+            );
+        }
+        Ok(theme)
+    }
+
+    fn read_members(&mut self, object: &Map<String, Value>) -> Result<(), ThemeError> {
+        for (key, value) in object {
+            let key = key.as_str();
+            match key {
+                "base" => (),
+                "background" => self.background = color(key, value)?,
+                "node_fill" => self.node_fill = opaque_color(key, value)?,
+                "node_outline" => self.node_outline = opaque_color(key, value)?,
+                "column_outlines" => {
+                    let Some(array) = value.as_array().filter(|array| !array.is_empty()) else {
+                        return error(key, format!("Expected a list of colors, found {}", value));
+                    };
+                    self.column_outlines = array
+                        .iter()
+                        .map(|value| opaque_color(key, value))
+                        .collect::<Result<_, _>>()?;
+                }
+                "column_fill_amount" => self.column_fill_amount = fraction(key, value)?,
+                "node_width" => self.node_width = size(key, value)?,
+                "node_label_height" => self.node_label_height = size(key, value)?,
+                "node_corner_size" => self.node_corner_size = distance(key, value)?,
+                "node_parameter_padding" => self.node_parameter_padding = distance(key, value)?,
+                "node_label_padding" => self.node_label_padding = distance(key, value)?,
+                "node_icon_padding" => self.node_icon_padding = distance(key, value)?,
+                "node_icon_size" => self.node_icon_size = size(key, value)?,
+                "inter_node_padding" => self.inter_node_padding = distance(key, value)?,
+                "node_gutter_width" => self.node_gutter_width = distance(key, value)?,
+                "inter_panel_padding" => self.inter_panel_padding = distance(key, value)?,
+                "link_width" => self.link_width = size(key, value)?,
+                "preview_widget_size" => self.preview_widget_size = size(key, value)?,
+                "tool_button_size" => self.tool_button_size = size(key, value)?,
+                "tool_icon_size" => self.tool_icon_size = size(key, value)?,
+                "panel_shadow_offset" => {
+                    let offset = value.as_array().filter(|array| array.len() == 2);
+                    let Some(offset) = offset else {
+                        return error(key, format!("Expected [x, y], found {}", value));
+                    };
+                    self.panel_shadow_offset = [number(key, &offset[0])?, number(key, &offset[1])?];
+                }
+                "panel_shadow_size" => self.panel_shadow_size = distance(key, value)?,
+                "panel_shadow_opacity" => self.panel_shadow_opacity = fraction(key, value)?,
+                "node_label_color" => self.node_label_color = color(key, value)?,
+                "node_label_unit_color" => self.node_label_unit_color = color(key, value)?,
+                "node_label_size" => self.node_label_size = size(key, value)?,
+                "big_value_color" => self.big_value_color = color(key, value)?,
+                "big_value_size" => self.big_value_size = size(key, value)?,
+                _ => return error(key, format!("Unknown member")),
+            }
+        }
+        Ok(())
+    }
+
+    /// Works out the members that were not set from the ones they depend on,
+    /// the same way Theme::dark does.
+    fn derive_members(&mut self, object: &Map<String, Value>) -> Result<(), ThemeError> {
+        if !object.contains_key("node_icon_padding") {
+            self.node_icon_padding = self.node_label_padding;
+        }
+        if !object.contains_key("node_gutter_width") {
+            self.node_gutter_width = self.inter_node_padding * 2.0;
+        }
+        if !object.contains_key("node_label_size") {
+            self.node_label_size = self.node_label_height - 2.0 * self.node_label_padding;
+            if self.node_label_size <= 0.0 {
+                return error(
+                    "node_label_padding",
+                    format!(
+                        "{} leaves no room for labels in a node_label_height of {}",
+                        self.node_label_padding, self.node_label_height
+                    ),
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+fn error<T>(key: &str, message: String) -> Result<T, ThemeError> {
+    Err(ThemeError {
+        key: key.to_owned(),
+        message,
+    })
+}
+
+fn string<'a>(key: &str, value: &'a Value) -> Result<&'a str, ThemeError> {
+    match value.as_str() {
+        Some(string) => Ok(string),
+        None => error(key, format!("Expected a string, found {}", value)),
+    }
+}
+
+fn number(key: &str, value: &Value) -> Result<f32, ThemeError> {
+    match value.as_f64() {
+        Some(number) => Ok(number as f32),
+        None => error(key, format!("Expected a number, found {}", value)),
+    }
+}
+
+/// A number that things are drawn with, which has to be more than 0.
+fn size(key: &str, value: &Value) -> Result<f32, ThemeError> {
+    let number = number(key, value)?;
+    if number <= 0.0 {
+        return error(
+            key,
+            format!("Expected a size greater than 0, found {}", value),
+        );
+    }
+    Ok(number)
+}
+
+/// A gap between things, which cannot be negative.
+fn distance(key: &str, value: &Value) -> Result<f32, ThemeError> {
+    let number = number(key, value)?;
+    if number < 0.0 {
+        return error(
+            key,
+            format!("Expected a distance of at least 0, found {}", value),
+        );
+    }
+    Ok(number)
+}
+
+fn fraction(key: &str, value: &Value) -> Result<f32, ThemeError> {
+    let number = number(key, value)?;
+    if !(0.0..=1.0).contains(&number) {
+        return error(
+            key,
+            format!("Expected a number from 0 to 1, found {}", value),
+        );
+    }
+    Ok(number)
+}
+
+/// Reads "#rrggbb" or "#rrggbbaa" into a linear color.
+fn color(key: &str, value: &Value) -> Result<[f32; 4], ThemeError> {
+    let text = string(key, value)?;
+    let digits = text.strip_prefix('#').unwrap_or("");
+    let channels: Option<Vec<u8>> = (0..digits.len())
+        .step_by(2)
+        .map(|start| {
+            let pair = digits.get(start..start + 2)?;
+            u8::from_str_radix(pair, 16).ok()
+        })
+        .collect();
+    let [r, g, b, a] = match channels.as_deref() {
+        Some(&[r, g, b]) => [r, g, b, 255],
+        Some(&[r, g, b, a]) => [r, g, b, a],
+        _ => {
+            return error(
+                key,
+                format!("Expected \"#rrggbb\" or \"#rrggbbaa\", found {}", value),
+            )
+        }
+    };
+    let channel = |value: u8| srgb_transfer_function(value as f32 / 255.0);
+    // Alpha stays linear, like in srgba_to_linear_rgba.
+    Ok([channel(r), channel(g), channel(b), a as f32 / 255.0])
+}
+
+fn opaque_color(key: &str, value: &Value) -> Result<[f32; 3], ThemeError> {
+    let [r, g, b, a] = color(key, value)?;
+    if a < 1.0 {
+        return error(key, format!("This color cannot be transparent"));
+    }
+    Ok([r, g, b])
+}
+
+/// A theme file that is read again whenever it changes, so themes can be
+/// edited while the app is running.
+pub struct ThemeFile {
+    path: PathBuf,
+    /// When the file was last changed as of the last time it was read.
+    modified: Option<SystemTime>,
+    /// When the file was last looked at.
+    checked: Option<Instant>,
+}
+
+impl ThemeFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            modified: None,
+            checked: None,
+        }
+    }
+
+    /// Reads the theme if the file was created or changed since it was last
+    /// read. Returns None if it was not, if there is no file, or if the file
+    /// was looked at less than CHECK_INTERVAL ago.
+    pub fn reload_if_changed(&mut self) -> Option<Result<Theme, ThemeError>> {
+        let now = Instant::now();
+        if let Some(checked) = self.checked {
+            if now.duration_since(checked) < CHECK_INTERVAL {
+                return None;
+            }
+        }
+        self.checked = Some(now);
+        let modified = std::fs::metadata(&self.path).and_then(|metadata| metadata.modified());
+        let modified = modified.ok()?;
+        if self.modified == Some(modified) {
+            return None;
+        }
+        self.modified = Some(modified);
+        Some(match std::fs::read_to_string(&self.path) {
+            Ok(text) => Theme::from_json(&text),
+            Err(err) => error(
+                "",
+                format!("Could not read {}: {}", self.path.display(), err),
+            ),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn members_replace_the_base() {
+        let theme = Theme::from_json(
+            r##"{
+                "base": "dark",
+                "node_width": 200,
+                "node_label_color": "#ffffff80",
+                "column_outlines": ["#ff0000", "#00ff00"]
+            }"##,
+        )
+        .unwrap();
+        let dark = Theme::dark();
+        assert_eq!(theme.node_width, 200.0);
+        assert_eq!(theme.node_label_color, [1.0, 1.0, 1.0, 128.0 / 255.0]);
+        assert_eq!(
+            theme.column_outlines,
+            vec![[1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+        );
+        assert_eq!(theme.background, dark.background);
+        assert_eq!(Theme::from_json("{}").unwrap(), dark);
+    }
+
+    #[test]
+    fn problems_name_the_member() {
+        let key = |text: &str| Theme::from_json(text).unwrap_err().key;
+        assert_eq!(key(r#"{"base": "neon"}"#), "base");
+        assert_eq!(key(r#"{"node_size": 1}"#), "node_size");
+        assert_eq!(key(r##"{"node_fill": "#ffffff80"}"##), "node_fill");
+        assert_eq!(key(r#"{"background": "white"}"#), "background");
+        assert_eq!(
+            key(r#"{"panel_shadow_offset": [1]}"#),
+            "panel_shadow_offset"
+        );
+        assert_eq!(key("[]"), "");
+    }
+
+    #[test]
+    fn sizes_are_range_checked() {
+        let key = |text: &str| Theme::from_json(text).unwrap_err().key;
+        assert_eq!(key(r#"{"node_width": 0}"#), "node_width");
+        assert_eq!(key(r#"{"node_label_size": -12}"#), "node_label_size");
+        assert_eq!(key(r#"{"inter_node_padding": -1}"#), "inter_node_padding");
+        assert_eq!(
+            key(r#"{"panel_shadow_opacity": 2}"#),
+            "panel_shadow_opacity"
+        );
+        let too_big = r#"{"tool_button_size": 20, "tool_icon_size": 24}"#;
+        assert_eq!(key(too_big), "tool_icon_size");
+        let fits = r#"{"tool_button_size": 20, "tool_icon_size": 20, "inter_node_padding": 0}"#;
+        assert!(Theme::from_json(fits).is_ok());
+        let crowded = r#"{"node_label_height": 10, "node_label_padding": 5}"#;
+        assert_eq!(key(crowded), "node_label_padding");
+    }
+
+    #[test]
+    fn derived_sizes_follow_their_members() {
+        let theme = Theme::from_json(
+            r#"{"node_label_height": 30, "node_label_padding": 5, "inter_node_padding": 4}"#,
+        )
+        .unwrap();
+        assert_eq!(theme.node_label_size, 20.0);
+        assert_eq!(theme.node_icon_padding, 5.0);
+        assert_eq!(theme.node_gutter_width, 8.0);
+        let theme = Theme::from_json(
+            r#"{"node_label_height": 30, "node_label_size": 12, "node_gutter_width": 0}"#,
+        )
+        .unwrap();
+        assert_eq!(theme.node_label_size, 12.0);
+        assert_eq!(theme.node_gutter_width, 0.0);
+        assert_eq!(theme.node_icon_padding, Theme::dark().node_icon_padding);
+    }
+}
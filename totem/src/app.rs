@@ -1,16 +1,21 @@
+mod camera;
+mod literal_edit;
 mod on_event;
+mod palette;
 mod render;
 
 use std::time::Duration;
 
+use arboard::Clipboard;
 use renderer::{
-    winit::{ControlFlow, EventLoop, PhysicalSize, Window, WindowBuilder},
-    Position, RenderEngine,
+    winit::{ControlFlow, EventLoop, ModifiersState, PhysicalSize, Window, WindowBuilder},
+    Position, RenderEngine, SvgExporter,
 };
-use theme::PREVIEW_TEXTURE_SIZE;
+use theme::{Theme, ThemeFile, PREVIEW_TEXTURE_SIZE};
 
+use self::{camera::Camera, literal_edit::LiteralEdit, palette::Palette};
 use crate::{
-    engine::{BuiltinDefinitions, Engine, NodeId, ParameterId},
+    engine::{BuiltinDefinitions, Engine, NodeId, ParameterId, ToolId},
     widgets::{BoundingBox, BoundingBoxKind},
 };
 
@@ -57,6 +62,37 @@ impl PerfCounters {
     }
 }
 
+/// What frames saved by pressing Ctrl+S are named after. They are numbered
+/// so that earlier ones are kept.
+const SVG_EXPORT_NAME: &str = "totem";
+/// A theme to use instead of the dark one, which is read again whenever it
+/// changes.
+const THEME_PATH: &str = "theme.json";
+
+/// A tool the user is assembling out of nodes in the editor, before it is
+/// added to the engine.
+pub struct ToolDraft {
+    pub target_prototype: Option<NodeId>,
+    pub mouse_drag_handler: Option<NodeId>,
+    pub mouse_press_handler: Option<NodeId>,
+    pub mouse_release_handler: Option<NodeId>,
+    pub scroll_handler: Option<NodeId>,
+    pub icon: String,
+}
+
+impl ToolDraft {
+    pub fn new() -> Self {
+        Self {
+            target_prototype: None,
+            mouse_drag_handler: None,
+            mouse_press_handler: None,
+            mouse_release_handler: None,
+            scroll_handler: None,
+            icon: format!("eye"),
+        }
+    }
+}
+
 pub struct App {
     window: Window,
     render_engine: RenderEngine,
@@ -66,10 +102,41 @@ pub struct App {
     root_bbox: BoundingBox,
     selected_node_path: Vec<NodeId>,
     previous_mouse_pos: Position,
+    drag_start_mouse_pos: Position,
+    camera: Camera,
+    /// Whether the middle mouse button is held to pan the camera.
+    panning: bool,
+    modifiers: ModifiersState,
+    preview_start: Position,
     hovering: Option<BoundingBoxKind>,
     dragging: Option<BoundingBoxKind>,
+    /// The tool that was last clicked in the toolbox. Clicking in the
+    /// preview uses it.
+    active_tool: Option<ToolId>,
+    /// The tool whose handlers are currently receiving mouse events.
+    using_tool: Option<ToolId>,
     tool_targets: Vec<(ParameterId, NodeId)>,
     collapse_to_literal: Option<(NodeId, NodeId)>,
+    tool_draft: ToolDraft,
+    /// Open while the user is typing the name of a node to insert.
+    palette: Option<Palette>,
+    literal_edit: Option<LiteralEdit>,
+    /// The system clipboard, which copied subgraphs and exported JSON are
+    /// written to and pasting reads from. None if it could not be opened.
+    clipboard: Option<Clipboard>,
+    /// Nodes previewed next to the active node, each in its own image.
+    pinned_previews: Vec<NodeId>,
+    /// Set by edits that can leave nodes nothing uses anymore, so that the
+    /// next frame removes them.
+    may_have_garbage: bool,
+    /// Gets the same images as render_engine when a frame is being saved as
+    /// SVG.
+    svg_exporter: SvgExporter,
+    /// Set to save the next frame as SVG. Images are only uploaded to
+    /// svg_exporter while this is set.
+    save_svg: bool,
+    theme: Theme,
+    theme_file: ThemeFile,
     perf_counters: PerfCounters,
 }
 
@@ -81,10 +148,10 @@ impl App {
             .build(&event_loop)
             .unwrap();
         let render_engine = RenderEngine::new_for_window(&window).await;
-        render_engine.upload_image(
-            0,
-            &[[255; 4]; (PREVIEW_TEXTURE_SIZE * PREVIEW_TEXTURE_SIZE) as usize],
-        );
+        let blank = [[255; 4]; (PREVIEW_TEXTURE_SIZE * PREVIEW_TEXTURE_SIZE) as usize];
+        render_engine.upload_image(0, &blank);
+        let mut svg_exporter = SvgExporter::new();
+        svg_exporter.upload_image(0, &blank);
         let (computation_engine, builtins) = Engine::new();
         let selected_node_path = vec![computation_engine.root_node()];
         App {
@@ -101,10 +168,29 @@ impl App {
             ),
             selected_node_path,
             previous_mouse_pos: Position { x: 0.0, y: 0.0 },
+            drag_start_mouse_pos: Position { x: 0.0, y: 0.0 },
+            camera: Camera::new(),
+            panning: false,
+            modifiers: ModifiersState::empty(),
+            preview_start: Position { x: 0.0, y: 0.0 },
             hovering: None,
             dragging: None,
+            active_tool: None,
+            using_tool: None,
             tool_targets: vec![],
             collapse_to_literal: None,
+            tool_draft: ToolDraft::new(),
+            palette: None,
+            literal_edit: None,
+            clipboard: Clipboard::new()
+                .map_err(|err| eprintln!("Could not open the clipboard: {}", err))
+                .ok(),
+            pinned_previews: Vec::new(),
+            may_have_garbage: false,
+            svg_exporter,
+            save_svg: false,
+            theme: Theme::dark(),
+            theme_file: ThemeFile::new(THEME_PATH),
             perf_counters: PerfCounters::new(),
         }
         .run(event_loop)
@@ -0,0 +1,49 @@
+use renderer::{Position, Shapes};
+
+use crate::widgets::BoundingBox;
+
+const MIN_ZOOM: f32 = 0.25;
+const MAX_ZOOM: f32 = 4.0;
+
+/// How far the node editors have been panned and zoomed. The preview drawer
+/// is not affected by it.
+pub(super) struct Camera {
+    /// Where the point the editors are laid out from ends up on the screen.
+    offset: Position,
+    zoom: f32,
+}
+
+impl Camera {
+    pub(super) fn new() -> Self {
+        Self {
+            offset: Position::zero(),
+            zoom: 1.0,
+        }
+    }
+
+    pub(super) fn pan(&mut self, dx: f32, dy: f32) {
+        self.offset.x += dx;
+        self.offset.y += dy;
+    }
+
+    /// Multiplies the zoom by the factor, keeping what is under the given
+    /// point on the screen in place.
+    pub(super) fn zoom_at(&mut self, screen: Position, factor: f32) {
+        let zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        let ratio = zoom / self.zoom;
+        self.offset.x = screen.x - (screen.x - self.offset.x) * ratio;
+        self.offset.y = screen.y - (screen.y - self.offset.y) * ratio;
+        self.zoom = zoom;
+    }
+
+    /// Moves shapes laid out for the editors to where they appear on the
+    /// screen.
+    pub(super) fn apply_to_shapes(&self, shapes: &mut Shapes) {
+        shapes.transform(self.offset, self.zoom);
+    }
+
+    /// Like apply_to_shapes, so that hovering uses screen positions too.
+    pub(super) fn apply_to_bbox(&self, bbox: &mut BoundingBox) {
+        bbox.transform(self.offset, self.zoom);
+    }
+}
@@ -0,0 +1,157 @@
+use renderer::Section;
+use theme::Theme;
+
+use super::App;
+use crate::{
+    engine::{BlobLayout, NodeId, NodeOperation, TypedBlob},
+    util::{self, pretty_format_number, pretty_format_number_parts},
+};
+
+/// Text the user is typing to replace the value of a literal.
+pub struct LiteralEdit {
+    pub node: NodeId,
+    pub text: String,
+    /// Why the text could not be used the last time the edit was finished.
+    /// The edit stays open so that the text can be fixed.
+    pub error: Option<String>,
+}
+
+/// How a literal is shown next to the argument it is used for.
+pub fn format_literal(value: &TypedBlob) -> String {
+    let value = value.view();
+    if let Ok(value) = value.as_f32() {
+        pretty_format_number(value)
+    } else if let Ok(value) = value.as_string() {
+        format!("\"{}\"", value)
+    } else {
+        format!("{:?}", value)
+    }
+}
+
+/// Like format_literal, but with the suffix of numbers dimmed.
+pub fn literal_sections(theme: &Theme, value: &TypedBlob) -> Vec<Section> {
+    if let Ok(value) = value.view().as_f32() {
+        let (digits, suffix) = pretty_format_number_parts(value);
+        vec![
+            Section::node_label(theme, digits),
+            Section::node_label_unit(theme, suffix),
+        ]
+    } else {
+        vec![Section::node_label(theme, format_literal(value))]
+    }
+}
+
+/// Turns text typed by the user into a value that replaces old_value.
+/// Quoted text becomes a string, and strings take any other text as it is.
+/// Otherwise the text is evaluated as arithmetic, which stays an integer if
+/// old_value was one and the text is a whole number without a decimal point.
+/// The result may have a different type than old_value, which the engine
+/// checks against whatever uses the literal.
+fn parse_literal(text: &str, old_value: &TypedBlob) -> Result<TypedBlob, String> {
+    let trimmed = text.trim();
+    let quoted = trimmed
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'));
+    if let Some(string) = quoted {
+        return Ok(string.to_owned().into());
+    } else if old_value.view().as_string().is_ok() {
+        return Ok(text.to_owned().into());
+    }
+    let number = util::parse_number(trimmed)?;
+    let whole = number.fract() == 0.0
+        && !trimmed.contains('.')
+        && (i32::MIN as f32..=i32::MAX as f32).contains(&number);
+    if whole && old_value.layout() == &BlobLayout::Integer {
+        Ok((number as i32).into())
+    } else {
+        Ok(number.into())
+    }
+}
+
+impl App {
+    pub(super) fn start_literal_edit(&mut self, node: NodeId) {
+        let NodeOperation::Literal(value) = &self.computation_engine[node].operation else {
+            return;
+        };
+        let value = value.view();
+        let text = if let Ok(value) = value.as_i32() {
+            format!("{}", value)
+        } else if let Ok(value) = value.as_f32() {
+            format!("{}", value)
+        } else if let Ok(value) = value.as_string() {
+            value.to_owned()
+        } else {
+            // Structs and arrays are edited through their components.
+            return;
+        };
+        self.literal_edit = Some(LiteralEdit {
+            node,
+            text,
+            error: None,
+        });
+    }
+
+    /// Replaces the literal with the typed value. If that does not work,
+    /// the edit stays open and shows why.
+    pub(super) fn finish_literal_edit(&mut self) {
+        let Some(mut edit) = self.literal_edit.take() else {
+            return;
+        };
+        let old_value = self.computation_engine[edit.node].as_literal();
+        let replaced = parse_literal(&edit.text, old_value).and_then(|value| {
+            self.computation_engine
+                .replace_literal(edit.node, value)
+                .map_err(|err| err.to_string())
+        });
+        match replaced {
+            Ok(replacement) => {
+                self.may_have_garbage = true;
+                self.replace_in_selection(edit.node, replacement)
+            }
+            Err(err) => {
+                edit.error = Some(err);
+                self.literal_edit = Some(edit);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_literal_infers_the_type() {
+        let integer = TypedBlob::from(3);
+        let float = TypedBlob::from(3.0);
+        let string = TypedBlob::from(format!("text"));
+        assert_eq!(
+            parse_literal("2 * 4", &integer).unwrap().view().as_i32(),
+            Ok(8)
+        );
+        assert_eq!(parse_literal("2", &float).unwrap().view().as_f32(), Ok(2.0));
+        assert_eq!(
+            parse_literal("1.5", &float).unwrap().view().as_f32(),
+            Ok(1.5)
+        );
+        assert_eq!(
+            parse_literal("1.5", &integer).unwrap().view().as_f32(),
+            Ok(1.5)
+        );
+        assert_eq!(
+            parse_literal("3.0", &integer).unwrap().layout(),
+            &BlobLayout::Float
+        );
+        assert_eq!(
+            parse_literal("1Tl", &integer).unwrap().layout(),
+            &BlobLayout::Float
+        );
+        assert!(parse_literal("1 +", &integer).is_err());
+        let parsed = parse_literal("\"1\"", &float).unwrap();
+        assert_eq!(parsed.view().as_string(), Ok("1"));
+        let parsed = parse_literal(" \"hello\" ", &string).unwrap();
+        assert_eq!(parsed.view().as_string(), Ok("hello"));
+        let parsed = parse_literal("1 + 1", &string).unwrap();
+        assert_eq!(parsed.view().as_string(), Ok("1 + 1"));
+    }
+}
@@ -2,22 +2,32 @@ use itertools::Itertools;
 use maplit::hashmap;
 use renderer::{
     winit::{
-        ControlFlow, ElementState, Event, KeyboardInput, MouseButton, PhysicalPosition,
-        VirtualKeyCode, WindowEvent,
+        ControlFlow, ElementState, Event, KeyboardInput, ModifiersState, MouseButton,
+        MouseScrollDelta, PhysicalPosition, VirtualKeyCode, WindowEvent,
     },
-    Position,
+    Icons, Position, NUM_IMAGES,
 };
+use theme::PREVIEW_TEXTURE_SIZE;
 
-use super::App;
+use super::{camera::Camera, palette::Palette, App, ToolDraft};
 use crate::{
-    engine::{TypedBlob, Node, NodeId, NodeOperation, ParameterId, ToolId},
-    widgets::BoundingBoxKind,
+    engine::{
+        EditError, Node, NodeId, NodeOperation, ToolEvent, ToolId, ToolParameterKind, TypedBlob,
+        TOOL_TARGET_PREFIX, TOOL_WILDCARD_INPUT_NAME,
+    },
+    widgets::{BoundingBoxKind, NodeAction},
 };
 
+/// How much scrolling one line with ctrl held zooms the editors.
+const ZOOM_PER_LINE: f32 = 1.1;
+
 impl App {
     pub(super) fn on_event(&mut self, event: Event<()>) {
         match event {
-            Event::RedrawRequested(_window_id) => self.render(),
+            Event::RedrawRequested(_window_id) => {
+                self.render();
+                self.collect_garbage()
+            }
             Event::WindowEvent {
                 window_id: _,
                 event,
@@ -44,10 +54,13 @@ impl App {
         match event {
             WindowEvent::CloseRequested => self.control_flow = ControlFlow::Exit,
             WindowEvent::KeyboardInput { input, .. } => self.on_keyboard_input(input),
+            WindowEvent::ReceivedCharacter(character) => self.on_received_character(character),
             WindowEvent::CursorMoved { position, .. } => {
                 self.on_mouse_move(self.physical_pos_to_render_pos(position))
             }
             WindowEvent::MouseInput { state, button, .. } => self.on_mouse_input(button, state),
+            WindowEvent::MouseWheel { delta, .. } => self.on_scroll(delta),
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers,
             _ => (),
         }
     }
@@ -61,13 +74,296 @@ impl App {
         }
     }
 
+    fn on_received_character(&mut self, character: char) {
+        if let Some(edit) = &mut self.literal_edit {
+            if !character.is_control() {
+                edit.text.push(character);
+                edit.error = None;
+            }
+        } else if let Some(palette) = &mut self.palette {
+            if !character.is_control() {
+                palette.query.push(character);
+                palette.selected = 0;
+            }
+        } else if character == ' ' {
+            self.palette = Some(Palette::new(false));
+        }
+    }
+
     fn on_key_down(&mut self, code: VirtualKeyCode) {
+        if let Some(edit) = &mut self.literal_edit {
+            match code {
+                VirtualKeyCode::Escape => self.literal_edit = None,
+                VirtualKeyCode::Back => {
+                    edit.text.pop();
+                    edit.error = None;
+                }
+                VirtualKeyCode::Return => self.finish_literal_edit(),
+                _ => (),
+            }
+            return;
+        }
+        if self.palette.is_some() {
+            return self.on_palette_key_down(code);
+        }
         match code {
             VirtualKeyCode::Escape => self.control_flow = ControlFlow::Exit,
+            VirtualKeyCode::Up if self.modifiers.shift() => {
+                self.perform_node_action(self.active_node(), NodeAction::MoveUp)
+            }
+            VirtualKeyCode::Down if self.modifiers.shift() => {
+                self.perform_node_action(self.active_node(), NodeAction::MoveDown)
+            }
+            VirtualKeyCode::C if self.modifiers.ctrl() && self.modifiers.shift() => {
+                let json = self
+                    .computation_engine
+                    .export_json(self.active_node(), &self.builtins);
+                self.copy_text(json)
+            }
+            VirtualKeyCode::L if self.modifiers.ctrl() => {
+                let node = self.active_node();
+                let name = self.computation_engine[node].operation.name();
+                let json = self
+                    .computation_engine
+                    .export_library_json(node, &name, &self.builtins);
+                self.copy_text(json)
+            }
+            VirtualKeyCode::C if self.modifiers.ctrl() => {
+                let text = self
+                    .computation_engine
+                    .copy_subgraph(self.active_node(), &self.builtins);
+                self.copy_text(text)
+            }
+            VirtualKeyCode::V if self.modifiers.ctrl() => self.paste(),
+            VirtualKeyCode::S if self.modifiers.ctrl() => self.save_svg = true,
+            VirtualKeyCode::E if self.modifiers.ctrl() => {
+                let names = self.builtins.script_names();
+                let root = self.computation_engine.root_node();
+                match self.computation_engine.write_script(root, &names) {
+                    Ok(script) => self.copy_text(script),
+                    Err(err) => eprintln!("{}", err),
+                }
+            }
+            VirtualKeyCode::Home => self.camera = Camera::new(),
+            VirtualKeyCode::Up => self.select_input(),
+            VirtualKeyCode::Down => self.select_consumer(),
+            VirtualKeyCode::Left => self.select_parent(),
+            VirtualKeyCode::Right | VirtualKeyCode::Return => self.select_first_argument(),
+            VirtualKeyCode::Tab => self.select_next_argument(),
+            VirtualKeyCode::Delete => {
+                self.perform_node_action(self.active_node(), NodeAction::Delete)
+            }
+            VirtualKeyCode::P => self.tool_draft.target_prototype = Some(self.active_node()),
+            VirtualKeyCode::H => self.tool_draft.mouse_drag_handler = Some(self.active_node()),
+            VirtualKeyCode::J => self.tool_draft.mouse_press_handler = Some(self.active_node()),
+            VirtualKeyCode::K => self.tool_draft.mouse_release_handler = Some(self.active_node()),
+            VirtualKeyCode::L => self.tool_draft.scroll_handler = Some(self.active_node()),
+            VirtualKeyCode::T => self.mark_active_tool_parameter(ToolParameterKind::Target),
+            VirtualKeyCode::W => self.mark_active_tool_parameter(ToolParameterKind::WildcardInput),
+            VirtualKeyCode::O => self.mark_active_tool_parameter(ToolParameterKind::Ordinary),
+            VirtualKeyCode::I => {
+                let names = Icons::names().collect_vec();
+                let current = names.iter().position(|&name| name == self.tool_draft.icon);
+                let next = current.map_or(0, |index| (index + 1) % names.len());
+                self.tool_draft.icon = names[next].to_owned();
+            }
+            VirtualKeyCode::N => self.create_drafted_tool(),
             _ => (),
         }
     }
 
+    fn on_palette_key_down(&mut self, code: VirtualKeyCode) {
+        let palette = self.palette.as_mut().unwrap();
+        match code {
+            VirtualKeyCode::Escape => self.palette = None,
+            VirtualKeyCode::Back => {
+                palette.query.pop();
+                palette.selected = 0;
+            }
+            VirtualKeyCode::Up => palette.selected = palette.selected.saturating_sub(1),
+            VirtualKeyCode::Down => {
+                let query = palette.query.clone();
+                let count = self.matching_palette_entries(&query).len();
+                let palette = self.palette.as_mut().unwrap();
+                palette.selected = (palette.selected + 1).min(count.saturating_sub(1));
+            }
+            VirtualKeyCode::Return => self.insert_selected_palette_entry(),
+            _ => (),
+        }
+    }
+
+    /// The nodes at the bottom of each input chain in the column that the
+    /// active node is in.
+    fn active_column_roots(&self) -> Vec<NodeId> {
+        match &self.selected_node_path[..] {
+            [.., parent, _] => self.computation_engine[*parent].arguments.clone(),
+            _ => vec![self.computation_engine.root_node()],
+        }
+    }
+
+    fn select_active(&mut self, node: NodeId) {
+        *self.selected_node_path.last_mut().unwrap() = node;
+    }
+
+    fn select_input(&mut self) {
+        if let Some(input) = self.computation_engine[self.active_node()].input {
+            self.select_active(input);
+        }
+    }
+
+    fn select_consumer(&mut self) {
+        let active = self.active_node();
+        for root in self.active_column_roots() {
+            let mut next_node = Some(root);
+            while let Some(node) = next_node {
+                next_node = self.computation_engine[node].input;
+                if next_node == Some(active) {
+                    return self.select_active(node);
+                }
+            }
+        }
+    }
+
+    fn select_parent(&mut self) {
+        if self.selected_node_path.len() > 1 {
+            self.selected_node_path.pop();
+        }
+    }
+
+    fn select_first_argument(&mut self) {
+        if let Some(&argument) = self.computation_engine[self.active_node()]
+            .arguments
+            .first()
+        {
+            self.selected_node_path.push(argument);
+        }
+    }
+
+    /// Moves to the next argument of the node in the previous column.
+    fn select_next_argument(&mut self) {
+        let roots = self.active_column_roots();
+        let active = self.active_node();
+        let in_chain = |mut node: NodeId| loop {
+            if node == active {
+                return true;
+            }
+            match self.computation_engine[node].input {
+                Some(input) => node = input,
+                None => return false,
+            }
+        };
+        if let Some(index) = roots.iter().position(|&root| in_chain(root)) {
+            self.select_active(roots[(index + 1) % roots.len()]);
+        }
+    }
+
+    pub(super) fn perform_node_action(&mut self, node: NodeId, action: NodeAction) {
+        let result = match action {
+            NodeAction::InsertAbove | NodeAction::InsertBelow => {
+                let above = action == NodeAction::InsertAbove;
+                if above && self.computation_engine[node].input.is_none() {
+                    return eprintln!("There is nothing above this node to insert after.");
+                }
+                self.palette = Some(Palette::new(above));
+                Ok(())
+            }
+            NodeAction::MoveUp => self.computation_engine.move_up(node),
+            NodeAction::MoveDown => self.computation_engine.move_down(node),
+            NodeAction::Duplicate => {
+                // The copied literals are left behind if the copy cannot be
+                // inserted.
+                self.may_have_garbage = true;
+                self.computation_engine
+                    .duplicate_node(node)
+                    .map(|copy| self.replace_in_selection(node, copy))
+            }
+            NodeAction::Delete => {
+                self.may_have_garbage = true;
+                self.computation_engine
+                    .delete_node(node)
+                    .map(|input| self.replace_in_selection(node, input))
+            }
+            NodeAction::Pin => {
+                self.toggle_pinned_preview(node);
+                Ok(())
+            }
+        };
+        if let Err(err) = result {
+            eprintln!("{}", err);
+        }
+    }
+
+    /// Pins a preview of the node, or a snapshot of it if shift is held.
+    /// Pinning a node that is already pinned unpins it instead.
+    fn toggle_pinned_preview(&mut self, node: NodeId) {
+        if !self.modifiers.shift() {
+            if let Some(index) = self.pinned_previews.iter().position(|&n| n == node) {
+                self.pinned_previews.remove(index);
+                self.may_have_garbage = true;
+                return;
+            }
+        }
+        // The first image is used by the preview of the active node.
+        if self.pinned_previews.len() + 1 >= NUM_IMAGES {
+            return eprintln!("At most {} previews can be pinned.", NUM_IMAGES - 1);
+        }
+        let node = if self.modifiers.shift() {
+            self.computation_engine.snapshot(node)
+        } else {
+            node
+        };
+        self.pinned_previews.push(node);
+    }
+
+    fn mark_active_tool_parameter(&mut self, kind: ToolParameterKind) {
+        let active = self.active_node();
+        if let Err(err) = self.computation_engine.mark_tool_parameter(active, kind) {
+            eprintln!("{}", err);
+        }
+    }
+
+    fn create_drafted_tool(&mut self) {
+        let (Some(target_prototype), Some(mouse_drag_handler)) = (
+            self.tool_draft.target_prototype,
+            self.tool_draft.mouse_drag_handler,
+        ) else {
+            eprintln!("Pick a target prototype (P) and a drag handler (H) first.");
+            return;
+        };
+        let name = format!(
+            "Custom Tool {}",
+            self.computation_engine.tools().count() + 1
+        );
+        let tool = match self.computation_engine.create_tool(
+            name,
+            self.tool_draft.icon.clone(),
+            target_prototype,
+            mouse_drag_handler,
+        ) {
+            Ok(tool) => tool,
+            Err(err) => return eprintln!("{}", err),
+        };
+        let other_handlers = [
+            (ToolEvent::MousePress, self.tool_draft.mouse_press_handler),
+            (
+                ToolEvent::MouseRelease,
+                self.tool_draft.mouse_release_handler,
+            ),
+            (ToolEvent::Scroll, self.tool_draft.scroll_handler),
+        ];
+        for (event, handler) in other_handlers {
+            if let Some(handler) = handler {
+                if let Err(err) = self
+                    .computation_engine
+                    .set_tool_handler(tool, event, handler)
+                {
+                    eprintln!("{}", err);
+                }
+            }
+        }
+        self.tool_draft = ToolDraft::new();
+    }
+
     fn on_key_up(&mut self, code: VirtualKeyCode) {
         match code {
             _ => (),
@@ -82,101 +378,201 @@ impl App {
     }
 
     fn on_mouse_down(&mut self, button: MouseButton) {
-        if button == MouseButton::Left {
+        if button == MouseButton::Middle {
+            self.panning = true;
+        } else if button == MouseButton::Left {
+            self.finish_literal_edit();
             self.dragging = self.hovering.clone();
-            if let &Some(BoundingBoxKind::InvokeTool(tool_id)) = &self.dragging {
-                let tool = &self.computation_engine.get_tool(tool_id);
-                let target_prototype = tool.target_prototype;
-                self.collapse_to_literal = None;
-                if let Ok(targets) =
-                    self.match_prototype_to_node(target_prototype, self.active_node())
-                {
-                    self.tool_targets = targets;
-                } else {
-                    let should_collapse = if let NodeOperation::Literal(..) =
-                        &self.computation_engine[self.active_node()].operation
-                    {
-                        true
-                    } else {
-                        false
-                    };
-                    let old_active = self.active_node();
-                    let new_active = self.insert_prototype(target_prototype, self.active_node());
-                    if should_collapse {
-                        self.collapse_to_literal = Some((old_active, new_active));
-                    }
-                    *self.selected_node_path.last_mut().unwrap() = new_active;
-                    self.tool_targets = self
-                        .match_prototype_to_node(target_prototype, new_active)
-                        .unwrap();
+            self.drag_start_mouse_pos = self.previous_mouse_pos;
+            let tool = match &self.dragging {
+                &Some(BoundingBoxKind::InvokeTool(tool_id)) => {
+                    self.active_tool = Some(tool_id);
+                    Some(tool_id)
                 }
+                Some(BoundingBoxKind::Preview) => self.active_tool,
+                _ => None,
+            };
+            if let Some(tool_id) = tool {
+                self.prepare_tool_targets(tool_id);
+                self.using_tool = Some(tool_id);
+                self.run_tool_handler(tool_id, ToolEvent::MousePress, (0.0, 0.0), (0.0, 0.0));
+            }
+        }
+    }
+
+    fn on_scroll(&mut self, delta: MouseScrollDelta) {
+        let (x, y) = match delta {
+            MouseScrollDelta::LineDelta(x, y) => (x, y),
+            // Treat a line as being as tall as a node.
+            MouseScrollDelta::PixelDelta(pos) => (
+                pos.x as f32 / self.theme.node_label_height,
+                pos.y as f32 / self.theme.node_label_height,
+            ),
+        };
+        let tool = match &self.hovering {
+            &Some(BoundingBoxKind::InvokeTool(tool_id)) => Some(tool_id),
+            Some(BoundingBoxKind::Preview) => self.active_tool,
+            _ => None,
+        };
+        if let (Some(tool_id), None) = (tool, self.using_tool) {
+            self.prepare_tool_targets(tool_id);
+            self.run_tool_handler(tool_id, ToolEvent::Scroll, (0.0, 0.0), (x, y));
+            self.finish_tool_use();
+        } else if tool.is_none() {
+            // Scrolling anywhere else moves the editors, or zooms them with
+            // ctrl held. Shift turns vertical scrolling horizontal.
+            let line_height = self.theme.node_label_height;
+            if self.modifiers.ctrl() {
+                self.camera
+                    .zoom_at(self.previous_mouse_pos, ZOOM_PER_LINE.powf(y));
+            } else if self.modifiers.shift() {
+                self.camera.pan(y * line_height, 0.0);
+            } else {
+                self.camera.pan(x * line_height, -y * line_height);
+            }
+        }
+    }
+
+    /// Finds the literals the tool should edit, inserting the tool's prototype
+    /// after the active node if it does not already match.
+    fn prepare_tool_targets(&mut self, tool_id: ToolId) {
+        let tool = &self.computation_engine.get_tool(tool_id);
+        let target_prototype = tool.target_prototype;
+        self.collapse_to_literal = None;
+        let matches = self
+            .computation_engine
+            .find_prototype_matches(target_prototype, self.active_node());
+        if let Some(closest) = matches.as_ref().ok().and_then(|matches| matches.first()) {
+            self.tool_targets = closest.targets.clone();
+        } else if !self.prototype_has_wildcard_input(target_prototype) {
+            // There is nothing to insert the prototype after, so the
+            // tool can only be used on nodes it already matches.
+            if let Err(err) = matches {
+                eprintln!("{}", err);
+            }
+            self.tool_targets = vec![];
+        } else {
+            let should_collapse = if let NodeOperation::Literal(..) =
+                &self.computation_engine[self.active_node()].operation
+            {
+                true
+            } else {
+                false
+            };
+            let old_active = self.active_node();
+            let new_active = self.insert_prototype(target_prototype, self.active_node());
+            if should_collapse {
+                self.collapse_to_literal = Some((old_active, new_active));
             }
+            *self.selected_node_path.last_mut().unwrap() = new_active;
+            self.tool_targets = match self
+                .computation_engine
+                .match_prototype(target_prototype, new_active)
+            {
+                Ok(found) => found.targets,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    vec![]
+                }
+            };
         }
     }
 
-    fn match_prototype_to_node(
-        &self,
-        prototype: NodeId,
-        node: NodeId,
-    ) -> Result<Vec<(ParameterId, NodeId)>, ()> {
+    fn prototype_has_wildcard_input(&self, prototype: NodeId) -> bool {
         let prototype = &self.computation_engine[prototype];
-        if let &NodeOperation::Parameter(param_id) = &prototype.operation {
-            if let Ok(name) = &self.computation_engine[prototype.arguments[0]]
+        if let NodeOperation::Parameter(..) = &prototype.operation {
+            let name = self.computation_engine[prototype.arguments[0]]
                 .as_literal()
-                .view()
+                .view();
+            if name
                 .as_string()
+                .unwrap()
+                .starts_with(TOOL_WILDCARD_INPUT_NAME)
             {
-                if name.starts_with("SPECIAL TOOL TARGET ") {
-                    if let NodeOperation::Literal(..) = &self.computation_engine[node].operation {
-                        return Ok(vec![(param_id, node)]);
-                    }
-                } else if name.starts_with("SPECIAL TOOL WILDCARD") {
-                    return Ok(vec![]);
-                }
+                return true;
             }
         }
-        let node = &self.computation_engine[node];
-        if prototype.operation == node.operation {
-            assert_eq!(prototype.arguments.len(), node.arguments.len());
-            assert_eq!(prototype.input.is_some(), node.input.is_some());
-            let mut result = vec![];
-            if let Some(prototype_input) = prototype.input {
-                result.append(
-                    &mut self.match_prototype_to_node(prototype_input, node.input.unwrap())?,
-                );
-            }
-            for index in 0..prototype.arguments.len() {
-                result.append(
-                    &mut self.match_prototype_to_node(
-                        prototype.arguments[index],
-                        node.arguments[index],
-                    )?,
-                );
-            }
-            return Ok(result);
+        if let Some(input) = prototype.input {
+            self.prototype_has_wildcard_input(input)
+        } else {
+            false
         }
-        Err(())
     }
 
     fn insert_prototype(&mut self, prototype: NodeId, after: NodeId) -> NodeId {
         let (prototype_instance, instance_bottom) = self.instantiate_prototype(prototype, after);
-        self.replace_references(after, prototype_instance);
-        self.computation_engine[instance_bottom.unwrap()].input = Some(after);
-        self.computation_engine.mark_dirty(instance_bottom.unwrap());
+        let result = self
+            .replace_references(after, prototype_instance)
+            .and_then(|()| {
+                self.computation_engine
+                    .set_input(instance_bottom.unwrap(), Some(after))
+            });
+        if let Err(err) = result {
+            eprintln!("{}", err);
+        }
         prototype_instance
     }
 
-    fn replace_references(&mut self, to: NodeId, with: NodeId) {
-        for node in self.computation_engine.nodes_mut() {
-            for arg in node.arguments.iter_mut().chain(node.input.iter_mut()) {
-                if *arg == to {
-                    *arg = with;
-                }
-            }
+    /// Puts text on the system clipboard.
+    fn copy_text(&mut self, text: String) {
+        let result = match &mut self.clipboard {
+            Some(clipboard) => clipboard.set_text(text).map_err(|err| err.to_string()),
+            None => Err(format!("The clipboard is not available")),
+        };
+        if let Err(err) = result {
+            eprintln!("{}", err);
         }
-        if self.computation_engine.root_node() == to {
-            self.computation_engine.set_root(with);
+    }
+
+    /// Replaces the active node with a copy of the subgraph in the
+    /// clipboard, which can hold either copied text or a JSON document.
+    fn paste(&mut self) {
+        let text = match &mut self.clipboard {
+            Some(clipboard) => clipboard.get_text().map_err(|err| err.to_string()),
+            None => Err(format!("The clipboard is not available")),
+        };
+        let text = match text {
+            Ok(text) => text,
+            Err(err) => return eprintln!("{}", err),
+        };
+        let pasted = if text.trim_start().starts_with('{') {
+            self.computation_engine
+                .import_json(&text, &self.builtins)
+                .map_err(|err| err.to_string())
+        } else {
+            self.computation_engine
+                .paste_subgraph(&text, &self.builtins)
+                .map_err(|err| err.to_string())
+        };
+        let pasted = match pasted {
+            Ok(pasted) => pasted,
+            Err(err) => return eprintln!("{}", err),
+        };
+        // The pasted nodes are left behind if they cannot replace the
+        // active node.
+        self.may_have_garbage = true;
+        let active = self.active_node();
+        if self.computation_engine.output_layout(pasted)
+            != self.computation_engine.output_layout(active)
+        {
+            return eprintln!("{}", EditError::TypeMismatch);
+        }
+        match self.replace_references(active, pasted) {
+            Ok(()) => self.computation_engine.mark_dirty(pasted),
+            Err(err) => eprintln!("{}", err),
         }
+    }
+
+    /// Lets the next frame remove to, or with if the replacement fails.
+    pub(super) fn replace_references(&mut self, to: NodeId, with: NodeId) -> Result<(), EditError> {
+        self.may_have_garbage = true;
+        self.computation_engine.replace_references(to, with)?;
+        self.replace_in_selection(to, with);
+        Ok(())
+    }
+
+    /// Selects with instead of to, deselecting the arguments of to.
+    pub(super) fn replace_in_selection(&mut self, to: NodeId, with: NodeId) {
         if let Some(position) = self.selected_node_path.iter().position(|node| *node == to) {
             self.selected_node_path.resize(position, to);
             self.selected_node_path.push(with);
@@ -195,9 +591,9 @@ impl App {
                 .as_literal()
                 .view();
             let name = name.as_string().unwrap();
-            if name.starts_with("SPECIAL TOOL TARGET ") {
+            if name.starts_with(TOOL_TARGET_PREFIX) {
                 return self.instantiate_prototype(prototype.input.unwrap(), root_input);
-            } else if name.starts_with("SPECIAL TOOL WILDCARD INPUT") {
+            } else if name.starts_with(TOOL_WILDCARD_INPUT_NAME) {
                 return (prototype_id, None);
             }
         }
@@ -223,41 +619,122 @@ impl App {
     }
 
     fn on_mouse_up(&mut self, button: MouseButton) {
-        if button == MouseButton::Left {
-            if let Some(BoundingBoxKind::SelectNode(index, node)) = self.dragging {
+        if button == MouseButton::Middle {
+            self.panning = false;
+        } else if button == MouseButton::Left {
+            self.update_hovering();
+            let dropped_on = self.hovering.clone();
+            if let (
+                Some(BoundingBoxKind::SelectNode(_, node)),
+                Some(BoundingBoxKind::Argument(parent, index)),
+            ) = (&self.dragging, dropped_on)
+            {
+                // Dropping a node on an argument makes the argument use it.
+                self.may_have_garbage = true;
+                if let Err(err) = self
+                    .computation_engine
+                    .replace_argument(parent, index, *node)
+                {
+                    eprintln!("{}", err);
+                } else if let Some(position) =
+                    self.selected_node_path.iter().position(|&n| n == parent)
+                {
+                    self.selected_node_path.truncate(position + 1);
+                }
+            } else if let Some(BoundingBoxKind::SelectNode(index, node)) = self.dragging {
+                // Clicking the active node again edits it if it is a literal.
+                if self.active_node() == node {
+                    self.start_literal_edit(node);
+                }
                 assert!(index <= self.selected_node_path.len());
                 self.selected_node_path.resize(index, node);
                 self.selected_node_path.push(node);
                 assert_eq!(self.selected_node_path.last(), Some(&node));
-            } else if let Some(BoundingBoxKind::InvokeTool(..)) = self.dragging {
-                if let Some((old_literal, output)) = self.collapse_to_literal {
-                    let mut io = self.computation_engine.default_io_blob(output);
-                    self.computation_engine.execute(output, &mut io);
-                    let value = io.view().index(&TypedBlob::from(format!("OUTPUT"))).to_owned();
-                    self.computation_engine
-                        .write_constant_data(old_literal, value.clone());
-                    *self.computation_engine[old_literal].as_literal_mut() = value;
-                    self.computation_engine.mark_dirty(output);
-                    self.replace_references(output, old_literal);
-                }
+            } else if let Some(BoundingBoxKind::Argument(parent, index)) = self.dragging {
+                self.start_literal_edit(self.computation_engine[parent].arguments[index]);
+            } else if let Some(BoundingBoxKind::NodeAction(node, action)) = self.dragging {
+                self.perform_node_action(node, action);
+            } else if let Some(BoundingBoxKind::PinnedPreview(index)) = self.dragging {
+                // Clicking a pinned preview unpins it.
+                self.pinned_previews.remove(index);
+                self.may_have_garbage = true;
+            } else if let Some(tool_id) = self.using_tool.take() {
+                self.run_tool_handler(tool_id, ToolEvent::MouseRelease, (0.0, 0.0), (0.0, 0.0));
+                self.finish_tool_use();
             }
             self.dragging = None;
         }
     }
 
+    /// If the tool was used on a literal, folds the nodes it inserted back
+    /// into that literal.
+    fn finish_tool_use(&mut self) {
+        if let Some((old_literal, output)) = self.collapse_to_literal.take() {
+            let mut io = self.computation_engine.default_io_blob(output);
+            self.computation_engine.execute(output, &mut io);
+            let value = io
+                .view()
+                .index(&TypedBlob::from(format!("OUTPUT")))
+                .to_owned();
+            if let Err(err) = self
+                .computation_engine
+                .set_literal_value(old_literal, value)
+            {
+                return eprintln!("{}", err);
+            }
+            self.computation_engine.mark_dirty(output);
+            if let Err(err) = self.replace_references(output, old_literal) {
+                eprintln!("{}", err);
+            }
+        }
+    }
+
+    /// Removes nodes that edits have left behind. Waits until the current
+    /// tool use is finished, since the tool may still restore them.
+    fn collect_garbage(&mut self) {
+        if !self.may_have_garbage || self.using_tool.is_some() {
+            return;
+        }
+        self.may_have_garbage = false;
+        // The widget hovered before the edit can belong to a removed node, so
+        // it is looked up again in the frame that was just drawn.
+        if self.dragging.is_none() {
+            self.update_hovering();
+        }
+        let mut keep = self.builtins.nodes();
+        keep.extend(self.selected_node_path.iter().copied());
+        keep.extend(self.tool_targets.iter().map(|&(_, node)| node));
+        keep.extend(self.collapse_to_literal.iter().flat_map(|&(a, b)| [a, b]));
+        let draft = &self.tool_draft;
+        keep.extend(draft.target_prototype);
+        keep.extend(draft.mouse_drag_handler);
+        keep.extend(draft.mouse_press_handler);
+        keep.extend(draft.mouse_release_handler);
+        keep.extend(draft.scroll_handler);
+        keep.extend(self.literal_edit.as_ref().map(|edit| edit.node));
+        keep.extend(self.pinned_previews.iter().copied());
+        // A drag that started before the edit still holds on to its widget.
+        for kind in self.hovering.iter().chain(self.dragging.iter()) {
+            match kind {
+                &BoundingBoxKind::Argument(node, _)
+                | &BoundingBoxKind::NodeAction(node, _)
+                | &BoundingBoxKind::SelectNode(_, node) => keep.push(node),
+                _ => (),
+            }
+        }
+        self.computation_engine.collect_garbage(keep);
+    }
+
     fn on_mouse_move(&mut self, new_pos: Position) {
         let dx = new_pos.x - self.previous_mouse_pos.x;
         let dy = new_pos.y - self.previous_mouse_pos.y;
         let d = (dx, dy);
         self.previous_mouse_pos = new_pos;
-        if let Some(target) = &self.dragging {
-            match target {
-                &BoundingBoxKind::InvokeTool(tool_id) => {
-                    self.drag_tool(tool_id, d);
-                }
-                _ => (),
-            }
-        } else {
+        if self.panning {
+            self.camera.pan(dx, dy);
+        } else if let Some(tool_id) = self.using_tool {
+            self.run_tool_handler(tool_id, ToolEvent::MouseDrag, d, (0.0, 0.0));
+        } else if self.dragging.is_none() {
             self.update_hovering();
         }
     }
@@ -277,28 +754,83 @@ impl App {
         self.hovering = Some(candidate.kind.clone());
     }
 
-    fn drag_tool(&mut self, tool: ToolId, d: (f32, f32)) {
-        let target_id = self.tool_targets[0].1;
-        let target_value = self.computation_engine[target_id].as_literal().clone();
-        let encoded_delta = TypedBlob::fixed_heterogeneous_map(vec![
-            (format!("X").into(), d.0.into()),
-            (format!("Y").into(), d.1.into()),
-        ]);
-        let tool = self.computation_engine.get_tool(tool);
-        let mut io = TypedBlob::fixed_heterogeneous_map(vec![
-            (format!("OUTPUT").into(), 0.0.into()),
-            (format!("INPUT Mouse Offset").into(), encoded_delta.clone()),
-            (
-                format!("INPUT SPECIAL TOOL TARGET Factor").into(),
-                target_value,
-            ),
+    fn run_tool_handler(
+        &mut self,
+        tool: ToolId,
+        event: ToolEvent,
+        mouse_offset: (f32, f32),
+        scroll_offset: (f32, f32),
+    ) {
+        let Some(handler) = self.computation_engine.get_tool(tool).handler(event) else {
+            return;
+        };
+        if self.tool_targets.is_empty() {
+            return;
+        }
+        let mouse = self.previous_mouse_pos;
+        let drag_offset = (
+            mouse.x - self.drag_start_mouse_pos.x,
+            mouse.y - self.drag_start_mouse_pos.y,
+        );
+        let preview_scale = PREVIEW_TEXTURE_SIZE as f32 / self.theme.preview_widget_size;
+        let cursor_position = (
+            (mouse.x - self.preview_start.x) * preview_scale,
+            (mouse.y - self.preview_start.y) * preview_scale,
+        );
+        let held = |modifier: ModifiersState| {
+            TypedBlob::from(if self.modifiers.contains(modifier) {
+                1.0
+            } else {
+                0.0
+            })
+        };
+        let modifiers = TypedBlob::fixed_heterogeneous_map(vec![
+            (format!("Shift").into(), held(ModifiersState::SHIFT)),
+            (format!("Ctrl").into(), held(ModifiersState::CTRL)),
+            (format!("Alt").into(), held(ModifiersState::ALT)),
         ]);
-        self.computation_engine
-            .execute(tool.mouse_drag_handler, &mut io);
-        let new_data = io.view().index(&format!("OUTPUT").into()).to_owned();
-        self.computation_engine
-            .write_constant_data(target_id, new_data.clone());
-        let target = &mut self.computation_engine[target_id];
-        *target.as_literal_mut() = new_data;
+        let steps = (mouse_offset.0.round() as i32, mouse_offset.1.round() as i32);
+        let builtin = &self.builtins.tool_inputs;
+        let mut inputs = hashmap![
+            builtin.mouse_offset.0 => vector_blob(mouse_offset.0.into(), mouse_offset.1.into()),
+            builtin.mouse_steps.0 => vector_blob(steps.0.into(), steps.1.into()),
+            builtin.drag_offset.0 => vector_blob(drag_offset.0.into(), drag_offset.1.into()),
+            builtin.scroll_offset.0 => vector_blob(scroll_offset.0.into(), scroll_offset.1.into()),
+            builtin.cursor_position.0 =>
+                vector_blob(cursor_position.0.into(), cursor_position.1.into()),
+            builtin.modifiers.0 => modifiers,
+        ];
+        for &(target_param, target_id) in &self.tool_targets {
+            let target_value = self.computation_engine[target_id].as_literal().clone();
+            inputs.insert(target_param, target_value);
+        }
+        let mut io = self
+            .computation_engine
+            .io_blob_with_inputs(handler, &inputs);
+        self.computation_engine.execute(handler, &mut io);
+        let output = io.view().index(&format!("OUTPUT").into()).to_owned();
+        let new_values = match self.computation_engine.tool_output_to_targets(tool, output) {
+            Ok(new_values) => new_values,
+            Err(err) => return eprintln!("{}", err),
+        };
+        for (target_param, new_data) in new_values {
+            let Some(&(_, target_id)) = self
+                .tool_targets
+                .iter()
+                .find(|(param, _)| *param == target_param)
+            else {
+                continue;
+            };
+            if let Err(err) = self
+                .computation_engine
+                .set_literal_value(target_id, new_data)
+            {
+                eprintln!("{}", err);
+            }
+        }
     }
 }
+
+fn vector_blob(x: TypedBlob, y: TypedBlob) -> TypedBlob {
+    TypedBlob::fixed_heterogeneous_map(vec![(format!("X").into(), x), (format!("Y").into(), y)])
+}
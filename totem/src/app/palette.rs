@@ -0,0 +1,284 @@
+use std::collections::BTreeSet;
+
+use itertools::Itertools;
+
+use super::App;
+use crate::engine::{BasicOp, BlobLayout, Node, NodeId, NodeOperation, TypedBlob};
+
+/// How many matching entries the palette shows at once.
+pub const MAX_PALETTE_ENTRIES: usize = 8;
+
+/// The quick-insert palette, which lets the user add nodes by typing part of
+/// their name.
+pub struct Palette {
+    pub query: String,
+    pub selected: usize,
+    /// Whether the new node goes between the active node and its input,
+    /// instead of after the active node.
+    pub above: bool,
+}
+
+impl Palette {
+    pub fn new(above: bool) -> Self {
+        Self {
+            query: String::new(),
+            selected: 0,
+            above,
+        }
+    }
+}
+
+/// A node the palette can add.
+#[derive(Clone, Debug)]
+pub enum PaletteEntry {
+    /// Replaces the node with a literal holding its current value.
+    Literal,
+    Parameter,
+    Basic(BasicOp),
+    GetComponent(String),
+    /// A struct like one already in the graph, with the node before it as
+    /// its first component and default values for the others.
+    ComposeStruct {
+        name: String,
+        components: Vec<String>,
+        layouts: Vec<BlobLayout>,
+    },
+    /// A use of a custom node definition, with the node before it as the
+    /// first parameter of the same type and default values for the others.
+    CustomNode {
+        name: String,
+        definition: NodeId,
+    },
+}
+
+impl PaletteEntry {
+    pub fn name(&self) -> String {
+        match self {
+            Self::Literal => format!("Literal"),
+            Self::Parameter => format!("Parameter"),
+            Self::Basic(op) => op.name().to_owned(),
+            Self::GetComponent(name) => format!("Get {}", name),
+            Self::ComposeStruct { name, .. } => format!("Make {}", name),
+            Self::CustomNode { name, .. } => name.clone(),
+        }
+    }
+}
+
+impl App {
+    /// The node that a node inserted by the palette would use as its input.
+    fn palette_anchor(&self) -> NodeId {
+        match &self.palette {
+            Some(palette) if palette.above => {
+                self.computation_engine[self.active_node()].input.unwrap()
+            }
+            _ => self.active_node(),
+        }
+    }
+
+    /// Every entry that can be inserted after the palette's anchor.
+    fn palette_entries(&self) -> Vec<PaletteEntry> {
+        let engine = &self.computation_engine;
+        let layout = engine.output_layout(self.palette_anchor());
+        let mut entries = vec![PaletteEntry::Literal, PaletteEntry::Parameter];
+        if let BlobLayout::Float | BlobLayout::Integer = &layout {
+            entries.extend(BasicOp::ALL.iter().cloned().map(PaletteEntry::Basic));
+        }
+        for key in layout.string_keys().unwrap_or_default() {
+            entries.push(PaletteEntry::GetComponent(key.to_owned()));
+        }
+        let mut structs = Vec::new();
+        let mut definitions = BTreeSet::new();
+        for (&id, node) in engine.nodes() {
+            match &node.operation {
+                NodeOperation::ComposeStruct(name, components) => {
+                    let layouts = node
+                        .arguments
+                        .iter()
+                        .map(|&argument| engine.output_layout(argument))
+                        .collect_vec();
+                    if layouts.first() == Some(&layout) {
+                        structs.push((name.clone(), components.clone(), layouts));
+                    }
+                    // Composers, whose components are all parameters, are
+                    // also usable as definitions.
+                    let is_composer = node.arguments.iter().all(|&argument| {
+                        matches!(engine[argument].operation, NodeOperation::Parameter(..))
+                    });
+                    if is_composer && !node.arguments.is_empty() {
+                        definitions.insert(id);
+                    }
+                }
+                NodeOperation::CustomNode { result, .. } => {
+                    definitions.insert(*result);
+                }
+                _ => (),
+            }
+        }
+        structs.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+        structs.dedup();
+        entries.extend(structs.into_iter().map(|(name, components, layouts)| {
+            PaletteEntry::ComposeStruct {
+                name,
+                components,
+                layouts,
+            }
+        }));
+        for definition in definitions {
+            if self.custom_node_arguments(definition, &layout).is_some() {
+                let name = match &engine[definition].operation {
+                    NodeOperation::ComposeStruct(name, _) => name.clone(),
+                    operation => format!("Custom {}", operation.name()),
+                };
+                entries.push(PaletteEntry::CustomNode { name, definition });
+            }
+        }
+        entries
+    }
+
+    /// The layouts of the arguments a use of the definition takes, and which
+    /// of them a node with the given layout would be passed as. None if no
+    /// parameter has that layout.
+    fn custom_node_arguments(
+        &self,
+        definition: NodeId,
+        layout: &BlobLayout,
+    ) -> Option<(Vec<BlobLayout>, usize)> {
+        let engine = &self.computation_engine;
+        let layouts = engine[definition]
+            .collect_parameter_nodes(definition, engine.nodes())
+            .into_iter()
+            .sorted()
+            .map(|parameter| engine.output_layout(engine[parameter].input.unwrap()))
+            .collect_vec();
+        let anchor_index = layouts.iter().position(|parameter| parameter == layout)?;
+        Some((layouts, anchor_index))
+    }
+
+    /// Entries whose names contain the query, with ones that start with it
+    /// first.
+    pub(super) fn matching_palette_entries(&self, query: &str) -> Vec<PaletteEntry> {
+        let query = query.to_lowercase();
+        let mut entries: Vec<_> = self
+            .palette_entries()
+            .into_iter()
+            .filter(|entry| entry.name().to_lowercase().contains(&query))
+            .collect();
+        entries.sort_by_key(|entry| !entry.name().to_lowercase().starts_with(&query));
+        entries
+    }
+
+    pub(super) fn insert_selected_palette_entry(&mut self) {
+        let Some(palette) = &self.palette else {
+            return;
+        };
+        let entries = self.matching_palette_entries(&palette.query);
+        let Some(entry) = entries.get(palette.selected).cloned() else {
+            return;
+        };
+        let above = palette.above;
+        let anchor = self.palette_anchor();
+        self.palette = None;
+        // The nodes pushed for the entry are left behind if it cannot be
+        // inserted.
+        self.may_have_garbage = true;
+        let new = match entry {
+            PaletteEntry::Literal => {
+                let mut io = self.computation_engine.default_io_blob(anchor);
+                self.computation_engine.execute(anchor, &mut io);
+                let value = io
+                    .view()
+                    .index(&TypedBlob::from(format!("OUTPUT")))
+                    .to_owned();
+                let literal = self.computation_engine.push_literal_node(value);
+                if let Err(err) = self.replace_references(anchor, literal) {
+                    return eprintln!("{}", err);
+                }
+                self.computation_engine.mark_dirty(literal);
+                return;
+            }
+            PaletteEntry::Parameter => {
+                let name = format!("Parameter");
+                let name = self.computation_engine.push_literal_node(name.into());
+                self.computation_engine.push_parameter(name, anchor).1
+            }
+            PaletteEntry::Basic(op) => {
+                let identity = match (&op, self.computation_engine.output_layout(anchor)) {
+                    (BasicOp::Multiply | BasicOp::Divide | BasicOp::Snap, BlobLayout::Float) => {
+                        1.0.into()
+                    }
+                    (BasicOp::Multiply | BasicOp::Divide | BasicOp::Snap, _) => 1.into(),
+                    (_, layout) => layout.default_blob(),
+                };
+                let argument = self.computation_engine.push_literal_node(identity);
+                self.computation_engine.push_basic(op, anchor, argument)
+            }
+            PaletteEntry::GetComponent(name) => {
+                self.computation_engine.push_get_component(anchor, &name)
+            }
+            PaletteEntry::ComposeStruct {
+                name,
+                components,
+                layouts,
+            } => {
+                let mut arguments = vec![anchor];
+                for layout in &layouts[1..] {
+                    let default = self
+                        .computation_engine
+                        .push_literal_node(layout.default_blob());
+                    arguments.push(default);
+                }
+                let node = Node {
+                    operation: NodeOperation::ComposeStruct(name, components),
+                    input: None,
+                    arguments,
+                };
+                match self.computation_engine.try_push_node(node) {
+                    Ok(new) => new,
+                    Err(err) => return eprintln!("{}", err),
+                }
+            }
+            PaletteEntry::CustomNode { definition, .. } => {
+                let layout = self.computation_engine.output_layout(anchor);
+                let Some((layouts, anchor_index)) = self.custom_node_arguments(definition, &layout)
+                else {
+                    return;
+                };
+                let arguments = layouts
+                    .iter()
+                    .enumerate()
+                    .map(|(index, layout)| {
+                        if index == anchor_index {
+                            anchor
+                        } else {
+                            self.computation_engine
+                                .push_literal_node(layout.default_blob())
+                        }
+                    })
+                    .collect();
+                let node = Node {
+                    operation: NodeOperation::CustomNode {
+                        result: definition,
+                        input: None,
+                    },
+                    input: None,
+                    arguments,
+                };
+                match self.computation_engine.try_push_node(node) {
+                    Ok(new) => new,
+                    Err(err) => return eprintln!("{}", err),
+                }
+            }
+        };
+        let result = if above {
+            self.computation_engine
+                .insert_above(self.active_node(), new)
+        } else {
+            self.computation_engine
+                .insert_below(anchor, new)
+                .map(|()| self.replace_in_selection(anchor, new))
+        };
+        if let Err(err) = result {
+            eprintln!("{}", err);
+        }
+    }
+}
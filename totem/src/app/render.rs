@@ -1,51 +1,90 @@
-use std::{collections::HashMap, time::Instant};
+use std::{collections::HashMap, path::PathBuf, time::Instant};
 
 use renderer::{
-    winit::ControlFlow, HorizontalAlign, IconInstance, ImageInstance, Position, RectInstance,
-    Section, Shapes, Size, SurfaceError, Text, VerticalAlign, BOTTOM_OUTLINE_FLAT,
-    LEFT_OUTLINE_DIAGONAL, LEFT_OUTLINE_FLAT, RIGHT_OUTLINE_ANTIDIAGONAL, RIGHT_OUTLINE_FLAT,
-    TOP_OUTLINE_FLAT,
-};
-use theme::{
-    column_colors, INTER_NODE_PADDING, INTER_PANEL_PADDING, NODE_FILL, NODE_GUTTER_WIDTH,
-    NODE_ICON_PADDING, NODE_ICON_SIZE, NODE_LABEL_HEIGHT, NODE_LABEL_PADDING, NODE_OUTLINE,
-    NODE_PARAMETER_PADDING, NODE_WIDTH, PREVIEW_TEXTURE_SIZE, PREVIEW_WIDGET_SIZE,
-    TOOL_BUTTON_PADDING, TOOL_BUTTON_SIZE, TOOL_ICON_SIZE,
+    winit::ControlFlow, ClipRect, HorizontalAlign, IconInstance, Icons, ImageInstance, Position,
+    RectInstance, Section, Shapes, Size, Stroke, SurfaceError, Text, TextOverflow, VerticalAlign,
+    BOTTOM_OUTLINE_FLAT, LEFT_OUTLINE_DIAGONAL, LEFT_OUTLINE_FLAT, RIGHT_OUTLINE_ANTIDIAGONAL,
+    RIGHT_OUTLINE_FLAT, TOP_OUTLINE_FLAT,
 };
+use theme::{Theme, PREVIEW_TEXTURE_SIZE};
 
-use super::App;
+use super::{
+    literal_edit::{format_literal, literal_sections},
+    palette::MAX_PALETTE_ENTRIES,
+    App, SVG_EXPORT_NAME, THEME_PATH,
+};
 use crate::{
-    engine::{TypedBlob, Node, NodeId, NodeOperation},
-    widgets::{BoundingBox, BoundingBoxKind},
+    engine::{BlobLayout, TypedBlob, Node, NodeId, NodeOperation},
+    widgets::{BoundingBox, BoundingBoxKind, NodeAction},
 };
 
 impl App {
     pub(super) fn render(&mut self) {
         let total_start = Instant::now();
-        let mut bboxes = Vec::new();
-        let mut base_layer = Shapes::new();
-        bboxes.push(self.render_preview_drawer(&mut base_layer));
-        let mut x = bboxes[0].end.x;
-        let mut editor_nodes = vec![(format!("Root"), self.computation_engine.root_node())];
+        self.reload_theme_if_changed();
+        let mut drawer_layer = Shapes::new();
+        let drawer_bbox = self.render_preview_drawer(&mut drawer_layer);
+        self.evaluate_node_rows();
+        let mut x = drawer_bbox.end.x;
+        let mut editor_layer = Shapes::new();
+        let mut editor_bboxes = Vec::new();
+        let root = self.computation_engine.root_node();
+        let mut editor_nodes = vec![(format!("Root"), root, None)];
+        let mut link_starts = HashMap::new();
         let mut index = 0;
         while editor_nodes.len() > 0 {
             let (bbox, next_nodes) = self.render_node_editor(
                 Position {
-                    x: x + INTER_PANEL_PADDING,
+                    x: x + self.theme.inter_panel_padding,
                     y: 0.0,
                 },
-                &mut base_layer,
+                &mut editor_layer,
                 index,
                 editor_nodes,
+                &mut link_starts,
             );
             editor_nodes = next_nodes;
             x = bbox.end.x;
-            bboxes.push(bbox);
+            editor_bboxes.push(bbox);
             index += 1;
         }
-        self.root_bbox = BoundingBox::new_from_children(bboxes);
+        let mut editors_bbox = BoundingBox::new_from_children(editor_bboxes);
+        self.camera.apply_to_shapes(&mut editor_layer);
+        self.camera.apply_to_bbox(&mut editors_bbox);
+        // The editors are clipped to the right of the drawer, so that they
+        // can be panned underneath it.
+        let target_size = self.render_engine.target_size();
+        let drawer_area = BoundingBox::new_start_size(
+            Position::zero(),
+            Size {
+                width: drawer_bbox.end.x + self.theme.inter_panel_padding / 2.0,
+                height: target_size.height,
+            },
+            BoundingBoxKind::Unused,
+        );
+        let mut clipped_editor_layer = Shapes::new();
+        clipped_editor_layer.push_clip(ClipRect {
+            position: [drawer_area.end.x, 0.0],
+            size: [
+                (target_size.width - drawer_area.end.x).max(0.0),
+                target_size.height,
+            ],
+        });
+        clipped_editor_layer.append(editor_layer);
+        clipped_editor_layer.pop_clip();
+        self.root_bbox =
+            BoundingBox::new_from_children(vec![drawer_bbox, drawer_area, editors_bbox]);
 
-        let layers = [&base_layer];
+        let layers = [&clipped_editor_layer, &drawer_layer];
+        if self.save_svg {
+            self.save_svg = false;
+            let svg = self.svg_exporter.export(target_size, &layers);
+            let path = unused_svg_export_path();
+            match std::fs::write(&path, svg) {
+                Ok(()) => eprintln!("Saved {}", path.display()),
+                Err(err) => eprintln!("Could not save {}: {}", path.display(), err),
+            }
+        }
         let start = Instant::now();
         let result = self.render_engine.render(&layers);
         self.perf_counters.gpu_time_acc += start.elapsed();
@@ -62,18 +101,59 @@ impl App {
         self.perf_counters.report_and_reset_if_appropriate();
     }
 
+    /// Switches to the theme in THEME_PATH when the file changes, keeping
+    /// the current theme if there is a problem with it.
+    fn reload_theme_if_changed(&mut self) {
+        match self.theme_file.reload_if_changed() {
+            Some(Ok(theme)) => {
+                self.render_engine.set_background(theme.background);
+                self.svg_exporter.set_background(theme.background);
+                self.theme = theme;
+            }
+            Some(Err(err)) => eprintln!("Could not load {}: {}", THEME_PATH, err),
+            None => (),
+        }
+    }
+
     pub fn active_node(&self) -> NodeId {
         self.selected_node_path.last().copied().unwrap()
     }
 
     fn render_preview_drawer(&mut self, layer: &mut Shapes) -> BoundingBox {
         let mut bboxes = Vec::new();
-        let bbox =
-            self.render_output_preview(Position { x: 0.0, y: 0.0 }, layer, self.active_node());
-        let y = bbox.end.y + INTER_PANEL_PADDING;
+        let bbox = self.render_output_preview(
+            Position { x: 0.0, y: 0.0 },
+            layer,
+            self.active_node(),
+            0,
+            BoundingBoxKind::Preview,
+        );
+        let y = bbox.end.y + self.theme.inter_panel_padding;
+        let mut x = bbox.end.x + self.theme.inter_panel_padding;
         bboxes.push(bbox);
+        // Pinned previews go to the right of the active one, using the images
+        // after the first.
+        for (index, node) in self.pinned_previews.clone().into_iter().enumerate() {
+            let bbox = self.render_output_preview(
+                Position { x, y: 0.0 },
+                layer,
+                node,
+                index + 1,
+                BoundingBoxKind::PinnedPreview(index),
+            );
+            x = bbox.end.x + self.theme.inter_panel_padding;
+            bboxes.push(bbox);
+        }
         let bbox = self.render_toolbox(Position { x: 0.0, y }, layer);
+        let y = bbox.end.y + self.theme.inter_panel_padding;
         bboxes.push(bbox);
+        if let Some(bbox) = self.render_palette(Position { x: 0.0, y }, layer) {
+            bboxes.push(bbox);
+        }
+        let y = bboxes.last().unwrap().end.y + self.theme.inter_panel_padding;
+        if let Some(bbox) = self.render_literal_edit_error(Position { x: 0.0, y }, layer) {
+            bboxes.push(bbox);
+        }
         BoundingBox::new_from_children(bboxes)
     }
 
@@ -82,7 +162,12 @@ impl App {
         position: Position,
         layer: &mut Shapes,
         output_of: NodeId,
+        image_index: usize,
+        kind: BoundingBoxKind,
     ) -> BoundingBox {
+        if let BoundingBoxKind::Preview = kind {
+            self.preview_start = position;
+        }
         let node = &self.computation_engine[output_of];
         let parameters = node.collect_parameters(self.computation_engine.nodes());
         let mut arguments = HashMap::new();
@@ -121,31 +206,80 @@ impl App {
             self.perf_counters.execution_time_acc += start.elapsed();
 
             let start = Instant::now();
-            self.render_engine.upload_image(0, &data);
+            self.render_engine.upload_image(image_index, &data);
+            if self.save_svg {
+                self.svg_exporter.upload_image(image_index, &data);
+            }
             self.perf_counters.upload_time_acc += start.elapsed();
 
-            render_texture_output_preview(position, layer, 0)
+            render_texture_output_preview(&self.theme, position, layer, image_index as i32, kind)
         } else {
-            let mut io = self.computation_engine.default_io_blob(output_of);
             let start = Instant::now();
-            self.computation_engine.execute(output_of, &mut io);
+            let value = self.computation_engine.evaluate(output_of, &HashMap::new());
             self.perf_counters.execution_time_acc += start.elapsed();
-            let value = unsafe { f32::from_ne_bytes(io.as_raw_bytes_mut().as_chunks().0[0]) };
-            render_simple_output_preview(position, layer, &value.into())
+            render_simple_output_preview(&self.theme, position, layer, &value, kind)
         }
     }
 
+    /// Evaluates every node shown in the node editors whose value can be
+    /// shown inline, so that render_node can find it in the cache. Nodes
+    /// that depend on the display position are left out, since they only
+    /// make sense as an image.
+    fn evaluate_node_rows(&mut self) {
+        let engine = &self.computation_engine;
+        let mut heads = vec![engine.root_node()];
+        for &selected in &self.selected_node_path {
+            heads.extend(engine[selected].arguments.iter().copied());
+        }
+        let mut rows = Vec::new();
+        for head in heads {
+            let mut next = Some(head);
+            while let Some(node) = next {
+                rows.push(node);
+                next = engine[node].input;
+            }
+        }
+        for node in rows {
+            if self.shows_inline_value(node) {
+                self.computation_engine.evaluate(node, &HashMap::new());
+            }
+        }
+    }
+
+    fn shows_inline_value(&self, node: NodeId) -> bool {
+        let engine = &self.computation_engine;
+        if let NodeOperation::Literal(..) = engine[node].operation {
+            return false;
+        }
+        let shown = match engine.output_layout(node) {
+            BlobLayout::Float | BlobLayout::Integer => true,
+            BlobLayout::DynamicIndex(element) => *element == BlobLayout::Byte,
+            _ => false,
+        };
+        shown
+            && engine[node]
+                .collect_parameters(engine.nodes())
+                .iter()
+                .all(|param| param.id != self.builtins.display_position.0)
+    }
+
+    /// Draws a column of node chains, each along with the argument of a node
+    /// in the previous column it is linked to, if any. Where the parameter
+    /// rows of arguments are drawn is added to link_starts, so that the
+    /// next column can link to them.
     fn render_node_editor(
         &self,
         start: Position,
         layer: &mut Shapes,
         index: usize,
-        nodes: Vec<(String, NodeId)>,
-    ) -> (BoundingBox, Vec<(String, NodeId)>) {
+        nodes: Vec<(String, NodeId, Option<(NodeId, usize)>)>,
+        link_starts: &mut HashMap<(NodeId, usize), Position>,
+    ) -> (BoundingBox, Vec<(String, NodeId, Option<(NodeId, usize)>)>) {
+        let theme = &self.theme;
         let mut next_column_nodes = Vec::new();
         let mut bboxes = Vec::new();
         let mut y = 0.0;
-        for &(_, node) in &nodes {
+        for &(_, node, _) in &nodes {
             let mut next_node = Some(node);
             while let Some(node_id) = next_node {
                 let node = &self.computation_engine[node_id];
@@ -155,35 +289,53 @@ impl App {
                         next_column_nodes.push((
                             format!("{}", node.operation.param_name(index, &params)),
                             *arg,
+                            Some((node_id, index)),
                         ));
                     }
                 }
                 next_node = node.input;
             }
         }
-        for (name, node) in nodes {
-            let node_bbox = self.render_node(Position { x: start.x, y }, layer, node, index);
+        for (name, node, argument) in nodes {
+            let node_bbox =
+                self.render_node(Position { x: start.x, y }, layer, node, index, link_starts);
             let x = start.x;
-            y = node_bbox.end.y + INTER_NODE_PADDING;
-            let [fill_color, outline_color] = column_colors()[index];
+            y = node_bbox.end.y + theme.inter_node_padding;
+            let [fill_color, outline_color] = theme.column_colors(index);
             layer.push_rect(RectInstance {
                 position: [x, y],
-                size: [NODE_WIDTH, NODE_LABEL_HEIGHT],
+                size: [theme.node_width, theme.node_label_height],
                 fill_color,
                 outline_color,
                 outline_modes: TOP_OUTLINE_FLAT
                     | BOTTOM_OUTLINE_FLAT
                     | LEFT_OUTLINE_FLAT
                     | RIGHT_OUTLINE_FLAT,
+                corner_sizes: [theme.node_corner_size; 4],
+                ..Default::default()
             });
+            if let Some(&link_start) = argument.and_then(|key| link_starts.get(&key)) {
+                let link_end = Position {
+                    x,
+                    y: y + theme.node_label_height / 2.0,
+                };
+                layer.push_stroke(link(theme, link_start, link_end, outline_color));
+            }
             layer.push_text(Text {
-                sections: vec![Section::node_label(name)],
-                center: [x + NODE_LABEL_PADDING, y + NODE_LABEL_HEIGHT / 2.0],
-                bounds: [NODE_WIDTH, NODE_LABEL_HEIGHT],
+                sections: vec![Section::node_label(theme, name)],
+                center: [
+                    x + theme.node_label_padding,
+                    y + theme.node_label_height / 2.0,
+                ],
+                bounds: [
+                    theme.node_width - 2.0 * theme.node_label_padding,
+                    theme.node_label_height,
+                ],
                 horizontal_align: HorizontalAlign::Left,
                 vertical_align: VerticalAlign::Center,
+                overflow: TextOverflow::Ellipsis,
             });
-            y += NODE_LABEL_HEIGHT + INTER_PANEL_PADDING;
+            y += theme.node_label_height + theme.inter_panel_padding;
             bboxes.push(node_bbox);
         }
         next_column_nodes.reverse();
@@ -196,106 +348,159 @@ impl App {
         layer: &mut Shapes,
         node_id: NodeId,
         containing_editor_index: usize,
+        link_starts: &mut HashMap<(NodeId, usize), Position>,
     ) -> BoundingBox {
+        let theme = &self.theme;
         let node = &self.computation_engine[node_id];
         let Position { x, y } = start;
+        let name = match &node.operation {
+            NodeOperation::Literal(value) => self.literal_text(node_id, value),
+            operation => operation.name(),
+        };
         let mut label = Text {
-            sections: vec![Section::node_label(node.operation.name())],
-            center: [x + NODE_LABEL_PADDING, y + NODE_LABEL_HEIGHT / 2.0],
-            bounds: [NODE_WIDTH, NODE_LABEL_HEIGHT],
+            sections: vec![Section::node_label(theme, name)],
+            center: [
+                x + theme.node_label_padding,
+                y + theme.node_label_height / 2.0,
+            ],
+            bounds: [
+                theme.node_width - 2.0 * theme.node_label_padding,
+                theme.node_label_height,
+            ],
             horizontal_align: HorizontalAlign::Left,
             vertical_align: VerticalAlign::Center,
+            overflow: TextOverflow::Ellipsis,
         };
         let mut y = y;
         let mut bboxes = Vec::new();
         if let Some(input) = node.input {
-            let bbox = self.render_node(start, layer, input, containing_editor_index);
+            let bbox = self.render_node(start, layer, input, containing_editor_index, link_starts);
             y = bbox.end.y;
-            y += INTER_NODE_PADDING;
+            y += theme.inter_node_padding;
             bboxes.push(bbox);
         }
-        let [fill_color, outline_color] = column_colors()[containing_editor_index];
+        let [fill_color, outline_color] = theme.column_colors(containing_editor_index);
         let bottom = y;
         if self.selected_node_path.contains(&node_id) {
             let parameters = node.collect_parameters(self.computation_engine.nodes());
-            for (index, _) in node.arguments.iter().enumerate().rev() {
+            for (index, &argument) in node.arguments.iter().enumerate().rev() {
                 let start = Position {
-                    x: x + NODE_GUTTER_WIDTH + NODE_PARAMETER_PADDING,
+                    x: x + theme.node_gutter_width + theme.node_parameter_padding,
                     y,
                 };
                 let label = node.operation.param_name(index, &parameters);
+                let value = match &self.computation_engine[argument].operation {
+                    NodeOperation::Literal(value) => Some(self.literal_text(argument, value)),
+                    _ => None,
+                };
                 let param_bbox = render_parameter(
+                    theme,
                     start,
                     layer,
                     label,
-                    column_colors()[containing_editor_index + 1],
+                    value,
+                    BoundingBoxKind::Argument(node_id, index),
+                    theme.column_colors(containing_editor_index + 1),
                 );
-                y = param_bbox.end.y + NODE_PARAMETER_PADDING;
+                let link_start = Position {
+                    x: param_bbox.end.x,
+                    y: (param_bbox.start.y + param_bbox.end.y) / 2.0,
+                };
+                link_starts.insert((node_id, index), link_start);
+                y = param_bbox.end.y + theme.node_parameter_padding;
                 bboxes.push(param_bbox);
             }
         }
         let height = if self.selected_node_path.last() == Some(&node_id) {
-            NODE_LABEL_HEIGHT + NODE_ICON_SIZE + 2.0 * NODE_ICON_PADDING - NODE_LABEL_PADDING - 2.0
+            theme.node_label_height + theme.node_icon_size + 2.0 * theme.node_icon_padding
+                - theme.node_label_padding
+                - 2.0
         } else {
-            NODE_LABEL_HEIGHT
+            theme.node_label_height
         };
         layer.push_rect(RectInstance {
             position: [x, y],
-            size: [NODE_GUTTER_WIDTH, height],
+            size: [theme.node_gutter_width, height],
             fill_color,
             outline_color,
             outline_modes: LEFT_OUTLINE_FLAT | BOTTOM_OUTLINE_FLAT,
+            ..Default::default()
         });
         layer.push_rect(RectInstance {
-            position: [x + NODE_GUTTER_WIDTH, y],
-            size: [NODE_WIDTH - NODE_GUTTER_WIDTH, height],
+            position: [x + theme.node_gutter_width, y],
+            size: [theme.node_width - theme.node_gutter_width, height],
             fill_color,
             outline_color,
             outline_modes: RIGHT_OUTLINE_FLAT | TOP_OUTLINE_FLAT | BOTTOM_OUTLINE_FLAT,
+            ..Default::default()
         });
-        label.center[1] = y + NODE_LABEL_HEIGHT / 2.0;
+        label.center[1] = y + theme.node_label_height / 2.0;
         let end = Position {
-            x: x + NODE_WIDTH,
+            x: x + theme.node_width,
             y: y + height,
         };
-        let icon_d = NODE_ICON_PADDING + NODE_ICON_SIZE;
+        let icon_d = theme.node_icon_padding + theme.node_icon_size;
         layer.push_rect(RectInstance {
             position: [start.x, end.y],
-            size: [INTER_NODE_PADDING * 2.0, INTER_NODE_PADDING],
+            size: [theme.inter_node_padding * 2.0, theme.inter_node_padding],
             fill_color,
             outline_color,
             outline_modes: LEFT_OUTLINE_DIAGONAL | RIGHT_OUTLINE_ANTIDIAGONAL,
+            ..Default::default()
         });
         if self.selected_node_path.contains(&node_id) {
             layer.push_rect(RectInstance {
                 position: [start.x, bottom],
-                size: [NODE_GUTTER_WIDTH, y - bottom + 1.0],
+                size: [theme.node_gutter_width, y - bottom + 1.0],
                 fill_color,
                 outline_color,
                 outline_modes: LEFT_OUTLINE_FLAT | RIGHT_OUTLINE_FLAT | BOTTOM_OUTLINE_FLAT,
+                ..Default::default()
             });
         }
         if self.selected_node_path.last() == Some(&node_id) {
-            layer.push_icon(IconInstance {
-                position: [start.x + NODE_ICON_PADDING, end.y - icon_d],
-                size: NODE_ICON_SIZE,
-                index: 1,
-            });
-            layer.push_icon(IconInstance {
-                position: [end.x - icon_d, end.y - icon_d],
-                size: NODE_ICON_SIZE,
-                index: 1,
-            });
-            layer.push_icon(IconInstance {
-                position: [end.x - 2.0 * icon_d, end.y - icon_d],
-                size: NODE_ICON_SIZE,
-                index: 1,
-            });
-            layer.push_icon(IconInstance {
-                position: [end.x - 3.0 * icon_d, end.y - icon_d],
-                size: NODE_ICON_SIZE,
-                index: 1,
-            });
+            // Spread the buttons evenly across the node.
+            let spacing =
+                (theme.node_width - theme.node_icon_padding) / NodeAction::ALL.len() as f32;
+            for (index, action) in NodeAction::ALL.into_iter().enumerate() {
+                let position = Position {
+                    x: start.x + theme.node_icon_padding + index as f32 * spacing,
+                    y: end.y - icon_d,
+                };
+                layer.push_icon(IconInstance {
+                    position: [position.x, position.y],
+                    size: theme.node_icon_size,
+                    index: Icons::get(action.icon()),
+                });
+                bboxes.push(BoundingBox::new_start_size(
+                    position,
+                    Size {
+                        width: theme.node_icon_size,
+                        height: theme.node_icon_size,
+                    },
+                    BoundingBoxKind::NodeAction(node_id, action),
+                ));
+            }
+        }
+        if let Some(value) = self
+            .computation_engine
+            .cached_result(node_id, &HashMap::new())
+            .filter(|_| self.shows_inline_value(node_id))
+        {
+            let value = Text {
+                sections: literal_sections(theme, value),
+                center: [
+                    x + theme.node_width - theme.node_label_padding,
+                    label.center[1],
+                ],
+                bounds: [theme.node_width, theme.node_label_height],
+                horizontal_align: HorizontalAlign::Right,
+                vertical_align: VerticalAlign::Center,
+                overflow: TextOverflow::Clip,
+            };
+            // The name gets whatever room the value leaves.
+            label.bounds[0] -= value.measure()[0] + theme.node_label_padding;
+            layer.push_text(value);
         }
         let kind = self.default_node_bbox_kind(node_id, &node.operation, containing_editor_index);
         bboxes.push(BoundingBox::new_start_end(Position { x, y }, end, kind));
@@ -303,6 +508,15 @@ impl App {
         BoundingBox::new_from_children(bboxes)
     }
 
+    /// The value of a literal, or the text replacing it if it is being
+    /// edited.
+    fn literal_text(&self, node: NodeId, value: &TypedBlob) -> String {
+        match &self.literal_edit {
+            Some(edit) if edit.node == node => format!("{}|", edit.text),
+            _ => format_literal(value),
+        }
+    }
+
     fn default_node_bbox_kind(
         &self,
         id: NodeId,
@@ -315,43 +529,260 @@ impl App {
     }
 
     fn render_toolbox(&self, start: Position, layer: &mut Shapes) -> BoundingBox {
+        let theme = &self.theme;
+        let mut bboxes = Vec::new();
+        let mut position = start;
+        for (tool_id, tool) in self.computation_engine.tools() {
+            if position.x + theme.tool_button_size > start.x + theme.preview_widget_size {
+                position = Position {
+                    x: start.x,
+                    y: position.y + theme.tool_button_size + theme.inter_node_padding,
+                };
+            }
+            layer.push_rect(RectInstance {
+                position: [position.x, position.y],
+                size: [theme.tool_button_size, theme.tool_button_size],
+                fill_color: theme.column_colors(0)[0],
+                outline_color: theme.column_colors(0)[1],
+                outline_modes: TOP_OUTLINE_FLAT
+                    | BOTTOM_OUTLINE_FLAT
+                    | LEFT_OUTLINE_FLAT
+                    | RIGHT_OUTLINE_FLAT,
+                corner_sizes: [theme.node_corner_size; 4],
+                ..Default::default()
+            });
+            layer.push_icon(IconInstance {
+                position: [
+                    position.x + theme.tool_button_padding(),
+                    position.y + theme.tool_button_padding(),
+                ],
+                size: theme.tool_icon_size,
+                index: icon_index(&tool.icon),
+            });
+            bboxes.push(BoundingBox::new_start_size(
+                position,
+                Size {
+                    width: theme.tool_button_size,
+                    height: theme.tool_button_size,
+                },
+                BoundingBoxKind::InvokeTool(tool_id),
+            ));
+            position.x += theme.tool_button_size + theme.inter_node_padding;
+        }
+        let draft = &self.tool_draft;
+        let status = if let Some(BoundingBoxKind::InvokeTool(tool_id)) = &self.hovering {
+            let tool = self.computation_engine.get_tool(*tool_id);
+            Some((tool.name.clone(), icon_index(&tool.icon)))
+        } else if let Some(BoundingBoxKind::NodeAction(_, action)) = &self.hovering {
+            Some((action.name().to_owned(), Icons::get(action.icon())))
+        } else if let Some(BoundingBoxKind::PinnedPreview(_)) = &self.hovering {
+            Some((format!("Unpin Preview"), Icons::get("pin-off")))
+        } else if draft.target_prototype.is_some() || draft.mouse_drag_handler.is_some() {
+            let status = |picked: bool| if picked { "set" } else { "missing" };
+            let label = format!(
+                "New tool: prototype {}, handler {}",
+                status(draft.target_prototype.is_some()),
+                status(draft.mouse_drag_handler.is_some()),
+            );
+            Some((label, icon_index(&draft.icon)))
+        } else {
+            None
+        };
+        if let Some((label, icon)) = status {
+            let position = Position {
+                x: start.x,
+                y: position.y + theme.tool_button_size + theme.inter_node_padding,
+            };
+            layer.push_icon(IconInstance {
+                position: [position.x, position.y + theme.tool_button_padding()],
+                size: theme.tool_icon_size,
+                index: icon,
+            });
+            let text = Text {
+                sections: vec![Section::node_label(theme, label)],
+                center: [
+                    position.x + theme.tool_icon_size + theme.node_label_padding,
+                    position.y + theme.tool_button_size / 2.0,
+                ],
+                bounds: [
+                    theme.preview_widget_size - theme.tool_icon_size,
+                    theme.tool_button_size,
+                ],
+                horizontal_align: HorizontalAlign::Left,
+                vertical_align: VerticalAlign::Center,
+                overflow: TextOverflow::Ellipsis,
+            };
+            let width = theme.tool_icon_size + theme.node_label_padding + text.measure()[0];
+            layer.push_text(text);
+            bboxes.push(BoundingBox::new_start_size(
+                position,
+                Size {
+                    width,
+                    height: theme.tool_button_size,
+                },
+                BoundingBoxKind::Unused,
+            ));
+        }
+        BoundingBox::new_from_children(bboxes)
+    }
+
+    fn render_palette(&self, start: Position, layer: &mut Shapes) -> Option<BoundingBox> {
+        let theme = &self.theme;
+        let palette = self.palette.as_ref()?;
+        let entries = self.matching_palette_entries(&palette.query);
+        let first = palette.selected.saturating_sub(MAX_PALETTE_ENTRIES - 1);
+        let lines = std::iter::once((format!("Insert: {}", palette.query), 0))
+            .chain(
+                entries
+                    .iter()
+                    .enumerate()
+                    .skip(first)
+                    .take(MAX_PALETTE_ENTRIES)
+                    .map(|(index, entry)| {
+                        let column = if index == palette.selected { 1 } else { 0 };
+                        (entry.name(), column)
+                    }),
+            );
         let mut bboxes = Vec::new();
+        let mut y = start.y;
+        for (label, column) in lines {
+            let [fill_color, outline_color] = theme.column_colors(column);
+            layer.push_rect(RectInstance {
+                position: [start.x, y],
+                size: [theme.preview_widget_size, theme.node_label_height],
+                fill_color,
+                outline_color,
+                outline_modes: TOP_OUTLINE_FLAT
+                    | BOTTOM_OUTLINE_FLAT
+                    | LEFT_OUTLINE_FLAT
+                    | RIGHT_OUTLINE_FLAT,
+                corner_sizes: [theme.node_corner_size; 4],
+                ..Default::default()
+            });
+            layer.push_text(Text {
+                sections: vec![Section::node_label(theme, label)],
+                center: [
+                    start.x + theme.node_label_padding,
+                    y + theme.node_label_height / 2.0,
+                ],
+                bounds: [
+                    theme.preview_widget_size - 2.0 * theme.node_label_padding,
+                    theme.node_label_height,
+                ],
+                horizontal_align: HorizontalAlign::Left,
+                vertical_align: VerticalAlign::Center,
+                overflow: TextOverflow::Ellipsis,
+            });
+            let size = Size {
+                width: theme.preview_widget_size,
+                height: theme.node_label_height,
+            };
+            bboxes.push(BoundingBox::new_start_size(
+                Position { x: start.x, y },
+                size,
+                BoundingBoxKind::Unused,
+            ));
+            y += theme.node_label_height + theme.inter_node_padding;
+        }
+        Some(BoundingBox::new_from_children(bboxes))
+    }
+
+    /// Why the text typed into a literal could not be used, wrapped to the
+    /// width of the drawer.
+    fn render_literal_edit_error(
+        &self,
+        start: Position,
+        layer: &mut Shapes,
+    ) -> Option<BoundingBox> {
+        let theme = &self.theme;
+        let error = self.literal_edit.as_ref()?.error.as_ref()?;
+        let mut text = Text {
+            sections: vec![Section::node_label(theme, error.clone())],
+            center: [start.x + theme.node_label_padding, 0.0],
+            bounds: [
+                theme.preview_widget_size - 2.0 * theme.node_label_padding,
+                f32::INFINITY,
+            ],
+            horizontal_align: HorizontalAlign::Left,
+            vertical_align: VerticalAlign::Center,
+            overflow: TextOverflow::Wrap,
+        };
+        let height =
+            (text.measure()[1] + 2.0 * theme.node_label_padding).max(theme.node_label_height);
+        text.center[1] = start.y + height / 2.0;
+        let [fill_color, outline_color] = theme.column_colors(0);
         layer.push_rect(RectInstance {
             position: [start.x, start.y],
-            size: [TOOL_BUTTON_SIZE, TOOL_BUTTON_SIZE],
-            fill_color: column_colors()[0][0],
-            outline_color: column_colors()[0][1],
+            size: [theme.preview_widget_size, height],
+            fill_color,
+            outline_color,
             outline_modes: TOP_OUTLINE_FLAT
                 | BOTTOM_OUTLINE_FLAT
                 | LEFT_OUTLINE_FLAT
                 | RIGHT_OUTLINE_FLAT,
+            corner_sizes: [theme.node_corner_size; 4],
+            ..Default::default()
         });
-        layer.push_icon(IconInstance {
-            position: [start.x + TOOL_BUTTON_PADDING, start.y + TOOL_BUTTON_PADDING],
-            size: TOOL_ICON_SIZE,
-            index: 1,
-        });
-        bboxes.push(BoundingBox::new_start_end(
+        layer.push_text(text);
+        Some(BoundingBox::new_start_size(
             start,
-            Position {
-                x: start.x + TOOL_BUTTON_SIZE,
-                y: start.y + TOOL_BUTTON_SIZE,
+            Size {
+                width: theme.preview_widget_size,
+                height,
             },
-            BoundingBoxKind::InvokeTool(self.builtins.adjust_float_tool),
-        ));
-        BoundingBox::new_from_children(bboxes)
+            BoundingBoxKind::Unused,
+        ))
     }
 }
 
+/// The first of totem.svg, totem-2.svg, totem-3.svg and so on that does not
+/// exist yet in the working directory.
+fn unused_svg_export_path() -> PathBuf {
+    let directory = std::env::current_dir().unwrap_or_default();
+    (1..)
+        .map(|number| match number {
+            1 => directory.join(format!("{}.svg", SVG_EXPORT_NAME)),
+            _ => directory.join(format!("{}-{}.svg", SVG_EXPORT_NAME, number)),
+        })
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+/// Tools name their icons so that they can be saved, but files from
+/// elsewhere may name icons this build does not have.
+fn icon_index(name: &str) -> i32 {
+    Icons::find(name).unwrap_or_else(|| Icons::get("eye"))
+}
+
+/// A curve from the parameter row of an argument to the chain of nodes it
+/// refers to, leaving and arriving horizontally.
+fn link(theme: &Theme, start: Position, end: Position, color: [f32; 3]) -> Stroke {
+    let bend = (end.x - start.x) / 2.0;
+    Stroke::cubic_bezier(
+        [
+            [start.x, start.y],
+            [start.x + bend, start.y],
+            [end.x - bend, end.y],
+            [end.x, end.y],
+        ],
+        theme.link_width,
+        color,
+    )
+}
+
+/// Draws the label of one of a node's arguments, along with its value if
+/// it is a literal.
 fn render_parameter(
+    theme: &Theme,
     start: Position,
     layer: &mut Shapes,
     name: &str,
+    value: Option<String>,
+    kind: BoundingBoxKind,
     [fill_color, outline_color]: [[f32; 3]; 2],
 ) -> BoundingBox {
-    let width = NODE_WIDTH - NODE_PARAMETER_PADDING - NODE_GUTTER_WIDTH;
-    let height = NODE_LABEL_HEIGHT;
-    let kind = BoundingBoxKind::Unused;
+    let width = theme.node_width - theme.node_parameter_padding - theme.node_gutter_width;
+    let height = theme.node_label_height;
     layer.push_rect(RectInstance {
         position: [start.x, start.y],
         size: [width, height],
@@ -361,52 +792,82 @@ fn render_parameter(
             | RIGHT_OUTLINE_FLAT
             | TOP_OUTLINE_FLAT
             | BOTTOM_OUTLINE_FLAT,
+        corner_sizes: [theme.node_corner_size; 4],
+        ..Default::default()
     });
     layer.push_text(Text {
-        sections: vec![Section::node_label(name.to_owned())],
+        sections: vec![Section::node_label(theme, name.to_owned())],
         center: [
-            start.x + NODE_WIDTH - NODE_LABEL_PADDING - NODE_GUTTER_WIDTH - NODE_PARAMETER_PADDING,
+            start.x + theme.node_width
+                - theme.node_label_padding
+                - theme.node_gutter_width
+                - theme.node_parameter_padding,
             start.y + height / 2.0,
         ],
-        bounds: [width - 2.0 * NODE_LABEL_PADDING, height],
+        bounds: [width - 2.0 * theme.node_label_padding, height],
         horizontal_align: HorizontalAlign::Right,
         vertical_align: VerticalAlign::Center,
+        overflow: TextOverflow::Ellipsis,
     });
+    if let Some(value) = value {
+        layer.push_text(Text {
+            sections: vec![Section::node_label(theme, value)],
+            center: [start.x + theme.node_label_padding, start.y + height / 2.0],
+            bounds: [width - 2.0 * theme.node_label_padding, height],
+            horizontal_align: HorizontalAlign::Left,
+            vertical_align: VerticalAlign::Center,
+            overflow: TextOverflow::Clip,
+        });
+    }
     BoundingBox::new_start_size(start, Size { width, height }, kind)
 }
 
-fn render_simple_output_preview(start: Position, layer: &mut Shapes, value: &TypedBlob) -> BoundingBox {
-    let size = PREVIEW_WIDGET_SIZE;
+fn render_simple_output_preview(
+    theme: &Theme,
+    start: Position,
+    layer: &mut Shapes,
+    value: &TypedBlob,
+    kind: BoundingBoxKind,
+) -> BoundingBox {
+    let size = theme.preview_widget_size;
     layer.push_rect(RectInstance {
         position: [start.x, start.y],
         size: [size, size],
-        fill_color: NODE_FILL,
-        outline_color: NODE_OUTLINE,
+        fill_color: theme.node_fill,
+        outline_color: theme.node_outline,
         outline_modes: TOP_OUTLINE_FLAT
             | BOTTOM_OUTLINE_FLAT
             | LEFT_OUTLINE_FLAT
             | RIGHT_OUTLINE_FLAT,
+        corner_sizes: [theme.node_corner_size; 4],
+        shadow_offset: theme.panel_shadow_offset,
+        shadow_size: theme.panel_shadow_size,
+        shadow_opacity: theme.panel_shadow_opacity,
+        ..Default::default()
     });
     layer.push_text(Text {
-        sections: vec![Section::big_value_text(format!("{:?}", value))],
+        sections: vec![Section::big_value_text(theme, format!("{:?}", value))],
         center: [start.x + size / 2.0, start.y + size / 2.0],
         bounds: [size, size],
         horizontal_align: HorizontalAlign::Center,
         vertical_align: VerticalAlign::Center,
+        overflow: TextOverflow::Wrap,
     });
     let size = Size {
         width: size,
         height: size,
     };
-    BoundingBox::new_start_size(start, size, BoundingBoxKind::Unused)
+    BoundingBox::new_start_size(start, size, kind)
 }
 
 fn render_texture_output_preview(
+    theme: &Theme,
     start: Position,
     layer: &mut Shapes,
     image_index: i32,
+    kind: BoundingBoxKind,
 ) -> BoundingBox {
-    let size = PREVIEW_WIDGET_SIZE;
+    let size = theme.preview_widget_size;
     layer.push_image(ImageInstance {
         position: [start.x, start.y],
         size,
@@ -416,5 +877,5 @@ fn render_texture_output_preview(
         width: size,
         height: size,
     };
-    BoundingBox::new_start_size(start, size, BoundingBoxKind::Unused)
+    BoundingBox::new_start_size(start, size, kind)
 }
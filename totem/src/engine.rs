@@ -1,5 +1,14 @@
 mod blob;
+mod cache;
+mod clipboard;
+mod edit;
+mod garbage;
+mod json;
 mod layout;
+mod prototype;
+mod script;
+mod tool;
+mod validate;
 
 use std::{
     collections::{HashMap, HashSet},
@@ -19,7 +28,10 @@ use itertools::Itertools;
 pub use layout::*;
 use maplit::{hashmap, hashset};
 use target_lexicon::Triple;
+pub use edit::EditError;
+pub use tool::*;
 
+use self::{cache::ResultCache, script::DEMO_SCRIPT, validate::ValidationError};
 use crate::util::{self, Id, IdCreator};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -342,6 +354,22 @@ impl CodeGenerationContext {
             .store(MemFlags::new(), value, output_ptr, 0);
     }
 
+    fn copy_global_data(c: NodeDefinitionContext, len: u32, id: DataId, output_ptr: Value) {
+        let local_id = c.module.declare_data_in_func(id, c.func_builder.func);
+        let ptr_type = c.module.target_config().pointer_type();
+        let ptr = c.func_builder.ins().symbol_value(ptr_type, local_id);
+        c.func_builder.emit_small_memory_copy(
+            c.module.target_config(),
+            output_ptr,
+            ptr,
+            len as u64,
+            1,
+            1,
+            true,
+            MemFlags::new(),
+        );
+    }
+
     fn node_output_layout(nodes: &HashMap<NodeId, Node>, node: NodeId) -> BlobLayout {
         let node = &nodes[&node];
         match &node.operation {
@@ -403,6 +431,9 @@ impl CodeGenerationContext {
                         Self::load_global_data(c, types::I32, data, output_ptr)
                     }
                     BlobLayout::Float => Self::load_global_data(c, types::F32, data, output_ptr),
+                    layout if layout.is_fixed() => {
+                        Self::copy_global_data(c, layout.size(), data, output_ptr)
+                    }
                     _ => (),
                 }
             }
@@ -423,6 +454,10 @@ impl CodeGenerationContext {
             NodeOperation::Basic(op) => {
                 let input = node.input.unwrap();
                 let argument = node.arguments[0];
+                let ty = match Self::node_output_layout(c.nodes, input) {
+                    BlobLayout::Integer => types::I32,
+                    _ => types::F32,
+                };
                 drop(node);
                 Self::compile_node_to_instructions(c.reborrow(input), output_ptr);
                 let argument_ss = c
@@ -437,16 +472,42 @@ impl CodeGenerationContext {
                 let input = c
                     .func_builder
                     .ins()
-                    .load(types::F32, MemFlags::new(), output_ptr, 0);
-                let argument =
-                    c.func_builder
-                        .ins()
-                        .load(types::F32, MemFlags::new(), argument_ptr, 0);
-                let result = match op {
-                    BasicOp::Add => c.func_builder.ins().fadd(input, argument),
-                    BasicOp::Subtract => c.func_builder.ins().fsub(input, argument),
-                    BasicOp::Multiply => c.func_builder.ins().fmul(input, argument),
-                    BasicOp::Divide => c.func_builder.ins().fdiv(input, argument),
+                    .load(ty, MemFlags::new(), output_ptr, 0);
+                let argument = c
+                    .func_builder
+                    .ins()
+                    .load(ty, MemFlags::new(), argument_ptr, 0);
+                let result = if ty == types::I32 {
+                    match op {
+                        BasicOp::Add => c.func_builder.ins().iadd(input, argument),
+                        BasicOp::Subtract => c.func_builder.ins().isub(input, argument),
+                        BasicOp::Multiply => c.func_builder.ins().imul(input, argument),
+                        BasicOp::Divide => {
+                            Self::emit_checked_sdiv(&mut c.func_builder, input, argument)
+                        }
+                        BasicOp::Minimum => c.func_builder.ins().smin(input, argument),
+                        BasicOp::Maximum => c.func_builder.ins().smax(input, argument),
+                        BasicOp::Snap => {
+                            let steps =
+                                Self::emit_checked_sdiv(&mut c.func_builder, input, argument);
+                            c.func_builder.ins().imul(steps, argument)
+                        }
+                    }
+                } else {
+                    let ins = c.func_builder.ins();
+                    match op {
+                        BasicOp::Add => ins.fadd(input, argument),
+                        BasicOp::Subtract => ins.fsub(input, argument),
+                        BasicOp::Multiply => ins.fmul(input, argument),
+                        BasicOp::Divide => ins.fdiv(input, argument),
+                        BasicOp::Minimum => ins.fmin(input, argument),
+                        BasicOp::Maximum => ins.fmax(input, argument),
+                        BasicOp::Snap => {
+                            let steps = ins.fdiv(input, argument);
+                            let steps = c.func_builder.ins().nearest(steps);
+                            c.func_builder.ins().fmul(steps, argument)
+                        }
+                    }
                 };
                 c.func_builder
                     .ins()
@@ -531,6 +592,21 @@ impl CodeGenerationContext {
         }
     }
 
+    /// Divides two integers without trapping. Dividing by zero gives zero,
+    /// and dividing i32::MIN by -1 wraps around instead of overflowing.
+    fn emit_checked_sdiv(builder: &mut FunctionBuilder, dividend: Value, divisor: Value) -> Value {
+        let is_zero = builder.ins().icmp_imm(IntCC::Equal, divisor, 0);
+        let is_minus_one = builder.ins().icmp_imm(IntCC::Equal, divisor, -1);
+        let needs_care = builder.ins().bor(is_zero, is_minus_one);
+        let one = builder.ins().iconst(types::I32, 1);
+        let safe_divisor = builder.ins().select(needs_care, one, divisor);
+        let quotient = builder.ins().sdiv(dividend, safe_divisor);
+        let negated = builder.ins().ineg(quotient);
+        let quotient = builder.ins().select(is_minus_one, negated, quotient);
+        let zero = builder.ins().iconst(types::I32, 0);
+        builder.ins().select(is_zero, zero, quotient)
+    }
+
     fn compile_node_wrapper(c: NodeDefinitionContext, output_ptr: Value) {
         let fun = Self::get_function_declaration_impl(
             c.functions,
@@ -562,12 +638,6 @@ pub struct ParameterDescription {
     pub default: NodeId,
 }
 
-pub struct Tool {
-    pub target_prototype: NodeId,
-    pub mouse_drag_handler: NodeId,
-}
-
-pub type ToolId = Id<Tool>;
 pub type NodeId = Id<Node>;
 pub type ParameterId = Id<Parameter>;
 
@@ -579,6 +649,7 @@ pub struct Engine {
     parameter_ids: IdCreator<Parameter>,
     tool_ids: IdCreator<Tool>,
     context: CodeGenerationContext,
+    results: ResultCache,
 }
 
 pub struct BuiltinDefinitions {
@@ -586,9 +657,9 @@ pub struct BuiltinDefinitions {
     pub y_component: ParameterId,
     pub compose_vector_2d: NodeId,
     pub compose_integer_vector_2d: NodeId,
-    pub mouse_offset: (ParameterId, NodeId),
     pub display_position: (ParameterId, NodeId),
-    pub adjust_float_tool: ToolId,
+    pub tool_inputs: BuiltinToolInputs,
+    pub tools: Vec<ToolId>,
 }
 
 impl Index<NodeId> for Engine {
@@ -625,9 +696,15 @@ impl Engine {
             parameter_ids,
             tool_ids,
             context,
+            results: ResultCache::new(),
         };
         let builtins = this.make_builtins();
         this.setup_demo(&builtins);
+        if cfg!(debug_assertions) {
+            if let Err(err) = this.validate() {
+                panic!("The builtin definitions are invalid: {}", err);
+            }
+        }
         (this, builtins)
     }
 
@@ -641,73 +718,18 @@ impl Engine {
             "Compose Vector/2D",
             vec![("X", 0.0.into()), ("Y", 0.0.into())],
         );
-        let zero = self.push_literal_node(0.0.into());
-        let default_vec2 = self.push_node(Node {
-            operation: NodeOperation::CustomNode {
-                result: compose_vector_2d,
-                input: None,
-            },
-            input: None,
-            arguments: vec![zero, zero],
-        });
-        let name = self.push_literal_node("Mouse Offset".to_owned().into());
-        let mouse_offset = self.push_parameter(name, default_vec2);
-
-        let zero = self.push_literal_node(0.into());
-        let default_ivec2 = self.push_node(Node {
-            operation: NodeOperation::CustomNode {
-                result: compose_integer_vector_2d,
-                input: None,
-            },
-            input: None,
-            arguments: vec![zero, zero],
-        });
-        let name = self.push_literal_node("Display Position".to_owned().into());
-        let display_position = self.push_parameter(name, default_ivec2);
-
-        let (prototype, target) = {
-            let target = self.push_simple_parameter("SPECIAL TOOL TARGET Factor", 1.0.into());
-            let input = self.push_simple_parameter("SPECIAL TOOL WILDCARD INPUT", 0.0.into());
-            let prototype = self.push_node(Node {
-                operation: NodeOperation::Basic(BasicOp::Multiply),
-                input: Some(input),
-                arguments: vec![target],
-            });
-            (prototype, target)
-        };
-        let drag_handler = {
-            let mouse_offset = mouse_offset.1;
-            let dx = self.push_get_component(mouse_offset, "X");
-            let dy = self.push_get_component(mouse_offset, "Y");
-            let dx_plus_dy = self.push_node(Node {
-                operation: NodeOperation::Basic(BasicOp::Add),
-                input: Some(dx),
-                arguments: vec![dy],
-            });
-            let scale = self.push_literal_node(0.01.into());
-            let delta = self.push_node(Node {
-                operation: NodeOperation::Basic(BasicOp::Multiply),
-                input: Some(dx_plus_dy),
-                arguments: vec![scale],
-            });
-            self.push_node(Node {
-                operation: NodeOperation::Basic(BasicOp::Add),
-                input: Some(target),
-                arguments: vec![delta],
-            })
-        };
-        let adjust_float_tool = self.add_tool(Tool {
-            target_prototype: prototype,
-            mouse_drag_handler: drag_handler,
-        });
+        // The preview writes pixel coordinates as floats.
+        let display_position = self.push_vector_parameter("Display Position", compose_vector_2d);
+        let tool_inputs = self.make_builtin_tool_inputs(compose_vector_2d, compose_integer_vector_2d);
+        let tools = self.make_builtin_tools(&tool_inputs);
         BuiltinDefinitions {
             x_component: vector_2d_parameters[0],
             y_component: vector_2d_parameters[1],
             compose_vector_2d,
             compose_integer_vector_2d,
-            mouse_offset,
             display_position,
-            adjust_float_tool,
+            tool_inputs,
+            tools,
         }
     }
 
@@ -716,14 +738,54 @@ impl Engine {
             .define_function_implementation(&self.nodes, FunctionKind::ExternalWrapper(node));
     }
 
-    pub fn write_constant_data(&mut self, node: NodeId, data: TypedBlob) {
-        self.context.write_constant_data(node, data);
+    /// Changes the value of a literal node, updating its compiled data if it
+    /// has any. Fails without changing anything if the new value has a
+    /// different layout, since compiled data cannot change size. The literal
+    /// should then be replaced by a new node, as replace_literal does.
+    pub fn set_literal_value(&mut self, node: NodeId, value: TypedBlob) -> Result<(), EditError> {
+        let NodeOperation::Literal(old_value) = &self[node].operation else {
+            return Err(EditError::NotALiteral(node));
+        };
+        if value.layout() != old_value.layout() {
+            return Err(EditError::TypeMismatch);
+        }
+        if self.context.constants.contains_key(&node) {
+            self.context.write_constant_data(node, value.clone());
+        }
+        *self[node].as_literal_mut() = value;
+        self.forget_results(node);
+        Ok(())
     }
 
     pub fn default_io_blob(&self, node: NodeId) -> TypedBlob {
         CodeGenerationContext::io_layout(&self.nodes, node).default_blob()
     }
 
+    /// Like default_io_blob, but inputs for the given parameters are filled in
+    /// with the provided values.
+    pub fn io_blob_with_inputs(
+        &self,
+        node: NodeId,
+        inputs: &HashMap<ParameterId, TypedBlob>,
+    ) -> TypedBlob {
+        let output = self.output_layout(node).default_blob();
+        let mut components = vec![(TypedBlob::from(format!("OUTPUT")), output)];
+        let params = self.nodes[&node].collect_parameter_nodes(node, &self.nodes);
+        for param in params.into_iter().sorted() {
+            let param = &self.nodes[&param].collect_parameters(&self.nodes)[0];
+            let value = match inputs.get(&param.id) {
+                Some(value) => value.clone(),
+                None => self.output_layout(param.default).default_blob(),
+            };
+            components.push((TypedBlob::from(format!("INPUT {}", param.name)), value));
+        }
+        TypedBlob::fixed_heterogeneous_map(components)
+    }
+
+    pub fn output_layout(&self, node: NodeId) -> BlobLayout {
+        CodeGenerationContext::node_output_layout(&self.nodes, node)
+    }
+
     pub fn execute(&mut self, node: NodeId, io: &mut TypedBlob) {
         self.compile(node);
         self.context
@@ -749,54 +811,24 @@ impl Engine {
         )
     }
 
-    fn add_tool(&mut self, tool: Tool) -> ToolId {
-        let id = self.tool_ids.next();
-        self.tools.insert(id, tool);
-        id
-    }
-
     pub fn get_tool(&self, tool: ToolId) -> &Tool {
         &self.tools[&tool]
     }
 
-    fn setup_demo(&mut self, builtins: &BuiltinDefinitions) {
-        // let value = self.root_node();
-        // let param1 = self.push_literal_node(2.0.into());
-        // let value = self.push_node(Node {
-        //     operation: NodeOperation::Basic(BasicOp::Multiply),
-        //     input: Some(value),
-        //     arguments: vec![param1],
-        // });
-        // // let param2 = self.push_simple_parameter("Value", 123.0.into());
-        // let param2 = self.push_literal_node(123.0.into());
-        // let root = self.push_node(Node {
-        //     operation: NodeOperation::Basic(BasicOp::Add),
-        //     input: Some(value),
-        //     arguments: vec![param2],
-        // });
-
-        // let vec = self.push_simple_struct("Vector/2D", vec![("X", 1.0.into()), ("Y",
-        // 2.0.into())]);
-        // let one = self.push_literal_node(1.0.into());
-        // let two = self.push_literal_node(2.0.into());
-        // let vec = self.push_node(Node {
-        //     operation: NodeOperation::CustomNode {
-        //         result: builtins.compose_vector_2d,
-        //         input: None,
-        //     },
-        //     input: None,
-        //     arguments: vec![one, two],
-        // });
-        // let value = self.push_get_component(vec, "X");
-        let value = self.push_get_component(builtins.display_position.1, "Y");
-        let divisor = self.push_literal_node(180.0.into());
-        let root = self.push_node(Node {
-            operation: NodeOperation::Basic(BasicOp::Divide),
-            input: Some(value),
-            arguments: vec![divisor],
-        });
+    /// All tools, in the order they were added.
+    pub fn tools(&self) -> impl Iterator<Item = (ToolId, &Tool)> {
+        self.tools
+            .iter()
+            .map(|(id, tool)| (*id, tool))
+            .sorted_by_key(|(id, _)| *id)
+    }
 
-        self.set_root(root);
+    /// Builds the graph the editor starts with. If that fails, the editor
+    /// starts with only the root it was created with.
+    fn setup_demo(&mut self, builtins: &BuiltinDefinitions) {
+        if let Err(err) = self.run_script(DEMO_SCRIPT, &mut builtins.script_names()) {
+            eprintln!("The demo script is invalid: {}", err);
+        }
     }
 
     pub fn push_simple_struct(&mut self, name: &str, components: Vec<(&str, TypedBlob)>) -> NodeId {
@@ -843,12 +875,36 @@ impl Engine {
         (node, parameters)
     }
 
+    /// Adds a node that is valid by construction, such as one built by the
+    /// engine itself. The node is only checked in debug builds.
     pub fn push_node(&mut self, node: Node) -> NodeId {
         let id = self.node_ids.next();
         self.nodes.insert(id, node);
+        if cfg!(debug_assertions) {
+            let result = self
+                .check_references_exist(id, &self[id])
+                .and_then(|()| self.check_arity(id));
+            if let Err(err) = result {
+                panic!("Tried to add an invalid node: {}", err);
+            }
+        }
         id
     }
 
+    /// Adds a node that may be invalid, such as one built from user input.
+    /// Returns an error without adding it if it is.
+    pub fn try_push_node(&mut self, node: Node) -> Result<NodeId, ValidationError> {
+        let id = self.node_ids.next();
+        // Nothing uses the new node yet, so it cannot be part of a cycle.
+        self.check_references_exist(id, &node)?;
+        self.nodes.insert(id, node);
+        if let Err(err) = self.check_arity(id) {
+            self.nodes.remove(&id);
+            return Err(err);
+        }
+        Ok(id)
+    }
+
     pub fn push_literal_node(&mut self, value: TypedBlob) -> NodeId {
         self.push_node(Node {
             operation: NodeOperation::Literal(value),
@@ -857,6 +913,14 @@ impl Engine {
         })
     }
 
+    pub fn push_basic(&mut self, op: BasicOp, input: NodeId, argument: NodeId) -> NodeId {
+        self.push_node(Node {
+            operation: NodeOperation::Basic(op),
+            input: Some(input),
+            arguments: vec![argument],
+        })
+    }
+
     pub fn push_get_component(&mut self, input: NodeId, component_name: &str) -> NodeId {
         self.push_node(Node {
             operation: NodeOperation::GetComponent(component_name.into()),
@@ -872,6 +936,27 @@ impl Engine {
         param
     }
 
+    /// Pushes a parameter whose default is the given vector composer with all
+    /// of its components set to zero.
+    pub fn push_vector_parameter(&mut self, name: &str, composer: NodeId) -> (ParameterId, NodeId) {
+        let zero = match self.output_layout(composer).layout_after_index(Some(&format!("X").into()))
+        {
+            BlobLayout::Integer => 0.into(),
+            _ => 0.0.into(),
+        };
+        let zero = self.push_literal_node(zero);
+        let default = self.push_node(Node {
+            operation: NodeOperation::CustomNode {
+                result: composer,
+                input: None,
+            },
+            input: None,
+            arguments: vec![zero, zero],
+        });
+        let name = self.push_literal_node(name.to_owned().into());
+        self.push_parameter(name, default)
+    }
+
     pub fn push_parameter(&mut self, name: NodeId, default_value: NodeId) -> (ParameterId, NodeId) {
         let id = self.parameter_ids.next();
         let node = Node {
@@ -900,22 +985,10 @@ impl Engine {
     }
 
     pub fn mark_dirty(&mut self, node: NodeId) {
-        self.context.undefined_functions.insert(FunctionKind::ExternalWrapper(node));
-        self.context.undefined_functions.insert(FunctionKind::InternalImplementation(node));
-        let mut other_dirty = Vec::new();
-        for (id, other) in &self.nodes {
-            if other
-                .arguments
-                .iter()
-                .chain(other.input.iter())
-                .any(|inp| *inp == node)
-            {
-                other_dirty.push(*id);
-                continue;
-            }
-        }
-        for id in other_dirty {
-            self.mark_dirty(id);
+        for node in self.dependents(node) {
+            self.context.undefined_functions.insert(FunctionKind::ExternalWrapper(node));
+            self.context.undefined_functions.insert(FunctionKind::InternalImplementation(node));
+            self.results.forget(node);
         }
     }
 }
@@ -1057,9 +1130,33 @@ pub enum BasicOp {
     Subtract,
     Multiply,
     Divide,
+    Minimum,
+    Maximum,
+    /// Rounds the input to a multiple of the argument.
+    Snap,
 }
 
 impl BasicOp {
+    pub const ALL: [BasicOp; 7] = [
+        BasicOp::Add,
+        BasicOp::Subtract,
+        BasicOp::Multiply,
+        BasicOp::Divide,
+        BasicOp::Minimum,
+        BasicOp::Maximum,
+        BasicOp::Snap,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().find(|op| op.name() == name).cloned()
+    }
+
+    /// Whether swapping the input and the argument gives the same result.
+    pub fn is_commutative(&self) -> bool {
+        use BasicOp::*;
+        matches!(self, Add | Multiply | Minimum | Maximum)
+    }
+
     pub fn name(&self) -> &'static str {
         use BasicOp::*;
         match self {
@@ -1067,6 +1164,9 @@ impl BasicOp {
             Subtract => "Subtract",
             Multiply => "Multiply",
             Divide => "Divide",
+            Minimum => "Minimum",
+            Maximum => "Maximum",
+            Snap => "Snap",
         }
     }
 
@@ -1077,6 +1177,9 @@ impl BasicOp {
             Subtract => &["Offset"],
             Multiply => &["Factor"],
             Divide => &["Divisor"],
+            Minimum => &["Limit"],
+            Maximum => &["Limit"],
+            Snap => &["Increment"],
         }
     }
 }
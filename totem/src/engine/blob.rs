@@ -6,6 +6,7 @@ mod debug;
 mod index;
 mod extractors;
 mod leak_unleak;
+mod raw;
 
 use std::fmt::{self, Debug, Formatter, Display};
 
@@ -0,0 +1,127 @@
+use super::{Blob, TypedBlob};
+use crate::engine::BlobLayout;
+
+impl TypedBlob {
+    /// The layout and contents of the blob, for storing it outside the
+    /// engine.
+    pub fn raw_parts(&self) -> (&BlobLayout, &Blob) {
+        (&self.layout, &self.blob)
+    }
+
+    /// The inverse of raw_parts. Checks that the layout is supported and
+    /// that the fixed part and the number of dynamic components match it.
+    pub fn from_raw_parts(layout: BlobLayout, blob: Blob) -> Result<Self, String> {
+        layout.check_supported()?;
+        if layout.is_fixed() && blob.bytes.len() != layout.size() as usize {
+            return Err(format!(
+                "A value of type {:?} takes {} bytes, found {}",
+                layout,
+                layout.size(),
+                blob.bytes.len()
+            ));
+        }
+        if layout.is_fixed() && blob.dynamic_components.len() != layout.num_dynamic_components(None)
+        {
+            return Err(format!(
+                "A value of type {:?} has {} dynamic components, found {}",
+                layout,
+                layout.num_dynamic_components(None),
+                blob.dynamic_components.len()
+            ));
+        }
+        Ok(Self { blob, layout })
+    }
+}
+
+impl BlobLayout {
+    /// The name this kind of layout is stored under outside the engine, in
+    /// JSON documents and copied text alike.
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            BlobLayout::Float => "float",
+            BlobLayout::Integer => "integer",
+            BlobLayout::Byte => "byte",
+            BlobLayout::FixedIndex(..) => "fixed_index",
+            BlobLayout::DynamicIndex(..) => "dynamic_index",
+            BlobLayout::FixedHeterogeneousMap(..) => "fixed_heterogeneous_map",
+            BlobLayout::FixedHomogeneousMap(..) => "fixed_homogeneous_map",
+            BlobLayout::DynamicMap(..) => "dynamic_map",
+        }
+    }
+
+    /// The layout without components whose kind_name is name.
+    pub fn scalar_from_kind_name(name: &str) -> Option<Self> {
+        [BlobLayout::Float, BlobLayout::Integer, BlobLayout::Byte]
+            .into_iter()
+            .find(|layout| layout.kind_name() == name)
+    }
+
+    /// Checks that the engine can take apart values of this layout: every
+    /// map has a list of exactly one key per value, which indexing relies on,
+    /// and strings are the only values with a dynamic size. Layouts read from
+    /// outside the engine may break either.
+    pub fn check_supported(&self) -> Result<(), String> {
+        match self {
+            BlobLayout::Float | BlobLayout::Integer | BlobLayout::Byte => Ok(()),
+            BlobLayout::FixedIndex(_, eltype) => eltype.check_supported(),
+            BlobLayout::DynamicIndex(eltype) if **eltype == BlobLayout::Byte => Ok(()),
+            BlobLayout::FixedHeterogeneousMap(keys, eltypes) => {
+                check_key_count(keys, eltypes.len())?;
+                eltypes.iter().try_for_each(BlobLayout::check_supported)
+            }
+            BlobLayout::DynamicIndex(..)
+            | BlobLayout::FixedHomogeneousMap(..)
+            | BlobLayout::DynamicMap(..) => {
+                Err(format!("Values of type {:?} are not supported", self))
+            }
+        }
+    }
+}
+
+fn check_key_count(keys: &TypedBlob, count: usize) -> Result<(), String> {
+    keys.layout.check_supported()?;
+    match keys.layout {
+        BlobLayout::FixedIndex(len, _) if len as usize == count && count > 0 => Ok(()),
+        _ => Err(format!(
+            "A map with {} values needs a list of {} keys, found {:?}",
+            count, count, keys.layout
+        )),
+    }
+}
+
+impl Blob {
+    pub fn new(bytes: Vec<u8>, dynamic_components: Vec<Blob>) -> Self {
+        Self {
+            bytes,
+            dynamic_components,
+        }
+    }
+
+    /// Reads a blob whose fixed part was written by hex.
+    pub fn from_hex(hex: &str, dynamic_components: Vec<Blob>) -> Result<Self, String> {
+        let bytes = if hex.len() % 2 == 0 && hex.is_ascii() {
+            (0..hex.len())
+                .step_by(2)
+                .map(|start| u8::from_str_radix(&hex[start..start + 2], 16).ok())
+                .collect::<Option<Vec<_>>>()
+        } else {
+            None
+        };
+        match bytes {
+            Some(bytes) => Ok(Self::new(bytes, dynamic_components)),
+            None => Err(format!("\"{}\" is not hexadecimal", hex)),
+        }
+    }
+
+    /// The fixed part of the blob as two hexadecimal digits per byte.
+    pub fn hex(&self) -> String {
+        self.bytes
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    pub fn dynamic_components(&self) -> &[Blob] {
+        &self.dynamic_components
+    }
+}
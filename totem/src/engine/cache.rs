@@ -0,0 +1,154 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+};
+
+use super::{Engine, NodeId, ParameterId, TypedBlob};
+
+/// How many differently parameterized results are kept for each node. Tools
+/// evaluate their handlers with new inputs every frame, so old ones have to
+/// be dropped at some point.
+const RESULTS_PER_NODE: usize = 8;
+
+/// Outputs of nodes that have already been executed, keyed on the inputs
+/// they were executed with.
+pub(super) struct ResultCache {
+    results: HashMap<NodeId, Vec<(TypedBlob, TypedBlob)>>,
+    /// The inputs of nodes that have been evaluated with the default values
+    /// of their parameters. The editor asks for those every frame, and
+    /// building them means walking everything the node depends on.
+    default_inputs: HashMap<NodeId, TypedBlob>,
+}
+
+impl ResultCache {
+    pub(super) fn new() -> Self {
+        Self {
+            results: HashMap::new(),
+            default_inputs: HashMap::new(),
+        }
+    }
+
+    fn get(&self, node: NodeId, inputs: &TypedBlob) -> Option<&TypedBlob> {
+        self.results
+            .get(&node)?
+            .iter()
+            .find(|(key, _)| key == inputs)
+            .map(|(_, output)| output)
+    }
+
+    fn insert(&mut self, node: NodeId, inputs: TypedBlob, output: TypedBlob) {
+        let results = self.results.entry(node).or_default();
+        if results.len() == RESULTS_PER_NODE {
+            results.remove(0);
+        }
+        results.push((inputs, output));
+    }
+
+    pub(super) fn forget(&mut self, node: NodeId) {
+        self.results.remove(&node);
+        self.default_inputs.remove(&node);
+    }
+}
+
+impl Engine {
+    /// The output of the node when its parameters have the given values, or
+    /// their defaults if they are missing. Executes the node only if it has
+    /// not been executed with the same values since it last changed.
+    pub fn evaluate(
+        &mut self,
+        node: NodeId,
+        inputs: &HashMap<ParameterId, TypedBlob>,
+    ) -> TypedBlob {
+        let key = match self.results.default_inputs.get(&node) {
+            Some(key) if inputs.is_empty() => Cow::Borrowed(key),
+            _ => Cow::Owned(self.io_blob_with_inputs(node, inputs)),
+        };
+        if let Some(output) = self.results.get(node, &key) {
+            return output.clone();
+        }
+        let key = key.into_owned();
+        if inputs.is_empty() {
+            self.results.default_inputs.insert(node, key.clone());
+        }
+        let mut io = key.clone();
+        self.execute(node, &mut io);
+        let output = io.view().index(&format!("OUTPUT").into()).to_owned();
+        self.results.insert(node, key, output.clone());
+        output
+    }
+
+    /// Like evaluate, but only returns a result if there already is one.
+    /// Results for the default values of the parameters are only found if
+    /// evaluate was called without inputs.
+    pub fn cached_result(
+        &self,
+        node: NodeId,
+        inputs: &HashMap<ParameterId, TypedBlob>,
+    ) -> Option<&TypedBlob> {
+        if inputs.is_empty() {
+            let key = self.results.default_inputs.get(&node)?;
+            self.results.get(node, key)
+        } else {
+            self.results
+                .get(node, &self.io_blob_with_inputs(node, inputs))
+        }
+    }
+
+    /// The node and every node that uses its output, directly or not,
+    /// including uses of custom nodes it is part of the definition of.
+    pub(super) fn dependents(&self, node: NodeId) -> HashSet<NodeId> {
+        let mut users: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
+        for (&id, other) in &self.nodes {
+            for dependency in other.dependencies() {
+                users.entry(dependency).or_default().push(id);
+            }
+        }
+        let mut dependents = HashSet::new();
+        let mut stack = vec![node];
+        while let Some(node) = stack.pop() {
+            if dependents.insert(node) {
+                stack.extend(users.get(&node).into_iter().flatten().copied());
+            }
+        }
+        dependents
+    }
+
+    /// Drops the results of everything that depends on the node, for when
+    /// its value changes without it having to be compiled again.
+    pub(super) fn forget_results(&mut self, node: NodeId) {
+        for node in self.dependents(node) {
+            self.results.forget(node);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{BasicOp, Node, NodeOperation};
+
+    #[test]
+    fn changing_a_definition_forgets_results_of_its_uses() {
+        let (mut engine, _) = Engine::new();
+        let left = engine.push_literal_node(TypedBlob::from(1.0));
+        let right = engine.push_literal_node(TypedBlob::from(2.0));
+        let sum = engine.push_basic(BasicOp::Add, left, right);
+        let node = Node {
+            operation: NodeOperation::CustomNode {
+                result: sum,
+                input: None,
+            },
+            input: None,
+            arguments: Vec::new(),
+        };
+        let custom = engine.try_push_node(node).unwrap();
+        let no_inputs = HashMap::new();
+        assert!(engine.cached_result(custom, &no_inputs).is_none());
+        assert_eq!(engine.evaluate(custom, &no_inputs).view().as_f32(), Ok(3.0));
+        let cached = engine.cached_result(custom, &no_inputs).unwrap();
+        assert_eq!(cached.view().as_f32(), Ok(3.0));
+        engine.set_literal_value(left, 5.0.into()).unwrap();
+        assert!(engine.cached_result(custom, &no_inputs).is_none());
+        assert_eq!(engine.evaluate(custom, &no_inputs).view().as_f32(), Ok(7.0));
+    }
+}
@@ -0,0 +1,598 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt::{self, Display, Formatter, Write},
+    iter::Peekable,
+    str::Chars,
+};
+
+use super::{
+    validate::ValidationError, BasicOp, Blob, BlobLayout, BuiltinDefinitions, Engine, Node, NodeId,
+    NodeOperation, Parameter, ParameterId, TypedBlob,
+};
+use crate::util::IdCreator;
+
+#[derive(Clone, Debug)]
+pub enum PasteError {
+    /// The text does not have the shape of a copied subgraph.
+    Malformed {
+        expected: &'static str,
+        found: String,
+    },
+    UnknownOperation(String),
+    UnknownLayout(String),
+    /// A node refers to a node that is not part of the text.
+    UnknownNode(String),
+    UnknownBuiltin(String),
+    InvalidValue(String),
+    Invalid(ValidationError),
+}
+
+impl Display for PasteError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed { expected, found } => {
+                write!(f, "Expected {}, found {}", expected, found)
+            }
+            Self::UnknownOperation(name) => write!(f, "\"{}\" is not an operation", name),
+            Self::UnknownLayout(name) => write!(f, "\"{}\" is not a type", name),
+            Self::UnknownNode(index) => write!(f, "There is no node {}", index),
+            Self::UnknownBuiltin(name) => write!(f, "There is no builtin named {}", name),
+            Self::InvalidValue(message) => write!(f, "{}", message),
+            Self::Invalid(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<ValidationError> for PasteError {
+    fn from(err: ValidationError) -> Self {
+        Self::Invalid(err)
+    }
+}
+
+/// Copied subgraphs are written as nested lists, such as
+/// `(node 0 (basic Add) 1 (2))`. Builtin definitions are written by name, as
+/// in `(builtin 1 "display_position")`.
+#[derive(Clone, Debug)]
+enum Expr {
+    Atom(String),
+    Text(String),
+    List(Vec<Expr>),
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Atom(atom) => write!(f, "{}", atom),
+            Self::Text(text) => {
+                write!(f, "\"")?;
+                for c in text.chars() {
+                    match c {
+                        '"' | '\\' => write!(f, "\\{}", c)?,
+                        '\n' => write!(f, "\\n")?,
+                        '\t' => write!(f, "\\t")?,
+                        '\r' => write!(f, "\\r")?,
+                        c => write!(f, "{}", c)?,
+                    }
+                }
+                write!(f, "\"")
+            }
+            Self::List(items) => {
+                write!(f, "(")?;
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+fn atom(text: impl ToString) -> Expr {
+    Expr::Atom(text.to_string())
+}
+
+fn text(text: &str) -> Expr {
+    Expr::Text(text.to_owned())
+}
+
+fn list(items: impl IntoIterator<Item = Expr>) -> Expr {
+    Expr::List(items.into_iter().collect())
+}
+
+fn malformed<T>(expected: &'static str, found: &Expr) -> Result<T, PasteError> {
+    Err(PasteError::Malformed {
+        expected,
+        found: found.to_string(),
+    })
+}
+
+fn read(text: &str) -> Result<Expr, PasteError> {
+    let mut chars = text.chars().peekable();
+    let expr = read_expr(&mut chars)?;
+    skip_whitespace(&mut chars);
+    if chars.peek().is_some() {
+        return Err(PasteError::Malformed {
+            expected: "the end of the text",
+            found: chars.collect(),
+        });
+    }
+    Ok(expr)
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.peek().map_or(false, |c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+fn read_expr(chars: &mut Peekable<Chars>) -> Result<Expr, PasteError> {
+    let end = || PasteError::Malformed {
+        expected: "a value",
+        found: format!("the end of the text"),
+    };
+    skip_whitespace(chars);
+    match chars.next().ok_or_else(end)? {
+        '(' => {
+            let mut items = Vec::new();
+            loop {
+                skip_whitespace(chars);
+                match chars.peek() {
+                    Some(')') => {
+                        chars.next();
+                        return Ok(Expr::List(items));
+                    }
+                    Some(..) => items.push(read_expr(chars)?),
+                    None => return Err(end()),
+                }
+            }
+        }
+        ')' => Err(PasteError::Malformed {
+            expected: "a value",
+            found: format!("')'"),
+        }),
+        '"' => {
+            let mut text = String::new();
+            loop {
+                match chars.next().ok_or_else(end)? {
+                    '"' => return Ok(Expr::Text(text)),
+                    '\\' => match chars.next().ok_or_else(end)? {
+                        'n' => text.push('\n'),
+                        't' => text.push('\t'),
+                        'r' => text.push('\r'),
+                        c => text.push(c),
+                    },
+                    c => text.push(c),
+                }
+            }
+        }
+        c => {
+            let mut atom = String::from(c);
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == '(' || c == ')' || c == '"' {
+                    break;
+                }
+                atom.push(c);
+                chars.next();
+            }
+            Ok(Expr::Atom(atom))
+        }
+    }
+}
+
+fn value_expr(value: &TypedBlob) -> Expr {
+    let view = value.view();
+    if let Ok(value) = view.as_f32() {
+        list([atom("float"), atom(format!("{:?}", value))])
+    } else if let Ok(value) = view.as_i32() {
+        list([atom("integer"), atom(value)])
+    } else if let Ok(value) = view.as_string() {
+        list([atom("string"), text(value)])
+    } else {
+        let (layout, blob) = value.raw_parts();
+        list([atom("blob"), layout_expr(layout), blob_expr(blob)])
+    }
+}
+
+fn blob_expr(blob: &Blob) -> Expr {
+    list([
+        Expr::Text(blob.hex()),
+        list(blob.dynamic_components().iter().map(blob_expr)),
+    ])
+}
+
+fn layout_expr(layout: &BlobLayout) -> Expr {
+    let kind = atom(layout.kind_name());
+    match layout {
+        BlobLayout::Float | BlobLayout::Integer | BlobLayout::Byte => kind,
+        BlobLayout::FixedIndex(len, eltype) => list([kind, atom(len), layout_expr(eltype)]),
+        BlobLayout::DynamicIndex(eltype) | BlobLayout::DynamicMap(eltype) => {
+            list([kind, layout_expr(eltype)])
+        }
+        BlobLayout::FixedHeterogeneousMap(keys, eltypes) => list([
+            kind,
+            value_expr(keys),
+            list(eltypes.iter().map(layout_expr)),
+        ]),
+        BlobLayout::FixedHomogeneousMap(keys, len, eltype) => {
+            list([kind, value_expr(keys), atom(len), layout_expr(eltype)])
+        }
+    }
+}
+
+fn parse_atom<T: std::str::FromStr>(expr: &Expr, expected: &'static str) -> Result<T, PasteError> {
+    match expr {
+        Expr::Atom(atom) => atom.parse().or_else(|_| malformed(expected, expr)),
+        _ => malformed(expected, expr),
+    }
+}
+
+fn parse_value(expr: &Expr) -> Result<TypedBlob, PasteError> {
+    let Expr::List(items) = expr else {
+        return malformed("a value", expr);
+    };
+    match &items[..] {
+        [Expr::Atom(kind), value] if kind == "float" => {
+            Ok(parse_atom::<f32>(value, "a decimal number")?.into())
+        }
+        [Expr::Atom(kind), value] if kind == "integer" => {
+            Ok(parse_atom::<i32>(value, "a whole number")?.into())
+        }
+        [Expr::Atom(kind), Expr::Text(value)] if kind == "string" => Ok(value.clone().into()),
+        [Expr::Atom(kind), layout, blob] if kind == "blob" => {
+            TypedBlob::from_raw_parts(parse_layout(layout)?, parse_blob(blob)?)
+                .map_err(PasteError::InvalidValue)
+        }
+        _ => malformed("a value", expr),
+    }
+}
+
+fn parse_blob(expr: &Expr) -> Result<Blob, PasteError> {
+    let Expr::List(items) = expr else {
+        return malformed("raw data", expr);
+    };
+    let [Expr::Text(hex), Expr::List(components)] = &items[..] else {
+        return malformed("raw data", expr);
+    };
+    let components = components
+        .iter()
+        .map(parse_blob)
+        .collect::<Result<Vec<_>, _>>()?;
+    Blob::from_hex(hex, components).map_err(PasteError::InvalidValue)
+}
+
+fn parse_layout(expr: &Expr) -> Result<BlobLayout, PasteError> {
+    let items = match expr {
+        Expr::Atom(name) => {
+            return BlobLayout::scalar_from_kind_name(name)
+                .ok_or_else(|| PasteError::UnknownLayout(name.clone()))
+        }
+        Expr::List(items) => items,
+        Expr::Text(..) => return malformed("a type", expr),
+    };
+    let Some((Expr::Atom(kind), rest)) = items.split_first() else {
+        return malformed("a type", expr);
+    };
+    let boxed = |expr| parse_layout(expr).map(Box::new);
+    match (&kind[..], rest) {
+        ("fixed_index", [len, eltype]) => Ok(BlobLayout::FixedIndex(
+            parse_atom(len, "a length")?,
+            boxed(eltype)?,
+        )),
+        ("dynamic_index", [eltype]) => Ok(BlobLayout::DynamicIndex(boxed(eltype)?)),
+        ("fixed_heterogeneous_map", [keys, Expr::List(eltypes)]) => {
+            Ok(BlobLayout::FixedHeterogeneousMap(
+                Box::new(parse_value(keys)?),
+                eltypes.iter().map(parse_layout).collect::<Result<_, _>>()?,
+            ))
+        }
+        ("fixed_homogeneous_map", [keys, len, eltype]) => Ok(BlobLayout::FixedHomogeneousMap(
+            Box::new(parse_value(keys)?),
+            parse_atom(len, "a length")?,
+            boxed(eltype)?,
+        )),
+        ("dynamic_map", [eltype]) => Ok(BlobLayout::DynamicMap(boxed(eltype)?)),
+        (
+            "fixed_index"
+            | "dynamic_index"
+            | "fixed_heterogeneous_map"
+            | "fixed_homogeneous_map"
+            | "dynamic_map",
+            _,
+        ) => malformed("a type", expr),
+        _ => Err(PasteError::UnknownLayout(kind.clone())),
+    }
+}
+
+/// The IDs that the nodes and parameters of pasted text are given.
+struct Remap<'a> {
+    nodes: HashMap<String, NodeId>,
+    parameters: HashMap<String, ParameterId>,
+    parameter_ids: &'a mut IdCreator<Parameter>,
+}
+
+impl<'a> Remap<'a> {
+    fn node(&self, expr: &Expr) -> Result<NodeId, PasteError> {
+        match expr {
+            Expr::Atom(index) => self
+                .nodes
+                .get(index)
+                .copied()
+                .ok_or_else(|| PasteError::UnknownNode(index.clone())),
+            _ => malformed("a node number", expr),
+        }
+    }
+
+    fn optional_node(&self, expr: &Expr) -> Result<Option<NodeId>, PasteError> {
+        match expr {
+            Expr::Atom(none) if none == "none" => Ok(None),
+            _ => self.node(expr).map(Some),
+        }
+    }
+
+    fn parameter(&mut self, expr: &Expr) -> Result<ParameterId, PasteError> {
+        let Expr::Atom(index) = expr else {
+            return malformed("a parameter number", expr);
+        };
+        let parameter_ids = &mut *self.parameter_ids;
+        Ok(*self
+            .parameters
+            .entry(index.clone())
+            .or_insert_with(|| parameter_ids.next()))
+    }
+
+    fn operation(&mut self, expr: &Expr) -> Result<NodeOperation, PasteError> {
+        let Expr::List(items) = expr else {
+            return malformed("an operation", expr);
+        };
+        let Some((Expr::Atom(kind), rest)) = items.split_first() else {
+            return malformed("an operation", expr);
+        };
+        match (&kind[..], rest) {
+            ("literal", [value]) => Ok(NodeOperation::Literal(parse_value(value)?)),
+            ("parameter", [parameter]) => Ok(NodeOperation::Parameter(self.parameter(parameter)?)),
+            ("basic", [Expr::Atom(name)]) => BasicOp::from_name(name)
+                .map(NodeOperation::Basic)
+                .ok_or_else(|| PasteError::UnknownOperation(name.clone())),
+            ("compose", [Expr::Text(name), Expr::List(components)]) => {
+                let components = components
+                    .iter()
+                    .map(|component| match component {
+                        Expr::Text(component) => Ok(component.clone()),
+                        _ => malformed("a component name", component),
+                    })
+                    .collect::<Result<_, _>>()?;
+                Ok(NodeOperation::ComposeStruct(name.clone(), components))
+            }
+            ("get", [Expr::Text(name)]) => Ok(NodeOperation::GetComponent(name.clone())),
+            ("custom", [result, input]) => Ok(NodeOperation::CustomNode {
+                result: self.node(result)?,
+                input: match input {
+                    Expr::Atom(none) if none == "none" => None,
+                    _ => Some(self.parameter(input)?),
+                },
+            }),
+            ("literal" | "parameter" | "basic" | "compose" | "get" | "custom", _) => {
+                malformed("an operation", expr)
+            }
+            _ => Err(PasteError::UnknownOperation(kind.clone())),
+        }
+    }
+}
+
+impl Engine {
+    /// The node and everything it depends on, with the nodes closest to it
+    /// first. What the given leaves depend on is left out.
+    pub(super) fn subgraph(&self, node: NodeId, leaves: &HashMap<NodeId, String>) -> Vec<NodeId> {
+        let mut order = Vec::new();
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::from([node]);
+        while let Some(node) = queue.pop_front() {
+            if visited.insert(node) {
+                order.push(node);
+                if !leaves.contains_key(&node) {
+                    queue.extend(self[node].dependencies());
+                }
+            }
+        }
+        order
+    }
+
+    /// Writes node and everything it depends on as text, including the
+    /// definitions of custom nodes, so that it can be pasted into any
+    /// engine. Builtin definitions are written by name, so that pasted
+    /// copies still read the parameters the app fills in.
+    pub fn copy_subgraph(&self, node: NodeId, builtins: &BuiltinDefinitions) -> String {
+        let builtin_names: HashMap<_, _> = builtins
+            .script_names()
+            .into_iter()
+            .map(|(name, node)| (node, name))
+            .collect();
+        let order = self.subgraph(node, &builtin_names);
+        let indices: HashMap<_, _> = order
+            .iter()
+            .enumerate()
+            .map(|(index, &node)| (node, index))
+            .collect();
+        let mut parameters = HashMap::new();
+        let mut parameter = |id: ParameterId| {
+            let next = parameters.len();
+            atom(*parameters.entry(id).or_insert(next))
+        };
+        let index = |node: &NodeId| atom(indices[node]);
+        let mut result = format!("(subgraph\n");
+        for id in order {
+            if let Some(name) = builtin_names.get(&id) {
+                let builtin = list([atom("builtin"), index(&id), text(name)]);
+                writeln!(result, "  {}", builtin).unwrap();
+                continue;
+            }
+            let node = &self[id];
+            let operation = match &node.operation {
+                NodeOperation::Literal(value) => list([atom("literal"), value_expr(value)]),
+                NodeOperation::Parameter(id) => list([atom("parameter"), parameter(*id)]),
+                NodeOperation::Basic(op) => list([atom("basic"), atom(op.name())]),
+                NodeOperation::ComposeStruct(name, components) => list([
+                    atom("compose"),
+                    text(name),
+                    list(components.iter().map(|component| text(component))),
+                ]),
+                NodeOperation::GetComponent(name) => list([atom("get"), text(name)]),
+                NodeOperation::CustomNode { result, input } => list([
+                    atom("custom"),
+                    index(result),
+                    input.map_or(atom("none"), &mut parameter),
+                ]),
+            };
+            let input = node.input.as_ref().map_or(atom("none"), index);
+            let arguments = list(node.arguments.iter().map(index));
+            let node = list([atom("node"), index(&id), operation, input, arguments]);
+            writeln!(result, "  {}", node).unwrap();
+        }
+        result.push(')');
+        result
+    }
+
+    /// Adds the nodes written by copy_subgraph to this engine, giving them
+    /// and their parameters new IDs. Builtins are the ones of this engine.
+    /// Returns the node that was copied.
+    pub fn paste_subgraph(
+        &mut self,
+        text: &str,
+        builtins: &BuiltinDefinitions,
+    ) -> Result<NodeId, PasteError> {
+        let expr = read(text)?;
+        let nodes = match &expr {
+            Expr::List(items) => match items.split_first() {
+                Some((Expr::Atom(kind), nodes)) if kind == "subgraph" && nodes.len() > 0 => nodes,
+                _ => return malformed("a copied subgraph", &expr),
+            },
+            _ => return malformed("a copied subgraph", &expr),
+        };
+        let mut remap = Remap {
+            nodes: HashMap::new(),
+            parameters: HashMap::new(),
+            parameter_ids: &mut self.parameter_ids,
+        };
+        let builtin_names = builtins.script_names();
+        let mut copied = None;
+        let mut fields = Vec::new();
+        for node in nodes {
+            let (index, id) = match node {
+                Expr::List(items) => match &items[..] {
+                    [Expr::Atom(kind), Expr::Atom(index), operation, input, Expr::List(arguments)]
+                        if kind == "node" =>
+                    {
+                        let id = self.node_ids.next();
+                        fields.push((id, operation, input, arguments));
+                        (index, id)
+                    }
+                    [Expr::Atom(kind), Expr::Atom(index), Expr::Text(name)]
+                        if kind == "builtin" =>
+                    {
+                        match builtin_names.get(name) {
+                            Some(&id) => (index, id),
+                            None => return Err(PasteError::UnknownBuiltin(name.clone())),
+                        }
+                    }
+                    _ => return malformed("a node", node),
+                },
+                _ => return malformed("a node", node),
+            };
+            if remap.nodes.insert(index.clone(), id).is_some() {
+                return malformed("each node number only once", node);
+            }
+            copied.get_or_insert(id);
+        }
+        let mut new_nodes = Vec::new();
+        for (id, operation, input, arguments) in fields {
+            let node = Node {
+                operation: remap.operation(operation)?,
+                input: remap.optional_node(input)?,
+                arguments: arguments
+                    .iter()
+                    .map(|argument| remap.node(argument))
+                    .collect::<Result<_, _>>()?,
+            };
+            new_nodes.push((id, node));
+        }
+        self.insert_nodes(new_nodes)?;
+        // There is at least one node, so something was copied.
+        Ok(copied.unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pasting_a_copy_gives_the_same_graph() {
+        let (mut engine, builtins) = Engine::new();
+        let number = engine.push_literal_node(1.5.into());
+        let name = engine.push_literal_node(format!("A \"quoted\"\nname").into());
+        let (_, parameter) = engine.push_parameter(name, number);
+        let sum = engine.push_basic(BasicOp::Add, parameter, number);
+        let vector = engine.push_literal_node(TypedBlob::fixed_heterogeneous_map(vec![
+            (format!("X").into(), (-3).into()),
+            (format!("Y").into(), i32::MAX.into()),
+        ]));
+        let root = engine.push_node(Node {
+            operation: NodeOperation::ComposeStruct(
+                format!("Pair"),
+                vec![format!("Sum"), format!("Vector")],
+            ),
+            input: None,
+            arguments: vec![sum, vector],
+        });
+        let copy = engine.copy_subgraph(root, &builtins);
+        let pasted = engine.paste_subgraph(&copy, &builtins).unwrap();
+        assert_ne!(pasted, root);
+        assert_eq!(engine.copy_subgraph(pasted, &builtins), copy);
+        assert_eq!(engine.output_layout(pasted), engine.output_layout(root));
+        assert!(engine.validate().is_ok());
+    }
+
+    #[test]
+    fn pasted_copies_read_the_same_builtins() {
+        let (mut engine, builtins) = Engine::new();
+        let mut names = builtins.script_names();
+        let script = r#"root = get(display_position, "Y") / parameter("Scale", 180.0)"#;
+        engine.run_script(script, &mut names).unwrap();
+        let root = names["root"];
+        let copy = engine.copy_subgraph(root, &builtins);
+        let count = engine.nodes().len();
+        let pasted = engine.paste_subgraph(&copy, &builtins).unwrap();
+        // Only the division, the component, the scale and its name and
+        // default are new.
+        assert_eq!(engine.nodes().len(), count + 5);
+        let parameters = |node: NodeId| {
+            engine[node]
+                .collect_parameters(engine.nodes())
+                .into_iter()
+                .map(|parameter| parameter.id)
+                .collect::<HashSet<_>>()
+        };
+        let (original, pasted) = (parameters(root), parameters(pasted));
+        let display_position = builtins.display_position.0;
+        assert!(original.contains(&display_position) && pasted.contains(&display_position));
+        assert_eq!(original.intersection(&pasted).count(), 1);
+        assert!(engine
+            .paste_subgraph("(subgraph (builtin 0 \"nowhere\"))", &builtins)
+            .is_err());
+        assert!(engine.validate().is_ok());
+    }
+
+    #[test]
+    fn paste_rejects_map_keys_that_do_not_fit() {
+        let (mut engine, builtins) = Engine::new();
+        let scalar_keys =
+            "(subgraph (node 0 (literal (blob (fixed_heterogeneous_map (integer 1) (float)) (\"00000000\" ()))) none ()))";
+        assert!(engine.paste_subgraph(scalar_keys, &builtins).is_err());
+        let too_few_keys = "(subgraph (node 0 (literal (blob (fixed_heterogeneous_map (blob (fixed_index 1 integer) (\"01000000\" ())) (float float)) (\"0000000000000000\" ()))) none ()))";
+        assert!(engine.paste_subgraph(too_few_keys, &builtins).is_err());
+        assert!(engine.validate().is_ok());
+    }
+}
@@ -0,0 +1,386 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display, Formatter},
+};
+
+use super::{validate::ValidationError, Engine, Node, NodeId, NodeOperation, TypedBlob};
+
+#[derive(Clone, Debug)]
+pub enum EditError {
+    /// The edit needs the node to have an input.
+    NoInput(NodeId),
+    /// The edit would change the type of a value that other nodes use.
+    TypeMismatch,
+    /// The edit would leave the graph invalid, for example by creating a
+    /// cycle.
+    Invalid(ValidationError),
+    /// The edit would change the result of nodes other than the one being
+    /// edited.
+    SharedNode(NodeId),
+    NoSuchArgument(NodeId, usize),
+    /// The node is not the input of any other node.
+    NothingBelow(NodeId),
+    /// The edit only works on literal nodes.
+    NotALiteral(NodeId),
+}
+
+impl Display for EditError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoInput(node) => write!(f, "{:?} has no input", node),
+            Self::TypeMismatch => write!(f, "The edit would change the type of a result"),
+            Self::Invalid(err) => write!(f, "{}", err),
+            Self::SharedNode(node) => write!(f, "{:?} is used by more than one node", node),
+            Self::NoSuchArgument(node, index) => {
+                write!(f, "{:?} does not have an argument {}", node, index)
+            }
+            Self::NothingBelow(node) => write!(f, "{:?} is not the input of another node", node),
+            Self::NotALiteral(node) => write!(f, "{:?} is not a literal", node),
+        }
+    }
+}
+
+impl From<ValidationError> for EditError {
+    fn from(err: ValidationError) -> Self {
+        Self::Invalid(err)
+    }
+}
+
+impl Engine {
+    /// Whether node uses the result of dependency, directly or indirectly.
+    pub fn depends_on(&self, node: NodeId, dependency: NodeId) -> bool {
+        self.dependencies(node).contains(&dependency)
+    }
+
+    /// The node itself and every node it uses, directly or indirectly.
+    fn dependencies(&self, node: NodeId) -> HashSet<NodeId> {
+        let mut stack = vec![node];
+        let mut visited = HashSet::new();
+        while let Some(node) = stack.pop() {
+            if visited.insert(node) {
+                stack.extend(self[node].dependencies());
+            }
+        }
+        visited
+    }
+
+    /// All nodes in the graph of the root node that use the result of the
+    /// given node. Nodes that are no longer part of that graph, such as ones
+    /// that have been deleted, are ignored.
+    pub fn consumers(&self, node: NodeId) -> Vec<NodeId> {
+        let live = self.dependencies(self.root_node);
+        self.nodes
+            .iter()
+            .filter(|(id, _)| live.contains(id))
+            .filter(|(_, other)| {
+                other
+                    .input
+                    .iter()
+                    .chain(other.arguments.iter())
+                    .any(|&used| used == node)
+            })
+            .map(|(&id, _)| id)
+            .collect()
+    }
+
+    /// Makes every node that uses `to` use `with` instead.
+    pub fn replace_references(&mut self, to: NodeId, with: NodeId) -> Result<(), EditError> {
+        self.redirect_references(to, with, None)
+    }
+
+    /// Like replace_references, but leaves the references of except alone.
+    fn redirect_references(
+        &mut self,
+        to: NodeId,
+        with: NodeId,
+        except: Option<NodeId>,
+    ) -> Result<(), EditError> {
+        let users = self
+            .nodes
+            .iter()
+            .filter(|(id, node)| {
+                Some(**id) != except
+                    && node
+                        .input
+                        .iter()
+                        .chain(node.arguments.iter())
+                        .any(|&used| used == to)
+            })
+            .map(|(&id, _)| id)
+            .collect::<Vec<_>>();
+        for &user in &users {
+            self.check_new_dependency(user, with)?;
+        }
+        for user in users {
+            let node = &mut self[user];
+            for arg in node.arguments.iter_mut().chain(node.input.iter_mut()) {
+                if *arg == to {
+                    *arg = with;
+                }
+            }
+        }
+        if self.root_node == to {
+            self.root_node = with;
+        }
+        Ok(())
+    }
+
+    /// Makes node use input as its input.
+    pub fn set_input(&mut self, node: NodeId, input: Option<NodeId>) -> Result<(), EditError> {
+        if let Some(input) = input {
+            self.check_new_dependency(node, input)?;
+        }
+        let old_input = std::mem::replace(&mut self[node].input, input);
+        if let Err(err) = self.check_arity(node) {
+            self[node].input = old_input;
+            return Err(err.into());
+        }
+        self.mark_dirty(node);
+        Ok(())
+    }
+
+    /// Makes everything that used node use new instead, where new is a node
+    /// that uses node.
+    pub fn insert_below(&mut self, node: NodeId, new: NodeId) -> Result<(), EditError> {
+        self.redirect_references(node, new, Some(new))?;
+        self.mark_dirty(new);
+        Ok(())
+    }
+
+    /// Makes new the input of node, where new is a node whose input is the
+    /// previous input of node.
+    pub fn insert_above(&mut self, node: NodeId, new: NodeId) -> Result<(), EditError> {
+        if self[node].input.is_none() {
+            return Err(EditError::NoInput(node));
+        }
+        self.check_new_dependency(node, new)?;
+        if self.output_layout(new) != self.output_layout(self[node].input.unwrap()) {
+            return Err(EditError::TypeMismatch);
+        }
+        self[node].input = Some(new);
+        self.mark_dirty(node);
+        Ok(())
+    }
+
+    /// Removes node from the graph by making everything that used it use its
+    /// input instead. Returns the input.
+    pub fn delete_node(&mut self, node: NodeId) -> Result<NodeId, EditError> {
+        let Some(input) = self[node].input else {
+            return Err(EditError::NoInput(node));
+        };
+        if self.output_layout(input) != self.output_layout(node) {
+            return Err(EditError::TypeMismatch);
+        }
+        self.replace_references(node, input)?;
+        self.mark_dirty(input);
+        Ok(input)
+    }
+
+    /// Adds a copy of node directly below it, so that the operation is
+    /// applied twice. Literal arguments are copied as well so that they can
+    /// be changed separately. Returns the copy.
+    pub fn duplicate_node(&mut self, node: NodeId) -> Result<NodeId, EditError> {
+        if self[node].input.is_none() {
+            return Err(EditError::NoInput(node));
+        }
+        if self.output_layout(self[node].input.unwrap()) != self.output_layout(node) {
+            return Err(EditError::TypeMismatch);
+        }
+        let mut arguments = Vec::new();
+        for argument in self[node].arguments.clone() {
+            arguments.push(match &self[argument].operation {
+                NodeOperation::Literal(value) => self.push_literal_node(value.clone()),
+                _ => argument,
+            });
+        }
+        let copy = self.push_node(Node {
+            operation: self[node].operation.clone(),
+            input: Some(node),
+            arguments,
+        });
+        self.insert_below(node, copy)?;
+        Ok(copy)
+    }
+
+    /// Copies node and everything it depends on except parameters, which
+    /// the copy shares with the original. The copy keeps producing the
+    /// current result while the original is edited. Returns the copy.
+    pub fn snapshot(&mut self, node: NodeId) -> NodeId {
+        self.snapshot_into(node, &mut HashMap::new())
+    }
+
+    fn snapshot_into(&mut self, node: NodeId, copies: &mut HashMap<NodeId, NodeId>) -> NodeId {
+        if let NodeOperation::Parameter(..) = self[node].operation {
+            return node;
+        }
+        if let Some(&copy) = copies.get(&node) {
+            return copy;
+        }
+        let mut operation = self[node].operation.clone();
+        if let NodeOperation::CustomNode { result, .. } = &mut operation {
+            *result = self.snapshot_into(*result, copies);
+        }
+        let input = self[node]
+            .input
+            .map(|input| self.snapshot_into(input, copies));
+        let arguments = self[node]
+            .arguments
+            .clone()
+            .into_iter()
+            .map(|argument| self.snapshot_into(argument, copies))
+            .collect();
+        let copy = self.push_node(Node {
+            operation,
+            input,
+            arguments,
+        });
+        copies.insert(node, copy);
+        copy
+    }
+
+    /// Swaps node with its input, so that its operation is applied first.
+    pub fn move_up(&mut self, node: NodeId) -> Result<(), EditError> {
+        let Some(above) = self[node].input else {
+            return Err(EditError::NoInput(node));
+        };
+        let Some(above_input) = self[above].input else {
+            return Err(EditError::NoInput(above));
+        };
+        if self.consumers(above) != vec![node] {
+            return Err(EditError::SharedNode(above));
+        }
+        // After the swap, above uses node, so node must not use above in
+        // any other way.
+        if self[node].arguments.contains(&above) {
+            return Err(ValidationError::Cycle(above).into());
+        }
+        let layout = self.output_layout(node);
+        if self.output_layout(above) != layout || self.output_layout(above_input) != layout {
+            return Err(EditError::TypeMismatch);
+        }
+        for used in self[node]
+            .dependencies()
+            .filter(|&used| used != above)
+            .collect::<Vec<_>>()
+        {
+            self.check_new_dependency(above, used)?;
+        }
+        // The operands of everything that changes, so that the swap can be
+        // undone if it leaves the graph invalid.
+        let saved = self
+            .nodes
+            .iter()
+            .filter(|(_, other)| {
+                other
+                    .input
+                    .iter()
+                    .chain(other.arguments.iter())
+                    .any(|&used| used == node)
+            })
+            .map(|(&id, other)| (id, other.input, other.arguments.clone()))
+            .collect::<Vec<_>>();
+        let saved_root = self.root_node;
+        for (id, _, _) in &saved {
+            if *id == above {
+                continue;
+            }
+            let other = &mut self[*id];
+            for arg in other.arguments.iter_mut().chain(other.input.iter_mut()) {
+                if *arg == node {
+                    *arg = above;
+                }
+            }
+        }
+        if self.root_node == node {
+            self.root_node = above;
+        }
+        self[node].input = Some(above_input);
+        self[above].input = Some(node);
+        let result = self
+            .check_acyclic()
+            .and_then(|()| self.check_arity(node))
+            .and_then(|()| self.check_arity(above));
+        if let Err(err) = result {
+            for (id, input, arguments) in saved {
+                self[id].input = input;
+                self[id].arguments = arguments;
+            }
+            self[above].input = Some(above_input);
+            self[node].input = Some(above);
+            self.root_node = saved_root;
+            return Err(err.into());
+        }
+        self.mark_dirty(node);
+        Ok(())
+    }
+
+    /// Swaps node with the node whose input it is, so that its operation is
+    /// applied later.
+    pub fn move_down(&mut self, node: NodeId) -> Result<(), EditError> {
+        let below = self
+            .consumers(node)
+            .into_iter()
+            .filter(|&consumer| self[consumer].input == Some(node))
+            .collect::<Vec<_>>();
+        match &below[..] {
+            [] => Err(EditError::NothingBelow(node)),
+            &[below] => self.move_up(below),
+            _ => Err(EditError::SharedNode(node)),
+        }
+    }
+
+    /// Makes node use with for one of its arguments.
+    pub fn replace_argument(
+        &mut self,
+        node: NodeId,
+        index: usize,
+        with: NodeId,
+    ) -> Result<(), EditError> {
+        let Some(&old) = self[node].arguments.get(index) else {
+            return Err(EditError::NoSuchArgument(node, index));
+        };
+        self.check_new_dependency(node, with)?;
+        if self.output_layout(with) != self.output_layout(old) {
+            return Err(EditError::TypeMismatch);
+        }
+        self[node].arguments[index] = with;
+        self.mark_dirty(node);
+        Ok(())
+    }
+
+    /// Changes the value of a literal node to one that may have a different
+    /// layout. Values with the same layout go through set_literal_value.
+    /// Otherwise the literal is replaced by a new node, which is returned,
+    /// after checking that everything using it can take the new type. Those
+    /// nodes are then compiled again.
+    pub fn replace_literal(&mut self, node: NodeId, value: TypedBlob) -> Result<NodeId, EditError> {
+        let NodeOperation::Literal(old_value) = &self[node].operation else {
+            return Err(EditError::NotALiteral(node));
+        };
+        if value.layout() == old_value.layout() {
+            return self.set_literal_value(node, value).map(|()| node);
+        }
+        let replacement = self.push_literal_node(value);
+        // Literals use nothing, so this cannot create a cycle.
+        self.replace_references(node, replacement)?;
+        let users = self.dependents(replacement);
+        let mut checked = HashSet::new();
+        let result = users
+            .iter()
+            .try_for_each(|&user| self.check_types_from(user, &users, &mut checked))
+            .map_err(EditError::Invalid)
+            .and_then(|()| {
+                self.tools
+                    .values()
+                    .try_for_each(|tool| self.validate_tool(tool))
+                    .map_err(|_| EditError::TypeMismatch)
+            });
+        if let Err(err) = result {
+            self.replace_references(replacement, node)?;
+            self.nodes.remove(&replacement);
+            return Err(err);
+        }
+        self.mark_dirty(replacement);
+        Ok(replacement)
+    }
+}
@@ -0,0 +1,71 @@
+use std::collections::HashSet;
+
+use super::{BuiltinDefinitions, CodeGenerationContext, Engine, FunctionKind, NodeId};
+
+impl BuiltinDefinitions {
+    /// Every node the builtin definitions refer to. These have to be kept
+    /// alive even when nothing in the graph uses them yet.
+    pub fn nodes(&self) -> Vec<NodeId> {
+        let inputs = &self.tool_inputs;
+        vec![
+            self.compose_vector_2d,
+            self.compose_integer_vector_2d,
+            self.display_position.1,
+            inputs.mouse_offset.1,
+            inputs.mouse_steps.1,
+            inputs.drag_offset.1,
+            inputs.scroll_offset.1,
+            inputs.cursor_position.1,
+            inputs.modifiers.1,
+        ]
+    }
+}
+
+impl CodeGenerationContext {
+    /// Drops everything compiled for the node. The JIT module cannot free
+    /// individual functions, so their code stays allocated, but they will
+    /// never be looked up again since node IDs are not reused.
+    fn forget_node(&mut self, node: NodeId) {
+        for function in [
+            FunctionKind::InternalImplementation(node),
+            FunctionKind::ExternalWrapper(node),
+        ] {
+            self.functions.remove(&function);
+            self.undefined_functions.remove(&function);
+            self.previously_defined_functions.remove(&function);
+        }
+        self.constants.remove(&node);
+    }
+}
+
+impl Engine {
+    /// Removes every node that cannot be reached from the root node, the
+    /// tools, or the given nodes, along with anything compiled for them.
+    /// Custom nodes keep their definitions alive. Returns how many nodes were
+    /// removed.
+    pub fn collect_garbage(&mut self, keep: impl IntoIterator<Item = NodeId>) -> usize {
+        let mut stack: Vec<_> = keep.into_iter().collect();
+        stack.push(self.root_node);
+        for tool in self.tools.values() {
+            stack.extend(tool.nodes());
+        }
+        let mut reachable = HashSet::new();
+        while let Some(node) = stack.pop() {
+            if reachable.insert(node) {
+                stack.extend(self[node].dependencies());
+            }
+        }
+        let garbage: Vec<_> = self
+            .nodes
+            .keys()
+            .filter(|node| !reachable.contains(node))
+            .copied()
+            .collect();
+        for &node in &garbage {
+            self.nodes.remove(&node);
+            self.context.forget_node(node);
+            self.results.forget(node);
+        }
+        garbage.len()
+    }
+}
@@ -0,0 +1,763 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt::{self, Display, Formatter},
+};
+
+use serde_json::{json, Value};
+
+use super::{
+    BasicOp, Blob, BlobLayout, BuiltinDefinitions, Engine, Node, NodeId, NodeOperation,
+    ParameterId, Tool, ToolEvent, TypedBlob,
+};
+
+/// Increased whenever the schema changes in a way that older readers would
+/// misunderstand. Files with a newer version are rejected.
+pub const JSON_FORMAT_VERSION: u64 = 2;
+const GRAPH_FORMAT: &str = "totem-graph";
+const LIBRARY_FORMAT: &str = "totem-library";
+/// The members of a tool that name its handler for each event.
+const TOOL_HANDLER_KEYS: [(ToolEvent, &str); 4] = [
+    (ToolEvent::MousePress, "mouse_press"),
+    (ToolEvent::MouseDrag, "mouse_drag"),
+    (ToolEvent::MouseRelease, "mouse_release"),
+    (ToolEvent::Scroll, "scroll"),
+];
+
+#[derive(Clone, Debug)]
+pub struct JsonError {
+    /// Where in the document the problem is, such as `nodes[2].operation`.
+    pub path: String,
+    pub message: String,
+}
+
+impl Display for JsonError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+/// A value inside a document being imported, along with where it is.
+struct Field<'a> {
+    value: &'a Value,
+    path: String,
+}
+
+impl<'a> Field<'a> {
+    fn error<T>(&self, message: String) -> Result<T, JsonError> {
+        Err(JsonError {
+            path: self.path.clone(),
+            message,
+        })
+    }
+
+    fn child(&self, value: &'a Value, key: &str) -> Field<'a> {
+        let path = if self.path.is_empty() {
+            key.to_owned()
+        } else {
+            format!("{}.{}", self.path, key)
+        };
+        Field { value, path }
+    }
+
+    /// A member that can be missing or null.
+    fn optional(&self, key: &str) -> Result<Option<Field<'a>>, JsonError> {
+        let Some(object) = self.value.as_object() else {
+            return self.error(format!("Expected an object, found {}", self.value));
+        };
+        Ok(object
+            .get(key)
+            .filter(|value| !value.is_null())
+            .map(|value| self.child(value, key)))
+    }
+
+    fn get(&self, key: &str) -> Result<Field<'a>, JsonError> {
+        match self.optional(key)? {
+            Some(field) => Ok(field),
+            None => self.error(format!("Missing \"{}\"", key)),
+        }
+    }
+
+    fn str(&self) -> Result<&'a str, JsonError> {
+        match self.value.as_str() {
+            Some(text) => Ok(text),
+            None => self.error(format!("Expected a string, found {}", self.value)),
+        }
+    }
+
+    fn u64(&self) -> Result<u64, JsonError> {
+        match self.value.as_u64() {
+            Some(number) => Ok(number),
+            None => self.error(format!("Expected a whole number, found {}", self.value)),
+        }
+    }
+
+    fn u32(&self) -> Result<u32, JsonError> {
+        match self.u64()?.try_into() {
+            Ok(number) => Ok(number),
+            Err(_) => self.error(format!("{} is too large", self.value)),
+        }
+    }
+
+    fn array(&self) -> Result<Vec<Field<'a>>, JsonError> {
+        match self.value.as_array() {
+            Some(items) => Ok(items
+                .iter()
+                .enumerate()
+                .map(|(index, value)| Field {
+                    value,
+                    path: format!("{}[{}]", self.path, index),
+                })
+                .collect()),
+            None => self.error(format!("Expected an array, found {}", self.value)),
+        }
+    }
+
+    fn strings(&self) -> Result<Vec<String>, JsonError> {
+        self.array()?
+            .iter()
+            .map(|item| item.str().map(str::to_owned))
+            .collect()
+    }
+}
+
+fn layout_json(layout: &BlobLayout) -> Value {
+    let kind = layout.kind_name();
+    match layout {
+        BlobLayout::Float | BlobLayout::Integer | BlobLayout::Byte => json!(kind),
+        BlobLayout::FixedIndex(length, element) => json!({
+            "type": kind,
+            "length": length,
+            "element": layout_json(element),
+        }),
+        BlobLayout::DynamicIndex(element) | BlobLayout::DynamicMap(element) => json!({
+            "type": kind,
+            "element": layout_json(element),
+        }),
+        BlobLayout::FixedHeterogeneousMap(keys, components) => json!({
+            "type": kind,
+            "keys": value_json(keys),
+            "components": components.iter().map(layout_json).collect::<Vec<_>>(),
+        }),
+        BlobLayout::FixedHomogeneousMap(keys, length, element) => json!({
+            "type": kind,
+            "keys": value_json(keys),
+            "length": length,
+            "element": layout_json(element),
+        }),
+    }
+}
+
+fn parse_layout(field: &Field) -> Result<BlobLayout, JsonError> {
+    if let Some(name) = field.value.as_str() {
+        return match BlobLayout::scalar_from_kind_name(name) {
+            Some(layout) => Ok(layout),
+            None => field.error(format!("Unknown type \"{}\"", name)),
+        };
+    }
+    let element = || parse_layout(&field.get("element")?).map(Box::new);
+    let kind = field.get("type")?;
+    let layout = match kind.str()? {
+        "fixed_index" => BlobLayout::FixedIndex(field.get("length")?.u32()?, element()?),
+        "dynamic_index" => BlobLayout::DynamicIndex(element()?),
+        "fixed_heterogeneous_map" => BlobLayout::FixedHeterogeneousMap(
+            Box::new(parse_value(&field.get("keys")?)?),
+            field
+                .get("components")?
+                .array()?
+                .iter()
+                .map(parse_layout)
+                .collect::<Result<_, _>>()?,
+        ),
+        "fixed_homogeneous_map" => BlobLayout::FixedHomogeneousMap(
+            Box::new(parse_value(&field.get("keys")?)?),
+            field.get("length")?.u32()?,
+            element()?,
+        ),
+        "dynamic_map" => BlobLayout::DynamicMap(element()?),
+        other => return kind.error(format!("Unknown type \"{}\"", other)),
+    };
+    match layout.check_supported() {
+        Ok(()) => Ok(layout),
+        Err(message) => field.error(message),
+    }
+}
+
+/// Values are written as plain JSON where the engine can take them apart,
+/// and as raw bytes otherwise.
+fn value_json(value: &TypedBlob) -> Value {
+    match plain_value_json(value) {
+        Some(plain) => json!({ "layout": layout_json(value.layout()), "value": plain }),
+        None => {
+            let (layout, blob) = value.raw_parts();
+            json!({ "layout": layout_json(layout), "raw": blob_json(blob) })
+        }
+    }
+}
+
+fn plain_value_json(value: &TypedBlob) -> Option<Value> {
+    let view = value.view();
+    match value.layout() {
+        BlobLayout::Float => view
+            .as_f32()
+            .ok()
+            .filter(|value| value.is_finite())
+            .map(Value::from),
+        BlobLayout::Integer => view.as_i32().ok().map(Value::from),
+        BlobLayout::DynamicIndex(element) if **element == BlobLayout::Byte => {
+            view.as_string().ok().map(Value::from)
+        }
+        BlobLayout::FixedIndex(length, _) => (0..*length as i32)
+            .map(|index| plain_value_json(&view.index(&index.into()).to_owned()))
+            .collect::<Option<Vec<_>>>()
+            .map(Value::from),
+        BlobLayout::FixedHeterogeneousMap(keys, _) => (0..keys.view().len()? as i32)
+            .map(|index| {
+                let key = keys.view().index(&index.into()).to_owned();
+                plain_value_json(&view.index(&key).to_owned())
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(Value::from),
+        _ => None,
+    }
+}
+
+fn blob_json(blob: &Blob) -> Value {
+    json!({
+        "bytes": blob.hex(),
+        "dynamic_components": blob.dynamic_components().iter().map(blob_json).collect::<Vec<_>>(),
+    })
+}
+
+fn parse_value(field: &Field) -> Result<TypedBlob, JsonError> {
+    let layout = parse_layout(&field.get("layout")?)?;
+    let value = match field.optional("raw")? {
+        Some(raw) => match TypedBlob::from_raw_parts(layout.clone(), parse_blob(&raw)?) {
+            Ok(value) => value,
+            Err(message) => return raw.error(message),
+        },
+        None => parse_plain_value(&field.get("value")?, &layout)?,
+    };
+    if value.layout() != &layout {
+        return field.error(format!(
+            "The value has type {:?}, which does not match its layout",
+            value.layout()
+        ));
+    }
+    Ok(value)
+}
+
+fn parse_plain_value(field: &Field, layout: &BlobLayout) -> Result<TypedBlob, JsonError> {
+    let items = |expected: usize| {
+        let items = field.array()?;
+        if items.len() != expected || expected == 0 {
+            return field.error(format!(
+                "Expected {} values, found {}",
+                expected,
+                items.len()
+            ));
+        }
+        Ok(items)
+    };
+    match layout {
+        BlobLayout::Float => match field.value.as_f64() {
+            Some(value) => Ok((value as f32).into()),
+            None => field.error(format!("Expected a number, found {}", field.value)),
+        },
+        BlobLayout::Integer => match field.value.as_i64().and_then(|value| value.try_into().ok()) {
+            Some(value) => Ok(i32::into(value)),
+            None => field.error(format!("Expected a 32 bit integer, found {}", field.value)),
+        },
+        BlobLayout::DynamicIndex(element) if **element == BlobLayout::Byte => {
+            Ok(field.str()?.to_owned().into())
+        }
+        BlobLayout::FixedIndex(length, element) => Ok(TypedBlob::fixed_array(
+            items(*length as usize)?
+                .iter()
+                .map(|item| parse_plain_value(item, element))
+                .collect::<Result<_, _>>()?,
+        )),
+        BlobLayout::FixedHeterogeneousMap(keys, components) => {
+            let values = items(components.len())?;
+            let mut entries = Vec::new();
+            for (index, (item, component)) in values.iter().zip(components).enumerate() {
+                let key = keys.view().index(&(index as i32).into()).to_owned();
+                entries.push((key, parse_plain_value(item, component)?));
+            }
+            Ok(TypedBlob::fixed_heterogeneous_map(entries))
+        }
+        _ => field.error(format!(
+            "Values of type {:?} have to be given as \"raw\"",
+            layout
+        )),
+    }
+}
+
+fn parse_blob(field: &Field) -> Result<Blob, JsonError> {
+    let hex = field.get("bytes")?;
+    let components = match field.optional("dynamic_components")? {
+        Some(components) => components
+            .array()?
+            .iter()
+            .map(parse_blob)
+            .collect::<Result<_, _>>()?,
+        None => Vec::new(),
+    };
+    match Blob::from_hex(hex.str()?, components) {
+        Ok(blob) => Ok(blob),
+        Err(message) => hex.error(message),
+    }
+}
+
+impl Engine {
+    /// Writes node and everything it depends on as a JSON graph document,
+    /// along with the tools the user has made.
+    pub fn export_json(&self, root: NodeId, builtins: &BuiltinDefinitions) -> String {
+        let (nodes, tools, indices) = self.nodes_json(root, builtins);
+        let document = json!({
+            "format": GRAPH_FORMAT,
+            "version": JSON_FORMAT_VERSION,
+            "root": indices[&root],
+            "nodes": nodes,
+            "tools": tools,
+        });
+        serde_json::to_string_pretty(&document).unwrap()
+    }
+
+    /// Writes the definition of a custom node as a standalone library
+    /// document, along with the tools the user has made. Takes either a
+    /// custom node or its definition.
+    pub fn export_library_json(
+        &self,
+        node: NodeId,
+        name: &str,
+        builtins: &BuiltinDefinitions,
+    ) -> String {
+        let definition = match &self[node].operation {
+            NodeOperation::CustomNode { result, .. } => *result,
+            _ => node,
+        };
+        let (nodes, tools, indices) = self.nodes_json(definition, builtins);
+        let document = json!({
+            "format": LIBRARY_FORMAT,
+            "version": JSON_FORMAT_VERSION,
+            "name": name,
+            "definition": indices[&definition],
+            "nodes": nodes,
+            "tools": tools,
+        });
+        serde_json::to_string_pretty(&document).unwrap()
+    }
+
+    /// Builtin definitions are written by name instead of being copied, so
+    /// that the app still fills in the parameters they read after importing.
+    fn nodes_json(
+        &self,
+        root: NodeId,
+        builtins: &BuiltinDefinitions,
+    ) -> (Vec<Value>, Vec<Value>, HashMap<NodeId, usize>) {
+        let builtin_names: HashMap<_, _> = builtins
+            .script_names()
+            .into_iter()
+            .map(|(name, node)| (node, name))
+            .collect();
+        let tools = self
+            .tools()
+            .filter(|(id, _)| !builtins.tools.contains(id))
+            .map(|(_, tool)| tool)
+            .collect::<Vec<_>>();
+        let mut order = Vec::new();
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::from([root]);
+        queue.extend(tools.iter().flat_map(|tool| tool.nodes()));
+        while let Some(node) = queue.pop_front() {
+            if visited.insert(node) {
+                order.push(node);
+                if !builtin_names.contains_key(&node) {
+                    queue.extend(self[node].dependencies());
+                }
+            }
+        }
+        let indices: HashMap<_, _> = order
+            .iter()
+            .enumerate()
+            .map(|(index, &node)| (node, index))
+            .collect();
+        let mut parameters = HashMap::new();
+        let mut parameter = |id: ParameterId| {
+            let next = parameters.len();
+            *parameters.entry(id).or_insert(next)
+        };
+        let nodes = order
+            .iter()
+            .map(|id| {
+                if let Some(name) = builtin_names.get(id) {
+                    return json!({ "id": indices[id], "builtin": name });
+                }
+                let node = &self[*id];
+                let operation = match &node.operation {
+                    NodeOperation::Literal(value) => {
+                        json!({ "type": "literal", "value": value_json(value) })
+                    }
+                    NodeOperation::Parameter(id) => {
+                        json!({ "type": "parameter", "parameter": parameter(*id) })
+                    }
+                    NodeOperation::Basic(op) => json!({ "type": "basic", "operation": op.name() }),
+                    NodeOperation::ComposeStruct(name, components) => json!({
+                        "type": "compose_struct",
+                        "name": name,
+                        "components": components,
+                    }),
+                    NodeOperation::GetComponent(component) => {
+                        json!({ "type": "get_component", "component": component })
+                    }
+                    NodeOperation::CustomNode { result, input } => json!({
+                        "type": "custom_node",
+                        "result": indices[result],
+                        "input": input.map(&mut parameter),
+                    }),
+                };
+                json!({
+                    "id": indices[id],
+                    "operation": operation,
+                    "input": node.input.map(|input| indices[&input]),
+                    "arguments": node.arguments.iter().map(|argument| indices[argument]).collect::<Vec<_>>(),
+                })
+            })
+            .collect();
+        let tools = tools
+            .iter()
+            .map(|tool| {
+                let mut handlers = serde_json::Map::new();
+                for (event, key) in TOOL_HANDLER_KEYS {
+                    if let Some(handler) = tool.handler(event) {
+                        handlers.insert(key.to_owned(), json!(indices[&handler]));
+                    }
+                }
+                json!({
+                    "name": tool.name,
+                    "icon": tool.icon,
+                    "target_prototype": indices[&tool.target_prototype],
+                    "handlers": handlers,
+                })
+            })
+            .collect();
+        (nodes, tools, indices)
+    }
+
+    /// Adds the nodes and tools of a graph or library document to this
+    /// engine, giving them and their parameters new IDs. Returns the root of
+    /// a graph or the definition in a library.
+    pub fn import_json(
+        &mut self,
+        text: &str,
+        builtins: &BuiltinDefinitions,
+    ) -> Result<NodeId, JsonError> {
+        let value: Value = serde_json::from_str(text).map_err(|err| JsonError {
+            path: String::new(),
+            message: err.to_string(),
+        })?;
+        let document = Field {
+            value: &value,
+            path: String::new(),
+        };
+        let version_field = document.get("version")?;
+        let version = version_field.u64()?;
+        if version > JSON_FORMAT_VERSION {
+            return version_field.error(format!(
+                "The document was written for version {}, but only versions up to {} can be read",
+                version, JSON_FORMAT_VERSION
+            ));
+        }
+        let format = document.get("format")?;
+        let entry = match format.str()? {
+            GRAPH_FORMAT => document.get("root")?,
+            LIBRARY_FORMAT => {
+                document.get("name")?.str()?;
+                document.get("definition")?
+            }
+            other => return format.error(format!("Unknown format \"{}\"", other)),
+        };
+
+        let nodes = document.get("nodes")?.array()?;
+        let builtin_names = builtins.script_names();
+        let mut ids = HashMap::new();
+        let mut paths = HashMap::new();
+        for node in &nodes {
+            let id = node.get("id")?;
+            let new_id = match node.optional("builtin")? {
+                Some(name) => match builtin_names.get(name.str()?) {
+                    Some(&builtin) => builtin,
+                    None => return name.error(format!("There is no builtin named {}", name.value)),
+                },
+                None => self.node_ids.next(),
+            };
+            if ids.insert(id.u64()?, new_id).is_some() {
+                return id.error(format!("There is more than one node {}", id.value));
+            }
+            paths.insert(new_id, node.path.clone());
+        }
+        let node_id = |field: &Field| match ids.get(&field.u64()?) {
+            Some(&id) => Ok(id),
+            None => field.error(format!("There is no node {}", field.value)),
+        };
+        let mut parameters = HashMap::new();
+        let parameter_ids = &mut self.parameter_ids;
+        let mut parameter = |field: &Field| {
+            let index = field.u64()?;
+            Ok::<_, JsonError>(
+                *parameters
+                    .entry(index)
+                    .or_insert_with(|| parameter_ids.next()),
+            )
+        };
+        let mut new_nodes = Vec::new();
+        for node in &nodes {
+            if node.optional("builtin")?.is_some() {
+                continue;
+            }
+            let operation = node.get("operation")?;
+            let kind = operation.get("type")?;
+            let operation = match kind.str()? {
+                "literal" => NodeOperation::Literal(parse_value(&operation.get("value")?)?),
+                "parameter" => NodeOperation::Parameter(parameter(&operation.get("parameter")?)?),
+                "basic" => {
+                    let op = operation.get("operation")?;
+                    match BasicOp::from_name(op.str()?) {
+                        Some(op) => NodeOperation::Basic(op),
+                        None => return op.error(format!("Unknown operation \"{}\"", op.str()?)),
+                    }
+                }
+                "compose_struct" => NodeOperation::ComposeStruct(
+                    operation.get("name")?.str()?.to_owned(),
+                    operation.get("components")?.strings()?,
+                ),
+                "get_component" => {
+                    NodeOperation::GetComponent(operation.get("component")?.str()?.to_owned())
+                }
+                "custom_node" => NodeOperation::CustomNode {
+                    result: node_id(&operation.get("result")?)?,
+                    input: match operation.optional("input")? {
+                        Some(input) => Some(parameter(&input)?),
+                        None => None,
+                    },
+                },
+                other => return kind.error(format!("Unknown operation \"{}\"", other)),
+            };
+            let id = node_id(&node.get("id")?)?;
+            let input = match node.optional("input")? {
+                Some(input) => Some(node_id(&input)?),
+                None => None,
+            };
+            let arguments = match node.optional("arguments")? {
+                Some(arguments) => arguments
+                    .array()?
+                    .iter()
+                    .map(node_id)
+                    .collect::<Result<_, _>>()?,
+                None => Vec::new(),
+            };
+            new_nodes.push((
+                id,
+                Node {
+                    operation,
+                    input,
+                    arguments,
+                },
+            ));
+        }
+        let mut tools = Vec::new();
+        if let Some(tool_fields) = document.optional("tools")? {
+            for field in tool_fields.array()? {
+                let mut tool = Tool {
+                    name: field.get("name")?.str()?.to_owned(),
+                    icon: field.get("icon")?.str()?.to_owned(),
+                    target_prototype: node_id(&field.get("target_prototype")?)?,
+                    mouse_drag_handler: None,
+                    mouse_press_handler: None,
+                    mouse_release_handler: None,
+                    scroll_handler: None,
+                };
+                let handlers = field.get("handlers")?;
+                for (event, key) in TOOL_HANDLER_KEYS {
+                    if let Some(handler) = handlers.optional(key)? {
+                        *tool.handler_mut(event) = Some(node_id(&handler)?);
+                    }
+                }
+                tools.push((field.path, tool));
+            }
+        }
+        let entry = node_id(&entry)?;
+        self.insert_nodes(new_nodes).map_err(|err| JsonError {
+            path: match err.node().and_then(|node| paths.get(&node)) {
+                Some(path) => path.clone(),
+                None => "nodes".to_owned(),
+            },
+            message: err.to_string(),
+        })?;
+        // Tools can only be checked once their nodes exist. Nodes left behind
+        // by a tool that turns out to be invalid are collected as garbage.
+        for (path, tool) in &tools {
+            if let Err(err) = self.validate_tool(tool) {
+                return Err(JsonError {
+                    path: path.clone(),
+                    message: err.to_string(),
+                });
+            }
+        }
+        for (_, tool) in tools {
+            // Documents carry every tool the user had, so importing several
+            // from the same session would otherwise add copies of them.
+            if !self.has_tool_like(&tool) {
+                // Every tool was checked above, so this cannot fail.
+                let _ = self.add_tool(tool);
+            }
+        }
+        Ok(entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(nodes: Value) -> String {
+        json!({
+            "format": GRAPH_FORMAT,
+            "version": JSON_FORMAT_VERSION,
+            "root": 0,
+            "nodes": nodes,
+        })
+        .to_string()
+    }
+
+    fn literal(id: u64, layout: Value, value: Value) -> Value {
+        json!({
+            "id": id,
+            "operation": { "type": "literal", "value": { "layout": layout, "value": value } },
+        })
+    }
+
+    #[test]
+    fn exported_graphs_import_again() {
+        let (mut engine, builtins) = Engine::new();
+        let left = engine.push_literal_node(TypedBlob::from(1.5));
+        let right = engine.push_literal_node(TypedBlob::from(format!("text")));
+        let name = engine.push_literal_node(TypedBlob::from(format!("name")));
+        let parameter = engine.push_parameter(name, left).1;
+        let node = Node {
+            operation: NodeOperation::ComposeStruct(
+                format!("Pair"),
+                vec![format!("a"), format!("b")],
+            ),
+            input: None,
+            arguments: vec![parameter, right],
+        };
+        let root = engine.try_push_node(node).unwrap();
+        let exported = engine.export_json(root, &builtins);
+        let imported = engine.import_json(&exported, &builtins).unwrap();
+        assert_ne!(imported, root);
+        assert_eq!(engine.export_json(imported, &builtins), exported);
+        assert!(engine.validate().is_ok());
+    }
+
+    #[test]
+    fn importing_twice_does_not_copy_tools() {
+        let (mut engine, builtins) = Engine::new();
+        let script = r#"target = parameter("SPECIAL TOOL TARGET Value", 1.0)
+prototype = target * 2.0
+handler = target + get(mouse_offset, "X")
+"#;
+        let mut names = builtins.script_names();
+        engine.run_script(script, &mut names).unwrap();
+        let tool = engine.create_tool(
+            format!("Double"),
+            format!("pound"),
+            names["prototype"],
+            names["handler"],
+        );
+        assert!(tool.is_ok());
+        let exported = engine.export_json(names["prototype"], &builtins);
+        let count = engine.tools().count();
+        engine.import_json(&exported, &builtins).unwrap();
+        engine.import_json(&exported, &builtins).unwrap();
+        assert_eq!(engine.tools().count(), count);
+
+        let (mut other, builtins) = Engine::new();
+        let count = other.tools().count();
+        other.import_json(&exported, &builtins).unwrap();
+        other.import_json(&exported, &builtins).unwrap();
+        assert_eq!(other.tools().count(), count + 1);
+        let renamed = exported.replace("\"Double\"", "\"Triple\"");
+        other.import_json(&renamed, &builtins).unwrap();
+        assert_eq!(other.tools().count(), count + 2);
+    }
+
+    #[test]
+    fn import_rejects_values_the_engine_cannot_take_apart() {
+        let (mut engine, builtins) = Engine::new();
+        let scalar_keys = json!({
+            "type": "fixed_heterogeneous_map",
+            "keys": { "layout": "integer", "value": 1 },
+            "components": ["float"],
+        });
+        let too_few_keys = json!({
+            "type": "fixed_heterogeneous_map",
+            "keys": {
+                "layout": { "type": "fixed_index", "length": 1, "element": "integer" },
+                "value": [1],
+            },
+            "components": ["float", "float"],
+        });
+        let dynamic = json!({ "type": "dynamic_index", "element": "float" });
+        for (layout, value) in [
+            (scalar_keys, json!([1.0])),
+            (too_few_keys, json!([1.0, 2.0])),
+            (dynamic.clone(), json!([1.0])),
+        ] {
+            let document = graph(json!([literal(0, layout, value)]));
+            let err = engine.import_json(&document, &builtins).unwrap_err();
+            assert!(err.path.starts_with("nodes[0]"), "{}", err);
+        }
+        let raw = json!([{
+            "id": 0,
+            "operation": {
+                "type": "literal",
+                "value": { "layout": dynamic, "raw": { "bytes": "0000803f" } },
+            },
+        }]);
+        assert!(engine.import_json(&graph(raw), &builtins).is_err());
+        assert!(engine.validate().is_ok());
+    }
+
+    #[test]
+    fn import_type_checks_nodes() {
+        let (mut engine, builtins) = Engine::new();
+        let document = graph(json!([
+            {
+                "id": 0,
+                "operation": { "type": "basic", "operation": "Add" },
+                "input": 1,
+                "arguments": [2],
+            },
+            literal(1, json!("float"), json!(1.0)),
+            literal(2, json!({ "type": "dynamic_index", "element": "byte" }), json!("text")),
+        ]));
+        let err = engine.import_json(&document, &builtins).unwrap_err();
+        assert_eq!(err.path, "nodes[0]", "{}", err);
+        let document = graph(json!([
+            {
+                "id": 0,
+                "operation": { "type": "get_component", "component": "missing" },
+                "input": 1,
+            },
+            literal(1, json!("float"), json!(1.0)),
+        ]));
+        let err = engine.import_json(&document, &builtins).unwrap_err();
+        assert_eq!(err.path, "nodes[0]", "{}", err);
+        assert!(engine.validate().is_ok());
+    }
+}
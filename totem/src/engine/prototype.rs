@@ -0,0 +1,342 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    fmt::{self, Display, Formatter},
+};
+
+use super::{BlobLayout, Engine, Node, NodeId, NodeOperation, ParameterId, ToolParameterKind};
+
+/// Where a prototype was found in the graph.
+#[derive(Clone, Debug)]
+pub struct PrototypeMatch {
+    /// The literal each tool target matched, in the order they appear in the
+    /// prototype.
+    pub targets: Vec<(ParameterId, NodeId)>,
+    /// How many connections away the match is from the node the search
+    /// started at.
+    pub distance: usize,
+}
+
+#[derive(Clone, Debug)]
+pub enum MatchError {
+    OperationMismatch {
+        expected: String,
+        found: String,
+    },
+    /// Counts the input as well as the arguments.
+    ArityMismatch {
+        operation: String,
+        expected: usize,
+        found: usize,
+    },
+    /// Tool targets only match literals, since those are what tools edit.
+    TargetNotLiteral {
+        target: String,
+        found: String,
+    },
+    TypeMismatch {
+        parameter: String,
+        expected: BlobLayout,
+        found: BlobLayout,
+    },
+    /// A parameter that appears several times in the prototype would have to
+    /// stand in for different nodes.
+    InconsistentBinding(String),
+}
+
+impl Display for MatchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OperationMismatch { expected, found } => {
+                write!(f, "Expected {} node, found {} node", expected, found)
+            }
+            Self::ArityMismatch {
+                operation,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Expected {} to have {} operands, found {}",
+                operation, expected, found
+            ),
+            Self::TargetNotLiteral { target, found } => write!(
+                f,
+                "The target \"{}\" can only edit literals, found {} node",
+                target, found
+            ),
+            Self::TypeMismatch {
+                parameter,
+                expected,
+                found,
+            } => write!(
+                f,
+                "\"{}\" expects a value of type {:?}, found {:?}",
+                parameter, expected, found
+            ),
+            Self::InconsistentBinding(parameter) => write!(
+                f,
+                "\"{}\" would have to stand in for more than one node",
+                parameter
+            ),
+        }
+    }
+}
+
+/// What the special parameters of a prototype have been matched to so far.
+#[derive(Clone, Default)]
+struct Bindings {
+    targets: Vec<(ParameterId, NodeId)>,
+    wildcard_input: Option<NodeId>,
+}
+
+impl Engine {
+    /// Checks whether the graph starting at node has the same shape as the
+    /// prototype. Tool targets match literals of the same type, the wildcard
+    /// input matches any node of the same type, and the operands of
+    /// commutative operations may be in either order.
+    pub fn match_prototype(
+        &self,
+        prototype: NodeId,
+        node: NodeId,
+    ) -> Result<PrototypeMatch, MatchError> {
+        let bindings = self.match_prototype_into(prototype, node, Bindings::default())?;
+        Ok(PrototypeMatch {
+            targets: bindings.targets,
+            distance: 0,
+        })
+    }
+
+    /// Looks for the prototype at the given node and every node it depends
+    /// on. Matches are sorted so that the ones closest to the given node come
+    /// first. If there are none, returns why the given node did not match.
+    pub fn find_prototype_matches(
+        &self,
+        prototype: NodeId,
+        near: NodeId,
+    ) -> Result<Vec<PrototypeMatch>, MatchError> {
+        let mut matches = Vec::new();
+        let mut closest_error = None;
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::from([(near, 0)]);
+        while let Some((node, distance)) = queue.pop_front() {
+            if !visited.insert(node) {
+                continue;
+            }
+            match self.match_prototype(prototype, node) {
+                Ok(found) => matches.push(PrototypeMatch { distance, ..found }),
+                Err(err) => {
+                    closest_error.get_or_insert(err);
+                }
+            }
+            let node = &self[node];
+            // The only argument of a parameter is its name.
+            if let NodeOperation::Parameter(..) = &node.operation {
+                queue.extend(node.input.map(|input| (input, distance + 1)));
+            } else {
+                let dependencies = node.input.iter().chain(node.arguments.iter());
+                queue.extend(dependencies.map(|&dependency| (dependency, distance + 1)));
+            }
+        }
+        matches.sort_by_key(|found| found.distance);
+        match closest_error {
+            Some(err) if matches.is_empty() => Err(err),
+            _ => Ok(matches),
+        }
+    }
+
+    fn match_prototype_into(
+        &self,
+        prototype_id: NodeId,
+        node_id: NodeId,
+        mut bindings: Bindings,
+    ) -> Result<Bindings, MatchError> {
+        let prototype = &self[prototype_id];
+        let node = &self[node_id];
+        if let &NodeOperation::Parameter(param_id) = &prototype.operation {
+            let name = self[prototype.arguments[0]].as_literal().view();
+            let name = name.as_string().unwrap_or("");
+            let kind = ToolParameterKind::of_name(name);
+            if kind != ToolParameterKind::Ordinary {
+                let expected = self.output_layout(prototype.input.unwrap());
+                let found = self.output_layout(node_id);
+                if expected != found {
+                    return Err(MatchError::TypeMismatch {
+                        parameter: ToolParameterKind::strip(name).to_owned(),
+                        expected,
+                        found,
+                    });
+                }
+            }
+            match kind {
+                ToolParameterKind::Target => {
+                    let NodeOperation::Literal(..) = &node.operation else {
+                        return Err(MatchError::TargetNotLiteral {
+                            target: ToolParameterKind::strip(name).to_owned(),
+                            found: node.operation.name(),
+                        });
+                    };
+                    let previous = bindings.targets.iter().find(|(id, _)| *id == param_id);
+                    match previous {
+                        Some(&(_, bound)) if bound != node_id => {
+                            return Err(MatchError::InconsistentBinding(
+                                ToolParameterKind::strip(name).to_owned(),
+                            ))
+                        }
+                        Some(..) => (),
+                        None => bindings.targets.push((param_id, node_id)),
+                    }
+                    return Ok(bindings);
+                }
+                ToolParameterKind::WildcardInput => {
+                    if bindings.wildcard_input.replace(node_id).unwrap_or(node_id) != node_id {
+                        return Err(MatchError::InconsistentBinding(format!("Wildcard Input")));
+                    }
+                    return Ok(bindings);
+                }
+                ToolParameterKind::Ordinary => (),
+            }
+        }
+
+        if prototype.operation != node.operation {
+            return Err(MatchError::OperationMismatch {
+                expected: prototype.operation.name(),
+                found: node.operation.name(),
+            });
+        }
+        let operands = |node: &Node| {
+            node.input
+                .iter()
+                .chain(node.arguments.iter())
+                .copied()
+                .collect::<Vec<_>>()
+        };
+        let (prototype_operands, node_operands) = (operands(prototype), operands(node));
+        if prototype.input.is_some() != node.input.is_some()
+            || prototype_operands.len() != node_operands.len()
+        {
+            return Err(MatchError::ArityMismatch {
+                operation: prototype.operation.name(),
+                expected: prototype_operands.len(),
+                found: node_operands.len(),
+            });
+        }
+        let match_in_order = |bindings: Bindings, node_operands: &[NodeId]| {
+            prototype_operands
+                .iter()
+                .zip(node_operands)
+                .try_fold(bindings, |bindings, (&prototype, &node)| {
+                    self.match_prototype_into(prototype, node, bindings)
+                })
+        };
+        match (&prototype.operation, &node_operands[..]) {
+            (NodeOperation::Basic(op), &[a, b]) if op.is_commutative() => {
+                match_in_order(bindings.clone(), &[a, b])
+                    .or_else(|err| match_in_order(bindings, &[b, a]).map_err(|_| err))
+            }
+            _ => match_in_order(bindings, &node_operands),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::engine::BasicOp;
+
+    /// Runs a script in an engine where `k` is a tool target and `wildcard`
+    /// is the wildcard input, both floats.
+    fn engine_with(script: &str) -> (Engine, HashMap<String, NodeId>) {
+        let (mut engine, builtins) = Engine::new();
+        let mut names = builtins.script_names();
+        let parameters = r#"k = parameter("SPECIAL TOOL TARGET K", 1.0)
+wildcard = parameter("SPECIAL TOOL WILDCARD INPUT", 0.0)
+"#;
+        engine
+            .run_script(&format!("{}{}", parameters, script), &mut names)
+            .unwrap();
+        (engine, names)
+    }
+
+    fn literal(engine: &Engine, node: NodeId) -> f32 {
+        engine[node].as_literal().view().as_f32().unwrap()
+    }
+
+    #[test]
+    fn commutative_operands_match_in_either_order() {
+        let (engine, names) = engine_with(
+            r#"prototype = wildcard * k
+node = get(display_position, "X") * 2.0
+"#,
+        );
+        let found = engine
+            .match_prototype(names["prototype"], names["node"])
+            .unwrap();
+        let NodeOperation::Parameter(k) = engine[names["k"]].operation else {
+            unreachable!()
+        };
+        assert_eq!(found.targets.len(), 1);
+        assert_eq!(found.targets[0].0, k);
+        assert_eq!(literal(&engine, found.targets[0].1), 2.0);
+    }
+
+    #[test]
+    fn mismatches_are_explained() {
+        let (mut engine, names) = engine_with(
+            r#"prototype = wildcard + k
+integers = 2 + 3
+"#,
+        );
+        let prototype = names["prototype"];
+        assert!(matches!(
+            engine.match_prototype(prototype, names["integers"]),
+            Err(MatchError::TypeMismatch {
+                expected: BlobLayout::Float,
+                found: BlobLayout::Integer,
+                ..
+            })
+        ));
+        // Nodes with the wrong number of operands cannot be built through
+        // the usual methods, but matching should not assume they are valid.
+        let id = engine.node_ids.next();
+        let operand = engine.push_literal_node(1.0.into());
+        engine.nodes.insert(
+            id,
+            Node {
+                operation: NodeOperation::Basic(BasicOp::Add),
+                input: Some(operand),
+                arguments: vec![],
+            },
+        );
+        assert!(matches!(
+            engine.match_prototype(prototype, id),
+            Err(MatchError::ArityMismatch {
+                expected: 2,
+                found: 1,
+                ..
+            })
+        ));
+        assert!(matches!(
+            engine.find_prototype_matches(prototype, id),
+            Err(MatchError::ArityMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn closer_matches_come_first() {
+        let (engine, names) = engine_with(
+            r#"prototype = k * wildcard
+inner = 2.0 * get(display_position, "X")
+outer = 3.0 * (inner + 1.0)
+"#,
+        );
+        let matches = engine
+            .find_prototype_matches(names["prototype"], names["outer"])
+            .unwrap();
+        let found = matches
+            .iter()
+            .map(|found| (found.distance, literal(&engine, found.targets[0].1)))
+            .collect::<Vec<_>>();
+        assert_eq!(found, [(0, 3.0), (2, 2.0)]);
+    }
+}
@@ -0,0 +1,813 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display, Formatter, Write},
+};
+
+use super::{
+    BasicOp, BlobLayout, BuiltinDefinitions, Engine, Node, NodeId, NodeOperation, TypedBlob,
+    ValidationError,
+};
+
+/// The graph the editor starts with.
+pub(super) const DEMO_SCRIPT: &str = r#"root = get(display_position, "Y") / 180.0"#;
+
+/// Scripts describe graphs one assignment per line, such as
+/// `root = get(display_position, "Y") / 180.0`. Assigning to `root` makes
+/// that node the root of the engine.
+#[derive(Clone, Debug)]
+pub struct ScriptError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for ScriptError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.message)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Name(String),
+    Float(f32),
+    /// Wider than the integers of the engine, so that the magnitude of the
+    /// smallest one can be read before it is negated.
+    Integer(i64),
+    Text(String),
+    Symbol(char),
+    Newline,
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Name(name) => write!(f, "{}", name),
+            Self::Float(value) => write!(f, "{:?}", value),
+            Self::Integer(value) => write!(f, "{}", value),
+            Self::Text(text) => write!(f, "{}", quote(text)),
+            Self::Symbol(symbol) => write!(f, "'{}'", symbol),
+            Self::Newline => write!(f, "the end of the line"),
+        }
+    }
+}
+
+fn quote(text: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in text.chars() {
+        match c {
+            '"' | '\\' => quoted.extend(['\\', c]),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn tokenize(script: &str) -> Result<Vec<(usize, Token)>, ScriptError> {
+    let mut tokens = Vec::new();
+    for (index, line) in script.lines().enumerate() {
+        let line_number = index + 1;
+        let error = |message| ScriptError {
+            line: line_number,
+            message,
+        };
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            let token = match c {
+                '#' => break,
+                c if c.is_whitespace() => continue,
+                c if c.is_alphabetic() || c == '_' => {
+                    let mut name = String::from(c);
+                    while let Some(&c) = chars.peek().filter(|c| c.is_alphanumeric() || **c == '_')
+                    {
+                        name.push(c);
+                        chars.next();
+                    }
+                    Token::Name(name)
+                }
+                c if c.is_ascii_digit() => {
+                    let mut number = String::from(c);
+                    let mut is_float = false;
+                    while let Some(&c) = chars.peek() {
+                        if c == '.' || c == 'e' {
+                            is_float = true;
+                        } else if !c.is_ascii_digit() && !(number.ends_with('e') && c == '-') {
+                            break;
+                        }
+                        number.push(c);
+                        chars.next();
+                    }
+                    let invalid = || error(format!("\"{}\" is not a number", number));
+                    if is_float {
+                        Token::Float(number.parse().map_err(|_| invalid())?)
+                    } else {
+                        Token::Integer(number.parse().map_err(|_| invalid())?)
+                    }
+                }
+                '"' => {
+                    let mut text = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('"') => break,
+                            Some('\\') => match chars.next() {
+                                Some('n') => text.push('\n'),
+                                Some('t') => text.push('\t'),
+                                Some(c) => text.push(c),
+                                None => return Err(error(format!("Missing '\"'"))),
+                            },
+                            Some(c) => text.push(c),
+                            None => return Err(error(format!("Missing '\"'"))),
+                        }
+                    }
+                    Token::Text(text)
+                }
+                '(' | ')' | '{' | '}' | ',' | ':' | '=' | '+' | '-' | '*' | '/' => Token::Symbol(c),
+                c => return Err(error(format!("Unexpected '{}'", c))),
+            };
+            tokens.push((line_number, token));
+        }
+        tokens.push((line_number, Token::Newline));
+    }
+    Ok(tokens)
+}
+
+/// The names of the float values that cannot be written with digits.
+fn special_float(name: &str) -> Option<f32> {
+    match name {
+        "inf" => Some(f32::INFINITY),
+        "nan" => Some(f32::NAN),
+        _ => None,
+    }
+}
+
+fn basic_function(name: &str) -> Option<BasicOp> {
+    BasicOp::ALL
+        .iter()
+        .find(|op| op.name().to_lowercase() == name)
+        .cloned()
+}
+
+fn infix_symbol(op: &BasicOp) -> Option<char> {
+    match op {
+        BasicOp::Add => Some('+'),
+        BasicOp::Subtract => Some('-'),
+        BasicOp::Multiply => Some('*'),
+        BasicOp::Divide => Some('/'),
+        _ => None,
+    }
+}
+
+struct ScriptParser<'a> {
+    engine: &'a mut Engine,
+    names: &'a mut HashMap<String, NodeId>,
+    tokens: Vec<(usize, Token)>,
+    position: usize,
+}
+
+impl<'a> ScriptParser<'a> {
+    fn peek(&self) -> &Token {
+        self.tokens
+            .get(self.position)
+            .map_or(&Token::Newline, |(_, token)| token)
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.peek().clone();
+        self.position += 1;
+        token
+    }
+
+    fn error<T>(&self, message: String) -> Result<T, ScriptError> {
+        let line = self
+            .tokens
+            .get(self.position.min(self.tokens.len().saturating_sub(1)))
+            .map_or(0, |(line, _)| *line);
+        Err(ScriptError { line, message })
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), ScriptError> {
+        if self.peek() == &Token::Symbol(symbol) {
+            self.position += 1;
+            Ok(())
+        } else {
+            self.error(format!("Expected '{}', found {}", symbol, self.peek()))
+        }
+    }
+
+    fn expect_text(&mut self) -> Result<String, ScriptError> {
+        match self.next() {
+            Token::Text(text) => Ok(text),
+            other => {
+                self.position -= 1;
+                self.error(format!("Expected text in quotes, found {}", other))
+            }
+        }
+    }
+
+    /// Adds the node for the expression whose first token is at start, or
+    /// explains why the expression does not make sense.
+    fn push(&mut self, start: usize, node: Node) -> Result<NodeId, ScriptError> {
+        let result = self.engine.try_push_node(node).and_then(|id| {
+            let checked = self.engine.check_types(id);
+            if checked.is_err() {
+                self.engine.nodes.remove(&id);
+            }
+            checked.map(|()| id)
+        });
+        match result {
+            Ok(id) => Ok(id),
+            Err(ValidationError::WrongType { problem, .. }) => {
+                self.error(format!("`{}` {}", self.source(start), problem))
+            }
+            Err(err) => self.error(format!("`{}`: {}", self.source(start), err)),
+        }
+    }
+
+    fn push_basic(
+        &mut self,
+        start: usize,
+        op: BasicOp,
+        input: NodeId,
+        argument: NodeId,
+    ) -> Result<NodeId, ScriptError> {
+        self.push(
+            start,
+            Node {
+                operation: NodeOperation::Basic(op),
+                input: Some(input),
+                arguments: vec![argument],
+            },
+        )
+    }
+
+    /// The tokens from start up to the current one, written back out.
+    fn source(&self, start: usize) -> String {
+        let tokens = &self.tokens[start..self.position.min(self.tokens.len())];
+        let mut source = String::new();
+        for (index, (_, token)) in tokens.iter().enumerate() {
+            let after_operand = index > 0
+                && matches!(
+                    tokens[index - 1].1,
+                    Token::Name(..)
+                        | Token::Float(..)
+                        | Token::Integer(..)
+                        | Token::Text(..)
+                        | Token::Symbol(')' | '}')
+                );
+            match token {
+                Token::Symbol('-') if !after_operand => source.push('-'),
+                Token::Symbol(c @ ('+' | '-' | '*' | '/')) => write!(source, " {} ", c).unwrap(),
+                Token::Symbol(c @ (',' | ':')) => write!(source, "{} ", c).unwrap(),
+                Token::Symbol(c) => source.push(*c),
+                Token::Float(value) => source.push_str(&float(*value)),
+                Token::Newline => (),
+                token => write!(source, "{}", token).unwrap(),
+            }
+        }
+        source
+    }
+
+    fn statements(&mut self) -> Result<(), ScriptError> {
+        while self.position < self.tokens.len() {
+            match self.next() {
+                Token::Newline => continue,
+                Token::Name(name) => {
+                    self.expect('=')?;
+                    let value = self.sum()?;
+                    if self.peek() != &Token::Newline {
+                        return self.error(format!("Unexpected {}", self.peek()));
+                    }
+                    if name == "root" {
+                        self.engine.set_root(value);
+                    }
+                    self.names.insert(name, value);
+                }
+                other => {
+                    self.position -= 1;
+                    return self.error(format!("Expected a name to assign to, found {}", other));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn sum(&mut self) -> Result<NodeId, ScriptError> {
+        let start = self.position;
+        let mut value = self.product()?;
+        loop {
+            let op = match self.peek() {
+                Token::Symbol('+') => BasicOp::Add,
+                Token::Symbol('-') => BasicOp::Subtract,
+                _ => return Ok(value),
+            };
+            self.position += 1;
+            let argument = self.product()?;
+            value = self.push_basic(start, op, value, argument)?;
+        }
+    }
+
+    fn product(&mut self) -> Result<NodeId, ScriptError> {
+        let start = self.position;
+        let mut value = self.factor()?;
+        loop {
+            let op = match self.peek() {
+                Token::Symbol('*') => BasicOp::Multiply,
+                Token::Symbol('/') => BasicOp::Divide,
+                _ => return Ok(value),
+            };
+            self.position += 1;
+            let argument = self.factor()?;
+            value = self.push_basic(start, op, value, argument)?;
+        }
+    }
+
+    fn factor(&mut self) -> Result<NodeId, ScriptError> {
+        match self.peek().clone() {
+            Token::Symbol('(') => {
+                self.position += 1;
+                let value = self.sum()?;
+                self.expect(')')?;
+                Ok(value)
+            }
+            Token::Name(name) => {
+                self.position += 1;
+                if self.peek() == &Token::Symbol('(') {
+                    self.position += 1;
+                    self.call(&name, self.position - 2)
+                } else if let Some(&node) = self.names.get(&name) {
+                    Ok(node)
+                } else if let Some(value) = special_float(&name) {
+                    Ok(self.engine.push_literal_node(value.into()))
+                } else {
+                    self.position -= 1;
+                    self.error(format!("\"{}\" has not been defined", name))
+                }
+            }
+            _ => {
+                let value = self.constant()?;
+                Ok(self.engine.push_literal_node(value))
+            }
+        }
+    }
+
+    /// A value written out in full, which becomes a literal.
+    fn constant(&mut self) -> Result<TypedBlob, ScriptError> {
+        match self.next() {
+            Token::Float(value) => Ok(value.into()),
+            Token::Integer(value) => self.integer(value),
+            Token::Name(name) if special_float(&name).is_some() => {
+                Ok(special_float(&name).unwrap().into())
+            }
+            Token::Text(text) => Ok(text.into()),
+            Token::Symbol('-') => match self.next() {
+                Token::Float(value) => Ok((-value).into()),
+                Token::Integer(value) => self.integer(-value),
+                Token::Name(name) if special_float(&name).is_some() => {
+                    Ok((-special_float(&name).unwrap()).into())
+                }
+                other => {
+                    self.position -= 1;
+                    self.error(format!("Only numbers can be negated, found {}", other))
+                }
+            },
+            Token::Symbol('{') => {
+                let mut components = Vec::new();
+                while self.peek() != &Token::Symbol('}') {
+                    if components.len() > 0 {
+                        self.expect(',')?;
+                    }
+                    let key = self.expect_text()?;
+                    self.expect(':')?;
+                    components.push((key.into(), self.constant()?));
+                }
+                self.position += 1;
+                if components.is_empty() {
+                    return self.error(format!("Structs need at least one component"));
+                }
+                Ok(TypedBlob::fixed_heterogeneous_map(components))
+            }
+            other => {
+                self.position -= 1;
+                self.error(format!("Expected a value, found {}", other))
+            }
+        }
+    }
+
+    fn integer(&self, value: i64) -> Result<TypedBlob, ScriptError> {
+        match i32::try_from(value) {
+            Ok(value) => Ok(value.into()),
+            Err(_) => self.error(format!("{} is too large for a 32 bit integer", value)),
+        }
+    }
+
+    /// Parses the arguments of a call, after the opening parenthesis. The
+    /// name of the function is at start.
+    fn call(&mut self, function: &str, start: usize) -> Result<NodeId, ScriptError> {
+        match function {
+            "get" => {
+                let input = self.sum()?;
+                self.expect(',')?;
+                let component = self.expect_text()?;
+                self.expect(')')?;
+                self.push(
+                    start,
+                    Node {
+                        operation: NodeOperation::GetComponent(component),
+                        input: Some(input),
+                        arguments: vec![],
+                    },
+                )
+            }
+            "parameter" => {
+                let name = self.sum()?;
+                self.expect(',')?;
+                let default = self.sum()?;
+                self.expect(')')?;
+                let id = self.engine.parameter_ids.next();
+                self.push(
+                    start,
+                    Node {
+                        operation: NodeOperation::Parameter(id),
+                        input: Some(default),
+                        arguments: vec![name],
+                    },
+                )
+            }
+            "compose" => {
+                let name = self.expect_text()?;
+                let mut components = Vec::new();
+                let mut arguments = Vec::new();
+                while self.peek() != &Token::Symbol(')') {
+                    self.expect(',')?;
+                    components.push(self.expect_text()?);
+                    self.expect(':')?;
+                    arguments.push(self.sum()?);
+                }
+                self.position += 1;
+                self.push(
+                    start,
+                    Node {
+                        operation: NodeOperation::ComposeStruct(name, components),
+                        input: None,
+                        arguments,
+                    },
+                )
+            }
+            _ => {
+                let arguments = self.arguments()?;
+                if let Some(op) = basic_function(function) {
+                    let &[input, argument] = &arguments[..] else {
+                        return self.error(format!("{} takes 2 values", function));
+                    };
+                    self.push_basic(start, op, input, argument)
+                } else if let Some(&result) = self.names.get(function) {
+                    self.push(
+                        start,
+                        Node {
+                            operation: NodeOperation::CustomNode {
+                                result,
+                                input: None,
+                            },
+                            input: None,
+                            arguments,
+                        },
+                    )
+                } else {
+                    self.error(format!("\"{}\" has not been defined", function))
+                }
+            }
+        }
+    }
+
+    fn arguments(&mut self) -> Result<Vec<NodeId>, ScriptError> {
+        let mut arguments = Vec::new();
+        while self.peek() != &Token::Symbol(')') {
+            if arguments.len() > 0 {
+                self.expect(',')?;
+            }
+            arguments.push(self.sum()?);
+        }
+        self.position += 1;
+        Ok(arguments)
+    }
+}
+
+impl BuiltinDefinitions {
+    /// The names scripts use to refer to the builtin definitions.
+    pub fn script_names(&self) -> HashMap<String, NodeId> {
+        let inputs = &self.tool_inputs;
+        [
+            ("compose_vector_2d", self.compose_vector_2d),
+            ("compose_integer_vector_2d", self.compose_integer_vector_2d),
+            ("display_position", self.display_position.1),
+            ("mouse_offset", inputs.mouse_offset.1),
+            ("mouse_steps", inputs.mouse_steps.1),
+            ("drag_offset", inputs.drag_offset.1),
+            ("scroll_offset", inputs.scroll_offset.1),
+            ("cursor_position", inputs.cursor_position.1),
+            ("modifiers", inputs.modifiers.1),
+        ]
+        .into_iter()
+        .map(|(name, node)| (name.to_owned(), node))
+        .collect()
+    }
+}
+
+impl Engine {
+    /// Adds the nodes a script describes. The script can refer to the given
+    /// names, and the names it assigns are added to them.
+    pub fn run_script(
+        &mut self,
+        script: &str,
+        names: &mut HashMap<String, NodeId>,
+    ) -> Result<(), ScriptError> {
+        ScriptParser {
+            tokens: tokenize(script)?,
+            engine: self,
+            names,
+            position: 0,
+        }
+        .statements()
+    }
+
+    /// Writes a script that builds root when run with the same names.
+    /// Nodes that are used more than once get their own line so that
+    /// running the script gives the same graph. Fails if the graph has
+    /// something scripts cannot describe.
+    pub fn write_script(
+        &self,
+        root: NodeId,
+        names: &HashMap<String, NodeId>,
+    ) -> Result<String, ScriptError> {
+        let mut writer = ScriptWriter {
+            engine: self,
+            names: names
+                .iter()
+                .map(|(name, &node)| (node, name.clone()))
+                .collect(),
+            uses: HashMap::new(),
+            order: Vec::new(),
+        };
+        writer.visit(root, &mut HashSet::new());
+        let definitions: HashSet<_> = writer
+            .order
+            .iter()
+            .filter_map(|&node| match &self[node].operation {
+                NodeOperation::CustomNode { result, .. } => Some(*result),
+                _ => None,
+            })
+            .collect();
+        let mut script = String::new();
+        let mut next_name = 1;
+        // The error is reported on the line the expression would have been
+        // written to.
+        let error = |script: &String, message| ScriptError {
+            line: script.lines().count() + 1,
+            message,
+        };
+        for node in writer.order.clone() {
+            let shared = writer.uses[&node] > 1 || definitions.contains(&node);
+            if shared && node != root && !writer.names.contains_key(&node) {
+                let value = writer.expression(node).map_err(|err| error(&script, err))?;
+                let name = format!("value_{}", next_name);
+                next_name += 1;
+                writeln!(script, "{} = {}", name, value).unwrap();
+                writer.names.insert(node, name);
+            }
+        }
+        let value = writer.expression(root).map_err(|err| error(&script, err))?;
+        writeln!(script, "root = {}", value).unwrap();
+        Ok(script)
+    }
+}
+
+struct ScriptWriter<'a> {
+    engine: &'a Engine,
+    names: HashMap<NodeId, String>,
+    uses: HashMap<NodeId, usize>,
+    /// Every node the script needs, with dependencies first.
+    order: Vec<NodeId>,
+}
+
+impl<'a> ScriptWriter<'a> {
+    fn visit(&mut self, node: NodeId, visited: &mut HashSet<NodeId>) {
+        *self.uses.entry(node).or_insert(0) += 1;
+        if self.names.contains_key(&node) || !visited.insert(node) {
+            return;
+        }
+        let dependencies = self.engine[node].dependencies().collect::<Vec<_>>();
+        for dependency in dependencies {
+            self.visit(dependency, visited);
+        }
+        self.order.push(node);
+    }
+
+    fn precedence(&self, node: NodeId) -> u8 {
+        if self.names.contains_key(&node) {
+            return 3;
+        }
+        match &self.engine[node].operation {
+            NodeOperation::Basic(BasicOp::Add | BasicOp::Subtract) => 1,
+            NodeOperation::Basic(BasicOp::Multiply | BasicOp::Divide) => 2,
+            _ => 3,
+        }
+    }
+
+    fn operand(&self, node: NodeId, parenthesize: bool) -> Result<String, String> {
+        let expression = self.expression(node)?;
+        if parenthesize {
+            Ok(format!("({})", expression))
+        } else {
+            Ok(expression)
+        }
+    }
+
+    fn expression(&self, id: NodeId) -> Result<String, String> {
+        if let Some(name) = self.names.get(&id) {
+            return Ok(name.clone());
+        }
+        let node = &self.engine[id];
+        let arguments = || {
+            node.arguments
+                .iter()
+                .map(|&argument| self.expression(argument))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(match &node.operation {
+            NodeOperation::Literal(value) => constant(value)?,
+            NodeOperation::Parameter(..) => format!(
+                "parameter({}, {})",
+                self.expression(node.arguments[0])?,
+                self.expression(node.input.unwrap())?
+            ),
+            NodeOperation::Basic(op) => {
+                let (input, argument) = (node.input.unwrap(), node.arguments[0]);
+                match infix_symbol(op) {
+                    Some(symbol) => {
+                        let precedence = self.precedence(id);
+                        format!(
+                            "{} {} {}",
+                            self.operand(input, self.precedence(input) < precedence)?,
+                            symbol,
+                            self.operand(argument, self.precedence(argument) <= precedence)?
+                        )
+                    }
+                    None => format!(
+                        "{}({}, {})",
+                        op.name().to_lowercase(),
+                        self.expression(input)?,
+                        self.expression(argument)?
+                    ),
+                }
+            }
+            NodeOperation::ComposeStruct(name, components) => {
+                let mut text = format!("compose({}", quote(name));
+                for (component, argument) in components.iter().zip(arguments()?) {
+                    write!(text, ", {}: {}", quote(component), argument).unwrap();
+                }
+                text.push(')');
+                text
+            }
+            NodeOperation::GetComponent(component) => format!(
+                "get({}, {})",
+                self.expression(node.input.unwrap())?,
+                quote(component)
+            ),
+            NodeOperation::CustomNode {
+                result,
+                input: None,
+            } => format!("{}({})", self.expression(*result)?, arguments()?.join(", ")),
+            NodeOperation::CustomNode {
+                input: Some(..), ..
+            } => {
+                return Err(format!(
+                    "Scripts cannot describe custom nodes with inputs yet"
+                ))
+            }
+        })
+    }
+}
+
+fn constant(value: &TypedBlob) -> Result<String, String> {
+    let view = value.view();
+    if let Ok(value) = view.as_f32() {
+        Ok(float(value))
+    } else if let Ok(value) = view.as_i32() {
+        Ok(format!("{}", value))
+    } else if let Ok(value) = view.as_string() {
+        Ok(quote(value))
+    } else if let (BlobLayout::FixedHeterogeneousMap(..), Some(keys)) =
+        (value.layout(), value.layout().string_keys())
+    {
+        let components = keys
+            .iter()
+            .map(|&key| {
+                let component = view.index(&key.to_owned().into()).to_owned();
+                Ok(format!("{}: {}", quote(key), constant(&component)?))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(format!("{{{}}}", components.join(", ")))
+    } else {
+        Err(format!(
+            "Scripts cannot describe values of type {:?} yet",
+            value.layout()
+        ))
+    }
+}
+
+/// Writes a float so that reading it back gives exactly the same value.
+fn float(value: f32) -> String {
+    if value.is_nan() {
+        format!("nan")
+    } else if value.is_infinite() {
+        format!("{}inf", if value < 0.0 { "-" } else { "" })
+    } else {
+        format!("{:?}", value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn written_scripts_read_back() {
+        let script = r#"value_1 = parameter("Size", 2.5)
+root = compose("Values", "Special": {"A": inf, "B": -inf, "C": nan}, "Smallest": -2147483648, "Text": "A \"quoted\"\nname", "Sum": value_1 + value_1 * 1e-7, "Vector": compose_vector_2d(-1.5, get(display_position, "X")))
+"#;
+        let (mut engine, builtins) = Engine::new();
+        engine
+            .run_script(script, &mut builtins.script_names())
+            .unwrap();
+        let root = engine.root_node();
+        let written = engine.write_script(root, &builtins.script_names()).unwrap();
+        assert_eq!(written, script);
+    }
+
+    #[test]
+    fn integers_must_fit() {
+        let (mut engine, builtins) = Engine::new();
+        let mut names = builtins.script_names();
+        assert!(engine.run_script("root = 2147483648", &mut names).is_err());
+        assert!(engine.run_script("root = -2147483649", &mut names).is_err());
+    }
+
+    #[test]
+    fn writing_unsupported_values_fails() {
+        let (mut engine, builtins) = Engine::new();
+        let list = engine.push_literal_node(TypedBlob::fixed_array(vec![1.0.into(), 2.0.into()]));
+        let err = engine
+            .write_script(list, &builtins.script_names())
+            .unwrap_err();
+        assert_eq!(err.line, 1);
+    }
+
+    #[test]
+    fn badly_typed_expressions_are_errors() {
+        let (mut engine, builtins) = Engine::new();
+        let mut names = builtins.script_names();
+        let count = engine.nodes().len();
+        let message = |engine: &mut Engine, script: &str| {
+            let err = engine.run_script(script, &mut names.clone()).unwrap_err();
+            err.message
+        };
+        assert_eq!(
+            message(&mut engine, r#"bad = get(1.0, "X")"#),
+            r#"`get(1.0, "X")` cannot get the component "X" of a value of type Float"#
+        );
+        assert_eq!(
+            message(&mut engine, r#"bad = 1.0 + "a""#),
+            "`1.0 + \"a\"` cannot add values of types Float and DynamicIndex(Byte)"
+        );
+        assert_eq!(
+            message(&mut engine, "bad = 2 * (1 - -1.5)"),
+            "`1 - -1.5` cannot subtract values of types Integer and Float"
+        );
+        assert_eq!(
+            message(&mut engine, "bad = parameter(1.0, 2.0)"),
+            "`parameter(1.0, 2.0)` needs a text literal as its name"
+        );
+        assert_eq!(
+            message(&mut engine, r#"bad = compose_vector_2d(1.0, "Y")"#)
+                .split(' ')
+                .next(),
+            Some("`compose_vector_2d(1.0,")
+        );
+        assert!(engine.run_script("a = 1\nb = a + 2.0", &mut names).is_err());
+        assert!(!names.contains_key("b"));
+        // The literals of the failed lines stay until garbage is collected,
+        // but none of the invalid nodes do.
+        assert!(engine.nodes().len() > count);
+        assert!(engine.validate().is_ok());
+    }
+
+    #[test]
+    fn demo_script_is_valid() {
+        let (mut engine, builtins) = Engine::new();
+        assert!(engine
+            .run_script(DEMO_SCRIPT, &mut builtins.script_names())
+            .is_ok());
+    }
+}
@@ -0,0 +1,736 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+};
+
+use itertools::Itertools;
+use theme::PREVIEW_TEXTURE_SIZE;
+
+use super::{
+    BasicOp, BlobLayout, EditError, Engine, Node, NodeId, NodeOperation, ParameterDescription,
+    ParameterId, TypedBlob,
+};
+use crate::util::Id;
+
+/// Parameters of a tool's target prototype whose names start with this are the
+/// literals the tool edits. The rest of the name is shown to the user.
+pub const TOOL_TARGET_PREFIX: &str = "SPECIAL TOOL TARGET ";
+/// A parameter of a target prototype with this name is replaced by whatever
+/// node the prototype is inserted after.
+pub const TOOL_WILDCARD_INPUT_NAME: &str = "SPECIAL TOOL WILDCARD INPUT";
+
+pub struct Tool {
+    pub name: String,
+    /// The name of the icon shown in the toolbox. The app looks it up, so
+    /// that the engine does not depend on how icons are drawn.
+    pub icon: String,
+    pub target_prototype: NodeId,
+    /// Click tools only have a press handler and ignore dragging.
+    pub mouse_drag_handler: Option<NodeId>,
+    pub mouse_press_handler: Option<NodeId>,
+    pub mouse_release_handler: Option<NodeId>,
+    pub scroll_handler: Option<NodeId>,
+}
+
+pub type ToolId = Id<Tool>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ToolEvent {
+    MousePress,
+    MouseDrag,
+    MouseRelease,
+    Scroll,
+}
+
+impl ToolEvent {
+    pub const ALL: [ToolEvent; 4] = [
+        ToolEvent::MousePress,
+        ToolEvent::MouseDrag,
+        ToolEvent::MouseRelease,
+        ToolEvent::Scroll,
+    ];
+}
+
+/// Parameters that the app fills in whenever it runs one of a tool's
+/// handlers. Handlers read them like any other parameter.
+pub struct BuiltinToolInputs {
+    /// How far the mouse moved since the last event, in pixels.
+    pub mouse_offset: (ParameterId, NodeId),
+    /// The same movement as mouse_offset, but in whole pixels so that tools
+    /// editing integers can step by exact amounts.
+    pub mouse_steps: (ParameterId, NodeId),
+    /// How far the mouse moved since the button was pressed, in pixels.
+    pub drag_offset: (ParameterId, NodeId),
+    /// How far the scroll wheel moved, in lines.
+    pub scroll_offset: (ParameterId, NodeId),
+    /// Where the cursor is, in the same coordinates as Display Position.
+    pub cursor_position: (ParameterId, NodeId),
+    /// Shift, Ctrl and Alt, each 1.0 when held and 0.0 otherwise.
+    pub modifiers: (ParameterId, NodeId),
+}
+
+impl Tool {
+    pub fn handler(&self, event: ToolEvent) -> Option<NodeId> {
+        match event {
+            ToolEvent::MousePress => self.mouse_press_handler,
+            ToolEvent::MouseDrag => self.mouse_drag_handler,
+            ToolEvent::MouseRelease => self.mouse_release_handler,
+            ToolEvent::Scroll => self.scroll_handler,
+        }
+    }
+
+    pub fn handler_mut(&mut self, event: ToolEvent) -> &mut Option<NodeId> {
+        match event {
+            ToolEvent::MousePress => &mut self.mouse_press_handler,
+            ToolEvent::MouseDrag => &mut self.mouse_drag_handler,
+            ToolEvent::MouseRelease => &mut self.mouse_release_handler,
+            ToolEvent::Scroll => &mut self.scroll_handler,
+        }
+    }
+
+    /// The target prototype and every handler the tool has.
+    pub fn nodes(&self) -> impl Iterator<Item = NodeId> {
+        [
+            Some(self.target_prototype),
+            self.mouse_drag_handler,
+            self.mouse_press_handler,
+            self.mouse_release_handler,
+            self.scroll_handler,
+        ]
+        .into_iter()
+        .flatten()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ToolParameterKind {
+    Ordinary,
+    Target,
+    WildcardInput,
+}
+
+#[derive(Clone, Debug)]
+pub enum ToolError {
+    NotAParameter(NodeId),
+    NoTargets,
+    /// The drag handler reads a tool target that is not part of the target
+    /// prototype.
+    UnknownTarget(String),
+    /// The drag handler produces a value that cannot be written back to the
+    /// targets it edits.
+    OutputLayoutMismatch,
+    Edit(EditError),
+}
+
+impl Display for ToolError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotAParameter(node) => write!(f, "{:?} is not a parameter", node),
+            Self::NoTargets => write!(f, "The target prototype has no tool targets"),
+            Self::UnknownTarget(name) => write!(
+                f,
+                "The drag handler reads the target \"{}\", which is not part of the target prototype",
+                ToolParameterKind::strip(name)
+            ),
+            Self::OutputLayoutMismatch => write!(
+                f,
+                "The drag handler's output does not have the same type as the targets it edits"
+            ),
+            Self::Edit(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<EditError> for ToolError {
+    fn from(err: EditError) -> Self {
+        Self::Edit(err)
+    }
+}
+
+impl ToolParameterKind {
+    pub fn of_name(name: &str) -> Self {
+        if name.starts_with(TOOL_TARGET_PREFIX) {
+            Self::Target
+        } else if name.starts_with(TOOL_WILDCARD_INPUT_NAME) {
+            Self::WildcardInput
+        } else {
+            Self::Ordinary
+        }
+    }
+
+    /// Returns the name without any prefix that marks it as a special tool
+    /// parameter.
+    pub fn strip(name: &str) -> &str {
+        if let Some(stripped) = name.strip_prefix(TOOL_TARGET_PREFIX) {
+            stripped
+        } else if let Some(stripped) = name.strip_prefix(TOOL_WILDCARD_INPUT_NAME) {
+            stripped.trim_start()
+        } else {
+            name
+        }
+    }
+
+    fn apply(self, name: &str) -> String {
+        let name = Self::strip(name);
+        match self {
+            Self::Ordinary => name.to_owned(),
+            Self::Target => format!("{}{}", TOOL_TARGET_PREFIX, name),
+            Self::WildcardInput if name.is_empty() => TOOL_WILDCARD_INPUT_NAME.to_owned(),
+            Self::WildcardInput => format!("{} {}", TOOL_WILDCARD_INPUT_NAME, name),
+        }
+    }
+}
+
+impl Engine {
+    /// Renames a parameter node so that target prototypes treat it as the
+    /// given kind of parameter.
+    pub fn mark_tool_parameter(
+        &mut self,
+        parameter: NodeId,
+        kind: ToolParameterKind,
+    ) -> Result<(), ToolError> {
+        let node = &self[parameter];
+        let NodeOperation::Parameter(..) = &node.operation else {
+            return Err(ToolError::NotAParameter(parameter));
+        };
+        let name_node = node.arguments[0];
+        let name = self[name_node].as_literal().view();
+        let name = kind.apply(name.as_string().unwrap());
+        self.set_literal_value(name_node, name.into())?;
+        self.mark_dirty(name_node);
+        Ok(())
+    }
+
+    /// Creates a tool from a target prototype and a drag handler that the user
+    /// has built out of regular nodes. The drag handler may read any of the
+    /// prototype's targets. If the prototype has one target, the handler
+    /// should produce a new value for it. Otherwise, it should produce a
+    /// struct with a component named after each target it edits.
+    pub fn create_tool(
+        &mut self,
+        name: String,
+        icon: String,
+        target_prototype: NodeId,
+        mouse_drag_handler: NodeId,
+    ) -> Result<ToolId, ToolError> {
+        self.add_tool(Tool {
+            name,
+            icon,
+            target_prototype,
+            mouse_drag_handler: Some(mouse_drag_handler),
+            mouse_press_handler: None,
+            mouse_release_handler: None,
+            scroll_handler: None,
+        })
+    }
+
+    /// Adds a tool after checking each of its handlers with
+    /// validate_tool_handler.
+    pub(super) fn add_tool(&mut self, tool: Tool) -> Result<ToolId, ToolError> {
+        self.validate_tool(&tool)?;
+        let id = self.tool_ids.next();
+        self.tools.insert(id, tool);
+        Ok(id)
+    }
+
+    pub(super) fn validate_tool(&self, tool: &Tool) -> Result<(), ToolError> {
+        let handlers = tool.nodes().filter(|&node| node != tool.target_prototype);
+        for handler in handlers {
+            self.validate_tool_handler(tool.target_prototype, handler)?;
+        }
+        Ok(())
+    }
+
+    /// Whether a tool with the same name, icon and graphs already exists,
+    /// such as after importing the same document twice. Imported nodes and
+    /// parameters get new IDs, so graphs are compared by their shape.
+    pub(super) fn has_tool_like(&self, tool: &Tool) -> bool {
+        self.tools.values().any(|existing| {
+            let mut pairs = GraphPairs::default();
+            existing.name == tool.name
+                && existing.icon == tool.icon
+                && self.same_shape(existing.target_prototype, tool.target_prototype, &mut pairs)
+                && ToolEvent::ALL.into_iter().all(|event| {
+                    match (existing.handler(event), tool.handler(event)) {
+                        (Some(a), Some(b)) => self.same_shape(a, b, &mut pairs),
+                        (a, b) => a == b,
+                    }
+                })
+        })
+    }
+
+    /// Whether the graphs at a and b are built the same way, with every
+    /// node and parameter of one standing for a single one of the other.
+    fn same_shape(&self, a: NodeId, b: NodeId, pairs: &mut GraphPairs) -> bool {
+        if let Some(&paired) = pairs.nodes.get(&a) {
+            return paired == b;
+        }
+        let (node_a, node_b) = (&self[a], &self[b]);
+        let mut same_parameter =
+            |a: ParameterId, b: ParameterId| *pairs.parameters.entry(a).or_insert(b) == b;
+        let same_operation = match (&node_a.operation, &node_b.operation) {
+            (&NodeOperation::Parameter(a), &NodeOperation::Parameter(b)) => same_parameter(a, b),
+            (
+                NodeOperation::CustomNode {
+                    result: result_a,
+                    input: input_a,
+                },
+                NodeOperation::CustomNode {
+                    result: result_b,
+                    input: input_b,
+                },
+            ) => {
+                let same_input = match (*input_a, *input_b) {
+                    (Some(a), Some(b)) => same_parameter(a, b),
+                    (a, b) => a == b,
+                };
+                same_input && self.same_shape(*result_a, *result_b, pairs)
+            }
+            (a, b) => a == b,
+        };
+        if !same_operation
+            || node_a.input.is_some() != node_b.input.is_some()
+            || node_a.arguments.len() != node_b.arguments.len()
+        {
+            return false;
+        }
+        pairs.nodes.insert(a, b);
+        let operands_a = node_a.input.iter().chain(node_a.arguments.iter());
+        let operands_b = node_b.input.iter().chain(node_b.arguments.iter());
+        operands_a
+            .zip(operands_b)
+            .all(|(&a, &b)| self.same_shape(a, b, pairs))
+    }
+
+    /// The builtin tools are made by the engine itself, so one being invalid
+    /// is a bug rather than something to report to the user.
+    fn add_builtin_tool(&mut self, tool: Tool) {
+        let name = tool.name.clone();
+        if let Err(err) = self.add_tool(tool) {
+            panic!("The builtin tool {} is invalid: {}", name, err);
+        }
+    }
+
+    /// Makes the tool run the given handler in response to an event, following
+    /// the same rules as the drag handler passed to create_tool.
+    pub fn set_tool_handler(
+        &mut self,
+        tool: ToolId,
+        event: ToolEvent,
+        handler: NodeId,
+    ) -> Result<(), ToolError> {
+        self.validate_tool_handler(self.tools[&tool].target_prototype, handler)?;
+        *self.tools.get_mut(&tool).unwrap().handler_mut(event) = Some(handler);
+        Ok(())
+    }
+
+    pub(super) fn validate_tool_handler(
+        &self,
+        target_prototype: NodeId,
+        handler: NodeId,
+    ) -> Result<(), ToolError> {
+        let targets = self.tool_targets_of(target_prototype);
+        if targets.is_empty() {
+            return Err(ToolError::NoTargets);
+        }
+        for parameter in self[handler].collect_parameters(&self.nodes) {
+            if ToolParameterKind::of_name(&parameter.name) == ToolParameterKind::Target
+                && !targets.iter().any(|target| target.id == parameter.id)
+            {
+                return Err(ToolError::UnknownTarget(parameter.name));
+            }
+        }
+        let output_layout = self.output_layout(handler);
+        if let [target] = &targets[..] {
+            if output_layout != self.output_layout(target.default) {
+                return Err(ToolError::OutputLayoutMismatch);
+            }
+            return Ok(());
+        }
+        let (Some(keys), BlobLayout::FixedHeterogeneousMap(_, component_layouts)) =
+            (output_layout.string_keys(), &output_layout)
+        else {
+            return Err(ToolError::OutputLayoutMismatch);
+        };
+        for (key, layout) in keys.into_iter().zip(component_layouts) {
+            let Some(target) = targets
+                .iter()
+                .find(|target| ToolParameterKind::strip(&target.name) == key)
+            else {
+                return Err(ToolError::UnknownTarget(key.to_owned()));
+            };
+            if layout != &self.output_layout(target.default) {
+                return Err(ToolError::OutputLayoutMismatch);
+            }
+        }
+        Ok(())
+    }
+
+    /// Splits a value produced by one of the tool's handlers into new values
+    /// for the targets it edits. Fails if the handler has been edited since
+    /// the tool was made so that its output no longer fits the targets.
+    pub fn tool_output_to_targets(
+        &self,
+        tool: ToolId,
+        output: TypedBlob,
+    ) -> Result<Vec<(ParameterId, TypedBlob)>, ToolError> {
+        let targets = self.tool_targets_of(self.tools[&tool].target_prototype);
+        if let [target] = &targets[..] {
+            return Ok(vec![(target.id, output)]);
+        }
+        let Some(keys) = output.layout().string_keys() else {
+            return Err(ToolError::OutputLayoutMismatch);
+        };
+        keys.into_iter()
+            .map(|key| {
+                let Some(target) = targets
+                    .iter()
+                    .find(|target| ToolParameterKind::strip(&target.name) == key)
+                else {
+                    return Err(ToolError::UnknownTarget(key.to_owned()));
+                };
+                let value = output.view().index(&key.to_owned().into()).to_owned();
+                Ok((target.id, value))
+            })
+            .collect()
+    }
+
+    fn tool_targets_of(&self, target_prototype: NodeId) -> Vec<ParameterDescription> {
+        self[target_prototype]
+            .collect_parameters(&self.nodes)
+            .into_iter()
+            .filter(|parameter| {
+                ToolParameterKind::of_name(&parameter.name) == ToolParameterKind::Target
+            })
+            .collect()
+    }
+}
+
+impl Engine {
+    pub(super) fn make_builtin_tool_inputs(
+        &mut self,
+        compose_vector_2d: NodeId,
+        compose_integer_vector_2d: NodeId,
+    ) -> BuiltinToolInputs {
+        let modifiers = {
+            let default = self.push_simple_struct(
+                "Modifiers",
+                vec![
+                    ("Shift", 0.0.into()),
+                    ("Ctrl", 0.0.into()),
+                    ("Alt", 0.0.into()),
+                ],
+            );
+            let name = self.push_literal_node("Modifiers".to_owned().into());
+            self.push_parameter(name, default)
+        };
+        BuiltinToolInputs {
+            mouse_offset: self.push_vector_parameter("Mouse Offset", compose_vector_2d),
+            mouse_steps: self.push_vector_parameter("Mouse Steps", compose_integer_vector_2d),
+            drag_offset: self.push_vector_parameter("Drag Offset", compose_vector_2d),
+            scroll_offset: self.push_vector_parameter("Scroll Offset", compose_vector_2d),
+            cursor_position: self.push_vector_parameter("Cursor Position", compose_vector_2d),
+            modifiers,
+        }
+    }
+
+    /// Converts mouse movement into a change in value. Holding shift makes
+    /// the change ten times smaller, holding ctrl makes the value change in
+    /// increments of snap_to.
+    fn push_drag_delta(&mut self, inputs: &BuiltinToolInputs, scale: f32, snap_to: f32) -> NodeId {
+        let sum_components = |this: &mut Self, vector: NodeId| {
+            let x = this.push_get_component(vector, "X");
+            let y = this.push_get_component(vector, "Y");
+            this.push_basic(BasicOp::Add, x, y)
+        };
+        let movement = sum_components(self, inputs.mouse_offset.1);
+        let total_movement = sum_components(self, inputs.drag_offset.1);
+        let shift = self.push_get_component(inputs.modifiers.1, "Shift");
+        let ctrl = self.push_get_component(inputs.modifiers.1, "Ctrl");
+        let scale = self.push_literal_node(scale.into());
+        let snap_to = self.push_literal_node(snap_to.into());
+
+        let fine_factor = {
+            let one = self.push_literal_node(1.0.into());
+            let shrink = self.push_literal_node(0.9.into());
+            let shrink = self.push_basic(BasicOp::Multiply, shift, shrink);
+            self.push_basic(BasicOp::Subtract, one, shrink)
+        };
+        let free_delta = self.push_basic(BasicOp::Multiply, movement, scale);
+        let free_delta = self.push_basic(BasicOp::Multiply, free_delta, fine_factor);
+        // Snapping is done on the total movement so that small movements
+        // eventually add up to a whole increment.
+        let snapped_delta = {
+            let previous_movement = self.push_basic(BasicOp::Subtract, total_movement, movement);
+            let now = self.push_basic(BasicOp::Multiply, total_movement, scale);
+            let now = self.push_basic(BasicOp::Snap, now, snap_to);
+            let before = self.push_basic(BasicOp::Multiply, previous_movement, scale);
+            let before = self.push_basic(BasicOp::Snap, before, snap_to);
+            self.push_basic(BasicOp::Subtract, now, before)
+        };
+        let difference = self.push_basic(BasicOp::Subtract, snapped_delta, free_delta);
+        let difference = self.push_basic(BasicOp::Multiply, difference, ctrl);
+        self.push_basic(BasicOp::Add, free_delta, difference)
+    }
+
+    fn push_vector_struct(&mut self, x: NodeId, y: NodeId) -> NodeId {
+        self.push_node(Node {
+            operation: NodeOperation::ComposeStruct(
+                format!("Vector/2D"),
+                vec![format!("X"), format!("Y")],
+            ),
+            input: None,
+            arguments: vec![x, y],
+        })
+    }
+
+    /// Clamps each component to the range 0 to 1.
+    fn push_color_struct(&mut self, components: [NodeId; 3]) -> NodeId {
+        let zero = self.push_literal_node(0.0.into());
+        let one = self.push_literal_node(1.0.into());
+        let components = components
+            .into_iter()
+            .map(|component| {
+                let component = self.push_basic(BasicOp::Maximum, component, zero);
+                self.push_basic(BasicOp::Minimum, component, one)
+            })
+            .collect_vec();
+        self.push_node(Node {
+            operation: NodeOperation::ComposeStruct(
+                format!("Color"),
+                vec![format!("R"), format!("G"), format!("B")],
+            ),
+            input: None,
+            arguments: components,
+        })
+    }
+
+    /// Returns the IDs of the tools, so that they can be left out when
+    /// saving the user's own tools. Has to be called before any other tools
+    /// are added.
+    pub(super) fn make_builtin_tools(&mut self, inputs: &BuiltinToolInputs) -> Vec<ToolId> {
+        let dx = self.push_get_component(inputs.mouse_offset.1, "X");
+        let dy = self.push_get_component(inputs.mouse_offset.1, "Y");
+        let step_x = self.push_get_component(inputs.mouse_steps.1, "X");
+        let step_y = self.push_get_component(inputs.mouse_steps.1, "Y");
+        let step_x_plus_y = self.push_basic(BasicOp::Add, step_x, step_y);
+        let scroll = self.push_get_component(inputs.scroll_offset.1, "Y");
+
+        // Multiplies whatever it is placed after.
+        let (prototype, target) = {
+            let target = self.push_simple_parameter("SPECIAL TOOL TARGET Factor", 1.0.into());
+            let input = self.push_simple_parameter("SPECIAL TOOL WILDCARD INPUT", 0.0.into());
+            let prototype = self.push_basic(BasicOp::Multiply, input, target);
+            (prototype, target)
+        };
+        let delta = self.push_drag_delta(inputs, 0.01, 0.1);
+        let drag_handler = self.push_basic(BasicOp::Add, target, delta);
+        let scroll_handler = {
+            let scale = self.push_literal_node(0.1.into());
+            let delta = self.push_basic(BasicOp::Multiply, scroll, scale);
+            self.push_basic(BasicOp::Add, target, delta)
+        };
+        self.add_builtin_tool(Tool {
+            name: format!("Adjust Float"),
+            icon: format!("arrow-left-right"),
+            target_prototype: prototype,
+            mouse_drag_handler: Some(drag_handler),
+            mouse_press_handler: None,
+            mouse_release_handler: None,
+            scroll_handler: Some(scroll_handler),
+        });
+
+        // Adds to whatever it is placed after.
+        let (prototype, target) = {
+            let target = self.push_simple_parameter("SPECIAL TOOL TARGET Offset", 0.0.into());
+            let input = self.push_simple_parameter("SPECIAL TOOL WILDCARD INPUT", 0.0.into());
+            let prototype = self.push_basic(BasicOp::Add, input, target);
+            (prototype, target)
+        };
+        let delta = self.push_drag_delta(inputs, 0.01, 0.1);
+        let drag_handler = self.push_basic(BasicOp::Add, target, delta);
+        let scroll_handler = {
+            let scale = self.push_literal_node(0.1.into());
+            let delta = self.push_basic(BasicOp::Multiply, scroll, scale);
+            self.push_basic(BasicOp::Add, target, delta)
+        };
+        self.add_builtin_tool(Tool {
+            name: format!("Offset Float"),
+            icon: format!("plus-minus"),
+            target_prototype: prototype,
+            mouse_drag_handler: Some(drag_handler),
+            mouse_press_handler: None,
+            mouse_release_handler: None,
+            scroll_handler: Some(scroll_handler),
+        });
+
+        // The remaining tools have no wildcard input, so they only edit
+        // literals that already exist instead of inserting new nodes.
+        let target = self.push_simple_parameter("SPECIAL TOOL TARGET Integer", 0.into());
+        let drag_handler = self.push_basic(BasicOp::Add, target, step_x_plus_y);
+        self.add_builtin_tool(Tool {
+            name: format!("Adjust Integer"),
+            icon: format!("pound"),
+            target_prototype: target,
+            mouse_drag_handler: Some(drag_handler),
+            mouse_press_handler: None,
+            mouse_release_handler: None,
+            scroll_handler: None,
+        });
+
+        let target = self.push_simple_parameter(
+            "SPECIAL TOOL TARGET Vector",
+            TypedBlob::fixed_heterogeneous_map(vec![
+                (format!("X").into(), 0.0.into()),
+                (format!("Y").into(), 0.0.into()),
+            ]),
+        );
+        let drag_handler = {
+            let scale = self.push_literal_node(0.01.into());
+            let x = self.push_get_component(target, "X");
+            let x_delta = self.push_basic(BasicOp::Multiply, dx, scale);
+            let x = self.push_basic(BasicOp::Add, x, x_delta);
+            let y = self.push_get_component(target, "Y");
+            let y_delta = self.push_basic(BasicOp::Multiply, dy, scale);
+            let y = self.push_basic(BasicOp::Add, y, y_delta);
+            self.push_vector_struct(x, y)
+        };
+        self.add_builtin_tool(Tool {
+            name: format!("Drag Vector/2D"),
+            icon: format!("cursor-move"),
+            target_prototype: target,
+            mouse_drag_handler: Some(drag_handler),
+            mouse_press_handler: None,
+            mouse_release_handler: None,
+            scroll_handler: None,
+        });
+
+        // Edits both components of a vector that is built out of two literals.
+        let (prototype, x, y) = {
+            let x = self.push_simple_parameter("SPECIAL TOOL TARGET X", 0.0.into());
+            let y = self.push_simple_parameter("SPECIAL TOOL TARGET Y", 0.0.into());
+            (self.push_vector_struct(x, y), x, y)
+        };
+        let drag_handler = {
+            let scale = self.push_literal_node(0.01.into());
+            let x_delta = self.push_basic(BasicOp::Multiply, dx, scale);
+            let x = self.push_basic(BasicOp::Add, x, x_delta);
+            let y_delta = self.push_basic(BasicOp::Multiply, dy, scale);
+            let y = self.push_basic(BasicOp::Add, y, y_delta);
+            self.push_vector_struct(x, y)
+        };
+        self.add_builtin_tool(Tool {
+            name: format!("Drag Components"),
+            icon: format!("axis-arrow"),
+            target_prototype: prototype,
+            mouse_drag_handler: Some(drag_handler),
+            mouse_press_handler: None,
+            mouse_release_handler: None,
+            scroll_handler: None,
+        });
+
+        // Clicking in the preview moves the point to where the cursor is.
+        let target = self.push_simple_parameter(
+            "SPECIAL TOOL TARGET Point",
+            TypedBlob::fixed_heterogeneous_map(vec![
+                (format!("X").into(), 0.0.into()),
+                (format!("Y").into(), 0.0.into()),
+            ]),
+        );
+        let cursor = {
+            let x = self.push_get_component(inputs.cursor_position.1, "X");
+            let y = self.push_get_component(inputs.cursor_position.1, "Y");
+            self.push_vector_struct(x, y)
+        };
+        self.add_builtin_tool(Tool {
+            name: format!("Place Point"),
+            icon: format!("map-marker"),
+            target_prototype: target,
+            mouse_drag_handler: Some(cursor),
+            mouse_press_handler: Some(cursor),
+            mouse_release_handler: None,
+            scroll_handler: None,
+        });
+
+        let target = self.push_simple_parameter("SPECIAL TOOL TARGET Angle", 0.0.into());
+        // Degrees per pixel.
+        let delta = self.push_drag_delta(inputs, 0.5, 15.0);
+        let drag_handler = self.push_basic(BasicOp::Add, target, delta);
+        let scroll_handler = {
+            // Degrees per line.
+            let scale = self.push_literal_node(15.0.into());
+            let delta = self.push_basic(BasicOp::Multiply, scroll, scale);
+            self.push_basic(BasicOp::Add, target, delta)
+        };
+        self.add_builtin_tool(Tool {
+            name: format!("Rotate Angle"),
+            icon: format!("rotate-right"),
+            target_prototype: target,
+            mouse_drag_handler: Some(drag_handler),
+            mouse_press_handler: None,
+            mouse_release_handler: None,
+            scroll_handler: Some(scroll_handler),
+        });
+
+        let target = self.push_simple_parameter(
+            "SPECIAL TOOL TARGET Color",
+            TypedBlob::fixed_heterogeneous_map(vec![
+                (format!("R").into(), 0.5.into()),
+                (format!("G").into(), 0.5.into()),
+                (format!("B").into(), 0.5.into()),
+            ]),
+        );
+        // Clicking in the preview picks red and green from where the cursor
+        // is, left to right and top to bottom. Scrolling adjusts blue.
+        let press_handler = {
+            let scale = self.push_literal_node((1.0 / PREVIEW_TEXTURE_SIZE as f32).into());
+            let x = self.push_get_component(inputs.cursor_position.1, "X");
+            let y = self.push_get_component(inputs.cursor_position.1, "Y");
+            let red = self.push_basic(BasicOp::Multiply, x, scale);
+            let green = self.push_basic(BasicOp::Multiply, y, scale);
+            let blue = self.push_get_component(target, "B");
+            self.push_color_struct([red, green, blue])
+        };
+        let scroll_handler = {
+            let scale = self.push_literal_node(0.05.into());
+            let delta = self.push_basic(BasicOp::Multiply, scroll, scale);
+            let red = self.push_get_component(target, "R");
+            let green = self.push_get_component(target, "G");
+            let blue = self.push_get_component(target, "B");
+            let blue = self.push_basic(BasicOp::Add, blue, delta);
+            self.push_color_struct([red, green, blue])
+        };
+        self.add_builtin_tool(Tool {
+            name: format!("Pick Color"),
+            icon: format!("eyedropper"),
+            target_prototype: target,
+            mouse_drag_handler: None,
+            mouse_press_handler: Some(press_handler),
+            mouse_release_handler: None,
+            scroll_handler: Some(scroll_handler),
+        });
+
+        // Booleans are integers that are either 0 or 1. Clicking flips them.
+        let target = self.push_simple_parameter("SPECIAL TOOL TARGET Enabled", 0.into());
+        let one = self.push_literal_node(1.into());
+        let press_handler = self.push_basic(BasicOp::Subtract, one, target);
+        self.add_builtin_tool(Tool {
+            name: format!("Toggle Boolean"),
+            icon: format!("toggle-switch"),
+            target_prototype: target,
+            mouse_drag_handler: None,
+            mouse_press_handler: Some(press_handler),
+            mouse_release_handler: None,
+            scroll_handler: None,
+        });
+        self.tools.keys().copied().sorted().collect()
+    }
+}
+
+/// The nodes and parameters of one graph that have been found to stand for
+/// ones of another.
+#[derive(Default)]
+struct GraphPairs {
+    nodes: HashMap<NodeId, NodeId>,
+    parameters: HashMap<ParameterId, ParameterId>,
+}
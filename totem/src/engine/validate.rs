@@ -0,0 +1,457 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display, Formatter},
+};
+
+use itertools::Itertools;
+
+use super::{BlobLayout, Engine, Node, NodeId, NodeOperation};
+
+#[derive(Clone, Debug)]
+pub enum ValidationError {
+    /// A node, or the engine itself if user is None, refers to a node that
+    /// does not exist.
+    MissingNode {
+        user: Option<NodeId>,
+        missing: NodeId,
+    },
+    /// The node depends on its own result.
+    Cycle(NodeId),
+    WrongArity {
+        node: NodeId,
+        expected: Arity,
+        found: Arity,
+    },
+    /// The node cannot work with the types of its operands.
+    WrongType { node: NodeId, problem: String },
+}
+
+/// Which operands a node has.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Arity {
+    pub has_input: bool,
+    pub arguments: usize,
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingNode {
+                user: Some(user),
+                missing,
+            } => write!(f, "{:?} uses {:?}, which does not exist", user, missing),
+            Self::MissingNode {
+                user: None,
+                missing,
+            } => write!(f, "The engine uses {:?}, which does not exist", missing),
+            Self::Cycle(node) => write!(f, "{:?} depends on its own result", node),
+            Self::WrongArity {
+                node,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{:?} should have {} and {} arguments, but has {} and {} arguments",
+                node,
+                if expected.has_input {
+                    "an input"
+                } else {
+                    "no input"
+                },
+                expected.arguments,
+                if found.has_input {
+                    "an input"
+                } else {
+                    "no input"
+                },
+                found.arguments,
+            ),
+            Self::WrongType { node, problem } => write!(f, "{:?} {}", node, problem),
+        }
+    }
+}
+
+impl ValidationError {
+    /// The node that is invalid, if the problem is with a node.
+    pub fn node(&self) -> Option<NodeId> {
+        match self {
+            Self::MissingNode { user, .. } => *user,
+            Self::Cycle(node) | Self::WrongArity { node, .. } | Self::WrongType { node, .. } => {
+                Some(*node)
+            }
+        }
+    }
+}
+
+impl Node {
+    /// Every node this node uses directly, including the definition of a
+    /// custom node.
+    pub fn dependencies(&self) -> impl Iterator<Item = NodeId> + '_ {
+        let result = match &self.operation {
+            NodeOperation::CustomNode { result, .. } => Some(*result),
+            _ => None,
+        };
+        self.input
+            .iter()
+            .chain(self.arguments.iter())
+            .copied()
+            .chain(result)
+    }
+}
+
+impl Engine {
+    /// Checks the whole graph for cycles, references to nodes that do not
+    /// exist, nodes with the wrong number of operands, and operands of the
+    /// wrong type. The editing methods keep the graph valid, so this is
+    /// mostly useful for tests.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let mut engine_references = vec![self.root_node];
+        for tool in self.tools.values() {
+            engine_references.extend(tool.nodes());
+        }
+        if let Some(&missing) = engine_references
+            .iter()
+            .find(|node| !self.nodes.contains_key(node))
+        {
+            return Err(ValidationError::MissingNode {
+                user: None,
+                missing,
+            });
+        }
+        for (&id, node) in &self.nodes {
+            self.check_references_exist(id, node)?;
+        }
+        self.check_acyclic()?;
+        for &id in self.nodes.keys() {
+            self.check_arity(id)?;
+        }
+        let all = self.nodes.keys().copied().collect();
+        let mut checked = HashSet::new();
+        for &id in self.nodes.keys() {
+            self.check_types_from(id, &all, &mut checked)?;
+        }
+        Ok(())
+    }
+
+    /// Checks that making user depend on used would not create a cycle.
+    pub fn check_new_dependency(&self, user: NodeId, used: NodeId) -> Result<(), ValidationError> {
+        if !self.nodes.contains_key(&used) {
+            Err(ValidationError::MissingNode {
+                user: Some(user),
+                missing: used,
+            })
+        } else if self.depends_on(used, user) {
+            Err(ValidationError::Cycle(user))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Adds nodes whose IDs have already been created, such as ones read from
+    /// a file. If the result would be invalid, none of them are added.
+    pub(super) fn insert_nodes(
+        &mut self,
+        nodes: Vec<(NodeId, Node)>,
+    ) -> Result<(), ValidationError> {
+        let ids = nodes.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+        for (id, node) in &nodes {
+            let missing = node.dependencies().find(|dependency| {
+                !self.nodes.contains_key(dependency) && !ids.contains(dependency)
+            });
+            if let Some(missing) = missing {
+                return Err(ValidationError::MissingNode {
+                    user: Some(*id),
+                    missing,
+                });
+            }
+        }
+        self.nodes.extend(nodes);
+        // Check for cycles first, since the other checks assume there are
+        // none.
+        let result = self
+            .check_acyclic()
+            .and_then(|()| ids.iter().try_for_each(|&id| self.check_arity(id)))
+            .and_then(|()| {
+                let new = ids.iter().copied().collect();
+                let mut checked = HashSet::new();
+                ids.iter()
+                    .try_for_each(|&id| self.check_types_from(id, &new, &mut checked))
+            });
+        if result.is_err() {
+            for id in ids {
+                self.nodes.remove(&id);
+            }
+        }
+        result
+    }
+
+    pub(super) fn check_references_exist(
+        &self,
+        id: NodeId,
+        node: &Node,
+    ) -> Result<(), ValidationError> {
+        match node
+            .dependencies()
+            .find(|dependency| !self.nodes.contains_key(dependency))
+        {
+            Some(missing) => Err(ValidationError::MissingNode {
+                user: Some(id),
+                missing,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    pub(super) fn check_acyclic(&self) -> Result<(), ValidationError> {
+        #[derive(Clone, Copy, PartialEq, Eq)]
+        enum Visit {
+            InProgress,
+            Done,
+        }
+        let mut visits = HashMap::new();
+        for &start in self.nodes.keys() {
+            if visits.contains_key(&start) {
+                continue;
+            }
+            // Each entry is a node and whether all of its dependencies have
+            // been visited.
+            let mut stack = vec![(start, false)];
+            while let Some((id, finished)) = stack.pop() {
+                if finished {
+                    visits.insert(id, Visit::Done);
+                    continue;
+                }
+                match visits.get(&id) {
+                    Some(Visit::Done) => continue,
+                    Some(Visit::InProgress) => return Err(ValidationError::Cycle(id)),
+                    None => (),
+                }
+                visits.insert(id, Visit::InProgress);
+                stack.push((id, true));
+                for dependency in self[id].dependencies() {
+                    match visits.get(&dependency) {
+                        Some(Visit::InProgress) => return Err(ValidationError::Cycle(dependency)),
+                        Some(Visit::Done) => (),
+                        None => stack.push((dependency, false)),
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the types of node and of the new nodes it depends on, with
+    /// dependencies first so that the layouts of the operands of each node
+    /// can be worked out safely. Nodes that are not new are assumed valid.
+    pub(super) fn check_types_from(
+        &self,
+        id: NodeId,
+        new: &HashSet<NodeId>,
+        checked: &mut HashSet<NodeId>,
+    ) -> Result<(), ValidationError> {
+        if !new.contains(&id) || !checked.insert(id) {
+            return Ok(());
+        }
+        for dependency in self[id].dependencies().collect_vec() {
+            self.check_types_from(dependency, new, checked)?;
+        }
+        self.check_types(id)
+    }
+
+    /// Checks that the operands of a node have types it can work with,
+    /// assuming that they are valid themselves.
+    pub(super) fn check_types(&self, id: NodeId) -> Result<(), ValidationError> {
+        let node = &self[id];
+        let wrong_type = |problem: String| Err(ValidationError::WrongType { node: id, problem });
+        match &node.operation {
+            NodeOperation::Literal(value) => {
+                if let Err(problem) = value.layout().check_supported() {
+                    return wrong_type(problem);
+                }
+            }
+            NodeOperation::Parameter(..) => {
+                let name = &self[node.arguments[0]];
+                let is_text = match &name.operation {
+                    NodeOperation::Literal(value) => value.view().as_string().is_ok(),
+                    _ => false,
+                };
+                if !is_text {
+                    return wrong_type(format!("needs a text literal as its name"));
+                }
+            }
+            NodeOperation::Basic(op) => {
+                let input = self.output_layout(node.input.unwrap());
+                let argument = self.output_layout(node.arguments[0]);
+                if !matches!(input, BlobLayout::Float | BlobLayout::Integer) {
+                    return wrong_type(format!(
+                        "cannot {} values of type {:?}",
+                        op.name().to_lowercase(),
+                        input
+                    ));
+                }
+                if argument != input {
+                    return wrong_type(format!(
+                        "cannot {} values of types {:?} and {:?}",
+                        op.name().to_lowercase(),
+                        input,
+                        argument
+                    ));
+                }
+            }
+            NodeOperation::ComposeStruct(_, components) => {
+                if components.is_empty() {
+                    return wrong_type(format!("needs at least one component"));
+                }
+            }
+            NodeOperation::GetComponent(component) => {
+                let input = self.output_layout(node.input.unwrap());
+                let has_component = match (&input, input.string_keys()) {
+                    (BlobLayout::FixedHeterogeneousMap(..), Some(keys)) => {
+                        keys.contains(&&component[..])
+                    }
+                    _ => false,
+                };
+                if !has_component {
+                    return wrong_type(format!(
+                        "cannot get the component \"{}\" of a value of type {:?}",
+                        component, input
+                    ));
+                }
+            }
+            NodeOperation::CustomNode { result, .. } => {
+                // Operands are passed in the order of the IDs of the
+                // parameters they replace.
+                let parameters = self[*result]
+                    .collect_parameter_nodes(*result, &self.nodes)
+                    .into_iter()
+                    .sorted();
+                let operands = node.input.iter().chain(node.arguments.iter());
+                for (parameter, &operand) in parameters.zip(operands) {
+                    let expected = self.output_layout(self[parameter].input.unwrap());
+                    let found = self.output_layout(operand);
+                    if found != expected {
+                        return wrong_type(format!(
+                            "needs a value of type {:?} for {:?}, found {:?}",
+                            expected, parameter, found
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) fn check_arity(&self, id: NodeId) -> Result<(), ValidationError> {
+        let node = &self[id];
+        let arity = |has_input, arguments| Arity {
+            has_input,
+            arguments,
+        };
+        let found = arity(node.input.is_some(), node.arguments.len());
+        let expected = match &node.operation {
+            NodeOperation::Literal(..) => arity(false, 0),
+            NodeOperation::Parameter(..) => arity(true, 1),
+            NodeOperation::Basic(..) => arity(true, 1),
+            NodeOperation::ComposeStruct(_, components) => arity(false, components.len()),
+            NodeOperation::GetComponent(..) => arity(true, 0),
+            NodeOperation::CustomNode { result, input } => {
+                let parameters = self[*result].collect_parameter_nodes(*result, &self.nodes);
+                let has_input = input.is_some();
+                arity(
+                    has_input,
+                    parameters.len().saturating_sub(has_input as usize),
+                )
+            }
+        };
+        if expected == found {
+            Ok(())
+        } else {
+            Err(ValidationError::WrongArity {
+                node: id,
+                expected,
+                found,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{BasicOp, EditError, TypedBlob};
+
+    /// An engine whose root adds two literals.
+    fn engine_with_sum() -> (Engine, NodeId, NodeId) {
+        let (mut engine, _) = Engine::new();
+        let left = engine.push_literal_node(TypedBlob::from(1));
+        let right = engine.push_literal_node(TypedBlob::from(2));
+        let sum = engine.push_basic(BasicOp::Add, left, right);
+        engine.set_root(sum);
+        (engine, left, sum)
+    }
+
+    #[test]
+    fn validate_finds_missing_nodes() {
+        let (mut engine, _, sum) = engine_with_sum();
+        let missing = engine.node_ids.next();
+        engine[sum].arguments[0] = missing;
+        assert!(matches!(
+            engine.validate(),
+            Err(ValidationError::MissingNode { user: Some(user), missing: found })
+                if user == sum && found == missing
+        ));
+    }
+
+    #[test]
+    fn validate_finds_cycles() {
+        let (mut engine, left, sum) = engine_with_sum();
+        let product = engine.push_basic(BasicOp::Multiply, sum, left);
+        engine[sum].input = Some(product);
+        assert!(matches!(engine.validate(), Err(ValidationError::Cycle(_))));
+    }
+
+    #[test]
+    fn validate_finds_wrong_arity() {
+        let (mut engine, _, sum) = engine_with_sum();
+        engine[sum].arguments.clear();
+        assert!(matches!(
+            engine.validate(),
+            Err(ValidationError::WrongArity { node, .. }) if node == sum
+        ));
+    }
+
+    #[test]
+    fn replacing_a_literal_checks_its_users() {
+        let (mut engine, left, sum) = engine_with_sum();
+        assert_eq!(engine.replace_literal(left, 3.into()).unwrap(), left);
+        let nodes = engine.nodes().len();
+        assert!(matches!(
+            engine.replace_literal(left, 1.5.into()),
+            Err(EditError::Invalid(ValidationError::WrongType { node, .. })) if node == sum
+        ));
+        assert_eq!(engine.nodes().len(), nodes);
+        assert_eq!(engine[sum].input, Some(left));
+        assert!(engine.validate().is_ok());
+
+        let default = engine.push_literal_node(TypedBlob::from(1));
+        let name = engine.push_literal_node(TypedBlob::from(format!("Value")));
+        let (_, parameter) = engine.push_parameter(name, default);
+        engine.set_root(parameter);
+        let replacement = engine.replace_literal(default, 1.5.into()).unwrap();
+        assert_ne!(replacement, default);
+        assert_eq!(engine[parameter].input, Some(replacement));
+        assert_eq!(engine.output_layout(parameter), BlobLayout::Float);
+        assert!(engine.validate().is_ok());
+    }
+
+    #[test]
+    fn move_up_rejects_node_using_its_input_twice() {
+        let (mut engine, left, sum) = engine_with_sum();
+        let product = engine.push_basic(BasicOp::Multiply, sum, sum);
+        engine.set_root(product);
+        assert!(engine.move_up(product).is_err());
+        assert!(engine.validate().is_ok());
+        assert_eq!(engine[product].input, Some(sum));
+        assert_eq!(engine[sum].input, Some(left));
+    }
+}
@@ -1,10 +1,45 @@
 use std::{cmp::Ordering, fmt::Debug, hash::Hash, marker::PhantomData};
 
+/// Suffixes used by pretty_format_number, along with the power of 1000 they
+/// stand for.
+const NUMBER_SUFFIXES: [(i32, &str); 23] = [
+    (11, "Dc"),
+    (10, "Nn"),
+    (9, "Oc"),
+    (8, "Sp"),
+    (7, "Sx"),
+    (6, "Qn"),
+    (5, "Qd"),
+    (4, "Tl"),
+    (3, "Bl"),
+    (2, "Ml"),
+    (1, "Th"),
+    (0, ""),
+    (-1, "tht"),
+    (-2, "mlt"),
+    (-3, "blt"),
+    (-4, "tlt"),
+    (-5, "qdc"),
+    (-6, "qnt"),
+    (-7, "sxt"),
+    (-8, "spt"),
+    (-9, "oct"),
+    (-10, "nnt"),
+    (-11, "dct"),
+];
+
 pub fn pretty_format_number(number: f32) -> String {
+    let (digits, suffix) = pretty_format_number_parts(number);
+    digits + &suffix
+}
+
+/// The digits and the suffix pretty_format_number would produce, kept apart
+/// so they can be styled differently.
+pub fn pretty_format_number_parts(number: f32) -> (String, String) {
     if !number.is_finite() {
-        format!("{}", number)
+        (format!("{}", number), String::new())
     } else if number == 0.0 {
-        format!("0.000")
+        (format!("0.000"), String::new())
     } else {
         let mut number = number;
         let mut power = 0;
@@ -16,41 +51,121 @@ pub fn pretty_format_number(number: f32) -> String {
             power -= 1;
             number *= 1000.0;
         }
-        let suffix = match power {
-            11 => "Dc",
-            10 => "Nn",
-            9 => "Oc",
-            8 => "Sp",
-            7 => "Sx",
-            6 => "Qn",
-            5 => "Qd",
-            4 => "Tl",
-            3 => "Bl",
-            2 => "Ml",
-            1 => "Th",
-            0 => "",
-            -1 => "tht",
-            -2 => "mlt",
-            -3 => "blt",
-            -4 => "tlt",
-            -5 => "qdc",
-            -6 => "qnt",
-            -7 => "sxt",
-            -8 => "spt",
-            -9 => "oct",
-            -10 => "nnt",
-            -11 => "dct",
-            _ => "?",
-        };
+        let suffix = NUMBER_SUFFIXES
+            .iter()
+            .find(|(suffix_power, _)| *suffix_power == power)
+            .map(|(_, suffix)| *suffix)
+            .unwrap_or("?");
         let len = if number < 0.0 { 6 } else { 5 };
+        let digits = format!("{:0.4}", number)[..len].to_owned();
         if suffix == "?" {
-            format!(
-                "{}×10{}",
-                &format!("{:0.4}", number)[..len],
-                superscript_format_number(3 * power)
+            (
+                digits,
+                format!("×10{}", superscript_format_number(3 * power)),
             )
         } else {
-            format!("{}{}", &format!("{:0.4}", number)[..len], suffix)
+            (digits, suffix.to_owned())
+        }
+    }
+}
+
+/// Evaluates arithmetic typed by the user, such as `(1.5Th + 20) / 2`.
+/// Numbers may end with any suffix that pretty_format_number produces.
+pub fn parse_number(text: &str) -> Result<f32, String> {
+    let mut parser = NumberParser {
+        chars: text.chars().filter(|c| !c.is_whitespace()).collect(),
+        position: 0,
+    };
+    let value = parser.sum()?;
+    match parser.peek() {
+        None => Ok(value),
+        Some(c) => Err(format!("Unexpected '{}'", c)),
+    }
+}
+
+struct NumberParser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl NumberParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> String {
+        let start = self.position;
+        while self.peek().map_or(false, &predicate) {
+            self.position += 1;
+        }
+        self.chars[start..self.position].iter().collect()
+    }
+
+    fn sum(&mut self) -> Result<f32, String> {
+        let mut value = self.product()?;
+        loop {
+            match self.peek() {
+                Some('+') => {
+                    self.position += 1;
+                    value += self.product()?;
+                }
+                Some('-') => {
+                    self.position += 1;
+                    value -= self.product()?;
+                }
+                _ => return Ok(value),
+            }
+        }
+    }
+
+    fn product(&mut self) -> Result<f32, String> {
+        let mut value = self.factor()?;
+        loop {
+            match self.peek() {
+                Some('*' | '×') => {
+                    self.position += 1;
+                    value *= self.factor()?;
+                }
+                Some('/') => {
+                    self.position += 1;
+                    value /= self.factor()?;
+                }
+                _ => return Ok(value),
+            }
+        }
+    }
+
+    fn factor(&mut self) -> Result<f32, String> {
+        match self.peek() {
+            Some('-') => {
+                self.position += 1;
+                Ok(-self.factor()?)
+            }
+            Some('(') => {
+                self.position += 1;
+                let value = self.sum()?;
+                if self.peek() != Some(')') {
+                    return Err(format!("Missing ')'"));
+                }
+                self.position += 1;
+                Ok(value)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let digits = self.take_while(|c| c.is_ascii_digit() || c == '.');
+                let value: f32 = digits
+                    .parse()
+                    .map_err(|_| format!("\"{}\" is not a number", digits))?;
+                let suffix = self.take_while(|c| c.is_ascii_alphabetic());
+                if suffix.is_empty() {
+                    return Ok(value);
+                }
+                let Some((power, _)) = NUMBER_SUFFIXES.iter().find(|(_, s)| *s == suffix) else {
+                    return Err(format!("Unknown suffix \"{}\"", suffix));
+                };
+                Ok(value * 1000f32.powi(*power))
+            }
+            Some(c) => Err(format!("Unexpected '{}'", c)),
+            None => Err(format!("Expected a number")),
         }
     }
 }
@@ -126,3 +241,59 @@ impl<Of> IdCreator<Of> {
         id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_number_evaluates_arithmetic() {
+        assert_eq!(parse_number("12"), Ok(12.0));
+        assert_eq!(parse_number(".5"), Ok(0.5));
+        assert_eq!(parse_number("(1 + 2) * -3"), Ok(-9.0));
+        assert_eq!(parse_number("10 / 4 - 1"), Ok(1.5));
+        assert_eq!(parse_number("2 × 3"), Ok(6.0));
+        assert_eq!(parse_number("--2"), Ok(2.0));
+        assert!(parse_number("").is_err());
+        assert!(parse_number("(1").is_err());
+        assert!(parse_number("1 +").is_err());
+        assert!(parse_number("1.2.3").is_err());
+        assert!(parse_number("1 / x").is_err());
+        // Whitespace is ignored, even between digits.
+        assert_eq!(parse_number("1 000"), Ok(1000.0));
+    }
+
+    #[test]
+    fn parse_number_reads_suffixes() {
+        for &(power, suffix) in &NUMBER_SUFFIXES {
+            let expected = 1.5 * 1000f32.powi(power);
+            let parsed = parse_number(&format!("1.5{}", suffix)).unwrap();
+            assert!(
+                (parsed - expected).abs() <= expected * 1e-6,
+                "1.5{} parsed as {}",
+                suffix,
+                parsed
+            );
+        }
+        assert_eq!(parse_number("2Th + 1"), Ok(2001.0));
+        assert!(parse_number("1.5xx").is_err());
+        // Suffixes are case sensitive, since "Th" and "tht" differ only in
+        // case and length.
+        assert!(parse_number("1.5TH").is_err());
+    }
+
+    #[test]
+    fn formatted_numbers_parse_back() {
+        for number in [1500.0, 12.0, -0.25, 3.5e9, 7.0e-6] {
+            let formatted = pretty_format_number(number);
+            let parsed = parse_number(&formatted).unwrap();
+            assert!(
+                (parsed - number).abs() <= number.abs() * 1e-3,
+                "{} was formatted as {} and parsed as {}",
+                number,
+                formatted,
+                parsed
+            );
+        }
+    }
+}
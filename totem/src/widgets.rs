@@ -1,16 +1,72 @@
 use renderer::{Position, Shapes, Size};
-use theme::{NODE_LABEL_HEIGHT, NODE_WIDTH};
 
 use crate::engine::{NodeId, ToolId};
 
 #[derive(Clone, Debug)]
 pub enum BoundingBoxKind {
+    /// The row showing an argument of a node, by its index.
+    Argument(NodeId, usize),
     InvokeTool(ToolId),
+    NodeAction(NodeId, NodeAction),
     Parent(Vec<BoundingBox>),
+    Preview,
+    /// A preview pinned next to the one of the active node, by its index.
+    PinnedPreview(usize),
     SelectNode(usize, NodeId),
     Unused,
 }
 
+/// The buttons shown on the active node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodeAction {
+    InsertAbove,
+    InsertBelow,
+    MoveUp,
+    MoveDown,
+    Duplicate,
+    Delete,
+    /// Shows the node in its own preview. With shift held, shows a snapshot
+    /// of it instead, so that it can be compared to later edits.
+    Pin,
+}
+
+impl NodeAction {
+    pub const ALL: [NodeAction; 7] = [
+        Self::InsertAbove,
+        Self::InsertBelow,
+        Self::MoveUp,
+        Self::MoveDown,
+        Self::Duplicate,
+        Self::Delete,
+        Self::Pin,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::InsertAbove => "Insert Above",
+            Self::InsertBelow => "Insert Below",
+            Self::MoveUp => "Move Up",
+            Self::MoveDown => "Move Down",
+            Self::Duplicate => "Duplicate",
+            Self::Delete => "Delete",
+            Self::Pin => "Pin Preview",
+        }
+    }
+
+    /// The name of the icon on the node's button for this action.
+    pub fn icon(self) -> &'static str {
+        match self {
+            Self::InsertAbove => "table-row-plus-before",
+            Self::InsertBelow => "table-row-plus-after",
+            Self::MoveUp => "arrow-up",
+            Self::MoveDown => "arrow-down",
+            Self::Duplicate => "content-copy",
+            Self::Delete => "delete",
+            Self::Pin => "pin",
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct BoundingBox {
     pub start: Position,
@@ -45,6 +101,22 @@ impl BoundingBox {
         self.end - self.start
     }
 
+    /// Moves the box and everything in it the same way Shapes::transform
+    /// moves what was drawn there.
+    pub fn transform(&mut self, offset: Position, scale: f32) {
+        let point = |pos: Position| Position {
+            x: pos.x * scale + offset.x,
+            y: pos.y * scale + offset.y,
+        };
+        self.start = point(self.start);
+        self.end = point(self.end);
+        if let BoundingBoxKind::Parent(children) = &mut self.kind {
+            for child in children {
+                child.transform(offset, scale);
+            }
+        }
+    }
+
     pub fn contains(&self, pos: Position) -> bool {
         pos.x >= self.start.x && pos.y >= self.start.y && pos.x <= self.end.x && pos.y <= self.end.y
     }